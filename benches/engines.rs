@@ -0,0 +1,70 @@
+// Compares the bit-packed engine, on one thread and on all available threads, against the naive one on a random soup.
+// Run with `cargo bench`.
+use conway::{CellState, Engine, EngineKind, Position, Rule, Topology};
+
+use rand::Rng;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn soup(engine: EngineKind, grid_size: usize, live_cells: &[Position]) -> Box<dyn Engine> {
+    let mut engine = engine
+        .create(grid_size, grid_size, Topology::TORUS)
+        .unwrap();
+    for &position in live_cells {
+        engine.set(position, CellState::Alive);
+    }
+    engine
+}
+
+fn time_generations(engine: &mut dyn Engine, rule: &Rule, generations: usize) -> Duration {
+    let start = Instant::now();
+    for _ in 0..generations {
+        engine.step(rule);
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let rule = Rule::default();
+    let generations = 50;
+    let mut rng = rand::thread_rng();
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+
+    for &grid_size in &[96_usize, 256, 1024] {
+        let size = grid_size as isize;
+        let live_cells: Vec<Position> = (0..size)
+            .flat_map(|y| (0..size).map(move |x| Position { x, y }))
+            .filter(|_| rng.gen_bool(0.5))
+            .collect();
+
+        let mut naive = soup(EngineKind::Naive, grid_size, &live_cells);
+        let mut bit_packed = soup(EngineKind::BitPacked, grid_size, &live_cells);
+        let mut threaded = soup(EngineKind::BitPacked, grid_size, &live_cells);
+        threaded.set_threads(threads);
+
+        // The timings only mean something if all engines agree
+        for _ in 0..5 {
+            let transitions = naive.step(&rule);
+            assert_eq!(transitions, bit_packed.step(&rule));
+            assert_eq!(transitions, threaded.step(&rule));
+        }
+
+        let naive_time = time_generations(naive.as_mut(), &rule, generations);
+        let bit_packed_time = time_generations(bit_packed.as_mut(), &rule, generations);
+        let threaded_time = time_generations(threaded.as_mut(), &rule, generations);
+
+        println!(
+            "{}x{} grid, {} generations: naive {:?}, bit-packed {:?} ({:.1}x faster), {} threads {:?} ({:.1}x faster)",
+            grid_size,
+            grid_size,
+            generations,
+            naive_time,
+            bit_packed_time,
+            naive_time.as_secs_f64() / bit_packed_time.as_secs_f64(),
+            threads,
+            threaded_time,
+            naive_time.as_secs_f64() / threaded_time.as_secs_f64()
+        );
+    }
+}
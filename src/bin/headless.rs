@@ -0,0 +1,433 @@
+// Runs a simulation without a window, for batch jobs and scripts. Loads a pattern file or starts from a random soup,
+// evolves it for a number of generations and writes the final pattern along with a summary of how the population went.
+use conway::census::{self, Census};
+use conway::cycle::{Cycle, Spaceship};
+use conway::rule_table::RuleTable;
+use conway::simulation::{self, CellState, Position};
+use conway::soup::{Soup, Symmetry};
+use conway::{util, EngineKind, Format, Pattern, Rule, Topology};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+const USAGE: &str = "\
+Usage: conway-headless [PATTERN] --generations N [OPTIONS]
+
+Evolves PATTERN, or a random soup if no pattern is given, and writes the final pattern in RLE format.
+
+Options:
+  -g, --generations N   Number of generations to run
+  -r, --rule RULE       Rule such as B3/S23, or a Golly rule table such as Wireworld.rule. Defaults to the
+                        pattern's rule, or Conway's Game of Life
+  -t, --topology NAME   Torus, Bounded plane, Cylinder, Möbius strip, Klein bottle or Cross-surface,
+                        or the left/right and top/bottom edges as in \"Wrapped/Dead\". Defaults to Torus
+  -s, --size WxH        Grid size in cells. Defaults to 96x96
+      --seed N          Seed for the random soup. A random seed is picked and printed if none is given
+      --density PERCENT Share of live cells in the random soup. Defaults to 50
+      --symmetry NAME   Symmetry of the random soup: C1, C2, C4, D2- (top and bottom mirrored),
+                        D2| (left and right mirrored), D4 or D8. Defaults to C1
+  -o, --output PATH     File for the final pattern, in the format its extension stands for. Defaults to stdout
+  -c, --census PATH     File for a census of the objects on the final grid, as .csv or .json
+  -h, --help            Show this message";
+
+struct Options {
+    pattern_path: Option<PathBuf>,
+    generations: usize,
+    rule: Option<Rule>,
+    topology: Topology,
+    width: usize,
+    height: usize,
+    seed: Option<u64>,
+    density: u32,
+    symmetry: Symmetry,
+    output_path: Option<PathBuf>,
+    census_path: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(mut arguments: impl Iterator<Item = String>) -> Result<Options> {
+        let mut options = Options {
+            pattern_path: None,
+            generations: 0,
+            rule: None,
+            topology: Topology::default(),
+            width: 96,
+            height: 96,
+            seed: None,
+            density: 50,
+            symmetry: Symmetry::C1,
+            output_path: None,
+            census_path: None,
+        };
+        let mut generations = None;
+
+        while let Some(argument) = arguments.next() {
+            let mut value = || {
+                arguments
+                    .next()
+                    .ok_or_else(|| anyhow!("{} needs a value", argument))
+            };
+            match argument.as_str() {
+                "-g" | "--generations" => {
+                    let value = value()?;
+                    generations = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid generation count \"{}\"", value))?,
+                    );
+                }
+                "-r" | "--rule" => {
+                    let value = value()?;
+                    let rule = if is_rule_table(Path::new(&value)) {
+                        let text = fs::read_to_string(&value)
+                            .with_context(|| format!("Couldn't read {}", value))?;
+                        let table = RuleTable::parse(&text)
+                            .map_err(|error| anyhow!("Invalid rule table {}: {}", value, error))?;
+                        Rule::from_table(table)
+                    } else {
+                        value
+                            .parse()
+                            .map_err(|error| anyhow!("Invalid rule \"{}\": {}", value, error))?
+                    };
+                    options.rule = Some(rule);
+                }
+                "-t" | "--topology" => {
+                    let value = value()?;
+                    options.topology = Topology::from_name(&value)
+                        .ok_or_else(|| anyhow!("Unknown topology \"{}\"", value))?;
+                }
+                "-s" | "--size" => {
+                    let (width, height) = parse_size(&value()?)?;
+                    options.width = width;
+                    options.height = height;
+                }
+                "--seed" => {
+                    let value = value()?;
+                    let seed = value
+                        .parse()
+                        .with_context(|| format!("Invalid seed \"{}\"", value))?;
+                    options.seed = Some(seed);
+                }
+                "--density" => {
+                    let value = value()?;
+                    options.density = match value.trim_end_matches('%').parse() {
+                        Ok(density) if density <= 100 => density,
+                        _ => bail!(
+                            "Density should be a percentage from 0 to 100, found \"{}\"",
+                            value
+                        ),
+                    };
+                }
+                "--symmetry" => {
+                    let value = value()?;
+                    options.symmetry = Symmetry::from_name(&value)
+                        .ok_or_else(|| anyhow!("Unknown symmetry \"{}\"", value))?;
+                }
+                "-o" | "--output" => options.output_path = Some(PathBuf::from(value()?)),
+                "-c" | "--census" => {
+                    let path = PathBuf::from(value()?);
+                    census_format(&path)?;
+                    options.census_path = Some(path);
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ if argument.starts_with('-') => bail!("Unknown option {}\n\n{}", argument, USAGE),
+                _ if options.pattern_path.is_none() => {
+                    options.pattern_path = Some(PathBuf::from(argument))
+                }
+                _ => bail!("Only one pattern can be given\n\n{}", USAGE),
+            }
+        }
+
+        options.generations = generations
+            .ok_or_else(|| anyhow!("The number of generations is missing\n\n{}", USAGE))?;
+        Ok(options)
+    }
+}
+
+fn parse_size(text: &str) -> Result<(usize, usize)> {
+    let mut parts = text.split(['x', 'X']);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(width), Some(height), None) => match (width.trim().parse(), height.trim().parse()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+            _ => bail!("Invalid grid size \"{}\"", text),
+        },
+        _ => bail!("Grid size should look like \"96x96\", found \"{}\"", text),
+    }
+}
+
+fn pattern_format(path: &Path) -> Result<Format> {
+    Format::from_path(path)
+        .ok_or_else(|| anyhow!("Pattern files need to end in .rle, .cells, .lif or .mc"))
+}
+
+// Function that writes a census in the format the file extension stands for
+fn census_format(path: &Path) -> Result<fn(&Census) -> String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("csv") => Ok(census::write_csv),
+        Some("json") => Ok(census::write_json),
+        _ => bail!("Census files need to end in .csv or .json"),
+    }
+}
+
+fn is_rule_table(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("rule"))
+}
+
+fn read_pattern(path: &Path) -> Result<Pattern> {
+    let format = pattern_format(path)?;
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    let mut pattern = format.parse(&text)?;
+    pattern.read_rule_table(path.parent().unwrap_or_else(|| Path::new("")))?;
+    Ok(pattern)
+}
+
+// Keeps track of the cells from the transitions the simulation sends, the way the UI does
+struct Population {
+    cells: HashMap<Position, CellState>, // Cells that aren't dead
+    live_cell_count: usize,
+    generation: usize,
+    lowest: (usize, usize), // Population and the generation it was reached in
+    highest: (usize, usize),
+    cycle: Option<Cycle>, // Cycle the pattern settled into
+    spaceship: Option<Spaceship>,
+    census: Option<Census>, // Last census the simulation took
+}
+
+impl Population {
+    fn apply(&mut self, message: simulation::Message) {
+        let transitions = match message {
+            simulation::Message::CellTransitions(transitions) => {
+                self.generation += 1;
+                transitions
+            }
+            simulation::Message::SetGeneration(generation, transitions) => {
+                self.generation = generation;
+                transitions
+            }
+            simulation::Message::Cycle(cycle) => {
+                self.cycle = cycle;
+                return;
+            }
+            simulation::Message::Spaceship(spaceship) => {
+                self.spaceship = spaceship;
+                return;
+            }
+            simulation::Message::Census(census) => {
+                self.census = Some(census);
+                return;
+            }
+            _ => return,
+        };
+
+        for (position, state) in transitions {
+            let previous_state = match state {
+                CellState::Dead => self.cells.remove(&position),
+                state => self.cells.insert(position, state),
+            };
+
+            // Dying cells don't count as live
+            match (previous_state.unwrap_or(CellState::Dead), state) {
+                (CellState::Alive, CellState::Alive) => (),
+                (_, CellState::Alive) => self.live_cell_count += 1,
+                (CellState::Alive, _) => self.live_cell_count -= 1,
+                _ => (),
+            }
+        }
+
+        // A new generation 0 starts the records over
+        let count = self.live_cell_count;
+        if self.generation == 0 || count < self.lowest.0 {
+            self.lowest = (count, self.generation);
+        }
+        if self.generation == 0 || count > self.highest.0 {
+            self.highest = (count, self.generation);
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let options = Options::parse(env::args().skip(1))?;
+
+    let pattern = match &options.pattern_path {
+        Some(path) => Some(read_pattern(path)?),
+        None => None,
+    };
+    let rule = options
+        .rule
+        .or_else(|| pattern.as_ref().and_then(|pattern| pattern.rule.clone()))
+        .unwrap_or_default();
+    if let Some(pattern) = &pattern {
+        if pattern.states() > rule.states() as usize {
+            bail!(
+                "The pattern has cells in {} states, but {} only has {}",
+                pattern.states(),
+                rule,
+                rule.states()
+            );
+        }
+    }
+    let soup = Soup {
+        seed: options.seed.unwrap_or_else(rand::random),
+        density: options.density,
+        symmetry: options.symmetry,
+        origin: Position { x: 0, y: 0 },
+        width: options.width,
+        height: options.height,
+    };
+
+    // Generations rules need an engine with room for dying states, and isotropic non-totalistic rules one that tells
+    // arrangements of live neighbors apart
+    let engine = if EngineKind::default().supports_rule(&rule) {
+        EngineKind::default()
+    } else {
+        EngineKind::Naive
+    };
+
+    let (ui, backend) = util::ThreadChannel::new_pair();
+    let config = simulation::Config {
+        width: options.width,
+        height: options.height,
+        target_refresh_rate: 60,
+        evolution_rate: 0,
+        is_paused: true,
+        rule: rule.clone(),
+        engine,
+        topology: options.topology,
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        soup,
+        history_budget: 0, // Nothing goes back in time here
+        pause_on_cycle: false,
+    };
+    let mut simulation = simulation::Simulation::new(ui, config)?;
+
+    let mut population = Population {
+        cells: HashMap::new(),
+        live_cell_count: 0,
+        generation: 0,
+        lowest: (0, 0),
+        highest: (0, 0),
+        cycle: None,
+        spaceship: None,
+        census: None,
+    };
+    let receive = |population: &mut Population| {
+        for message in backend.receive() {
+            population.apply(message);
+        }
+    };
+    receive(&mut population);
+
+    // A pattern replaces the random soup, centered on the grid
+    if let Some(pattern) = &pattern {
+        if pattern.width > options.width || pattern.height > options.height {
+            bail!(
+                "The pattern is {}x{} cells, which doesn't fit on a {}x{} grid. Pass a larger --size",
+                pattern.width,
+                pattern.height,
+                options.width,
+                options.height
+            );
+        }
+        let offset = Position {
+            x: (options.width - pattern.width) as isize / 2,
+            y: (options.height - pattern.height) as isize / 2,
+        };
+        let cells = pattern
+            .cells
+            .iter()
+            .map(|&(position, state)| (position + offset, state))
+            .collect();
+        simulation.handle(simulation::Message::LoadPattern(cells));
+        receive(&mut population);
+    }
+    let initial_population = population.live_cell_count;
+
+    // One generation at a time, so that no generation is missing from the records
+    for _ in 0..options.generations {
+        simulation.handle(simulation::Message::Evolve(1));
+        receive(&mut population);
+    }
+
+    if let Some(path) = &options.census_path {
+        simulation.handle(simulation::Message::TakeCensus);
+        receive(&mut population);
+        let census = population
+            .census
+            .as_ref()
+            .ok_or_else(|| anyhow!("The simulation didn't take a census"))?;
+        fs::write(path, census_format(path)?(census))
+            .with_context(|| format!("Couldn't write {}", path.display()))?;
+    }
+
+    let cells: Vec<(Position, CellState)> = population
+        .cells
+        .iter()
+        .map(|(&position, &state)| (position, state))
+        .collect();
+    let final_pattern = Pattern::from_cells(&cells, Some(rule.clone()));
+
+    let mut summary = vec![];
+    if pattern.is_none() {
+        summary.push(format!("Soup: {}", soup));
+    }
+    summary.push(format!("Rule: {}", rule));
+    summary.push(format!("Topology: {}", options.topology));
+    summary.push(format!("Grid: {}x{}", options.width, options.height));
+    summary.push(format!("Generations: {}", population.generation));
+    summary.push(format!("Initial population: {}", initial_population));
+    summary.push(format!("Final population: {}", population.live_cell_count));
+    summary.push(format!(
+        "Lowest population: {} in generation {}",
+        population.lowest.0, population.lowest.1
+    ));
+    summary.push(format!(
+        "Highest population: {} in generation {}",
+        population.highest.0, population.highest.1
+    ));
+    match population.cycle {
+        Some(Cycle { start, period: 1 }) => {
+            summary.push(format!("Still life since generation {}", start))
+        }
+        Some(Cycle { start, period }) => {
+            summary.push(format!("Period {} since generation {}", period, start))
+        }
+        None => (),
+    }
+    if let Some(spaceship) = population.spaceship {
+        summary.push(format!(
+            "Spaceship since generation {}: {}",
+            spaceship.start, spaceship
+        ));
+    }
+    let summary = summary.join("\n");
+
+    // With the pattern on stdout, the summary goes to stderr so that stdout stays a valid pattern file
+    match &options.output_path {
+        Some(path) => {
+            let format = pattern_format(path)?;
+            fs::write(path, format.write(&final_pattern))
+                .with_context(|| format!("Couldn't write {}", path.display()))?;
+            println!("{}", summary);
+        }
+        None => {
+            print!("{}", Format::Rle.write(&final_pattern));
+            eprintln!("{}", summary);
+        }
+    }
+
+    Ok(())
+}
@@ -1,25 +1,36 @@
 // Feature ideas:
 // - Button to add x random live cells
 // - Fields to input cell and grid size
-// - Click cells to toggle their state
 
-mod simulation;
-use simulation::{Cell, CellState, Position};
 mod style;
-mod util; // Contains channels for inter-thread communication
+
+use conway::census::{self, Census};
+use conway::cycle::{Cycle, Spaceship};
+use conway::macrocell::{self, Macrocell};
+use conway::rule_table::RuleTable;
+use conway::session::{self, Session};
+use conway::simulation::{self, CellState, Position};
+use conway::soup::{Soup, Symmetry};
+use conway::turmite::{Ant, Heading, Turmite};
+use conway::{util, EngineKind, Format, Pattern, Rule, Tiling, Topology};
 
 use iced::{
     button::{self, Button},
-    canvas::{self, Cache, Canvas, Cursor, Frame, Geometry},
-    executor,
+    canvas::{self, event, Cache, Canvas, Cursor, Frame, Geometry, Stroke},
+    executor, mouse,
     slider::{self, Slider},
     text_input::{self, TextInput},
     time, Align, Application, Column, Command, Container, Element, Length, Point, Rectangle, Row,
     Settings, Size, Space, Subscription, Text,
 };
 
+use anyhow::{bail, Result};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 pub fn main() -> iced::Result {
     UI::run(Settings::default())
@@ -36,11 +47,38 @@ struct UI {
 // Types of messages that can be sent between UI functions
 #[derive(Debug, Clone)]
 enum Message {
-    Tick(Instant),
+    Tick,
     EvolutionRateChange(f64),
     TogglePlay,
+    TogglePauseOnCycle,
+    StepBack,
+    SeekTo(usize),
     Evolve(usize),
     SetEvolveCount(usize, String),
+    JumpTo(usize),
+    SetJumpTarget(usize, String),
+    SetRuleText(String),
+    ApplyRule,
+    EngineChange(EngineKind),
+    TopologyChange(Topology),
+    ThreadCountChange(usize),
+    EditCells(Vec<(Position, CellState)>),
+    SetFilePath(String),
+    SetOffsetText(String),
+    OpenPattern,
+    SavePattern,
+    TakeCensus,
+    SetSeedText(String),
+    SetDensityText(String),
+    SymmetryChange(Symmetry),
+    NewSoup,
+    Pan(Position),
+    ResetView,
+    SetTurmiteText(String),
+    AddAnt,             // Lets the next click on the grid place an ant
+    PlaceAnt(Position), // Cell the ant was placed on
+    RemoveAnts,
+    SetAntSteps(usize, String),
 }
 
 impl Application for UI {
@@ -50,48 +88,137 @@ impl Application for UI {
 
     fn new(_flags: ()) -> (UI, Command<Self::Message>) {
         let cell_size = 8;
-        let grid_size = 96;
+        let grid_width: usize = 96;
+        let grid_height: usize = 96;
         let target_refresh_rate = 60; // Please don't set this to 0
         let evolution_rate = 25; // evolutions/(100s)
         let show_grid_lines = true;
         let grid_line_width = 2.0;
         let is_paused = true;
+        let pause_on_cycle = false;
         let evolve_count = 1;
+        let rule = Rule::default();
+        let topology = Topology::default();
+        let history_budget = 64 * 1024 * 1024; // Bytes
+        let max_threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let soup = Soup {
+            seed: rand::random(),
+            density: 50,
+            symmetry: Symmetry::C1,
+            origin: Position { x: 0, y: 0 },
+            width: grid_width,
+            height: grid_height,
+        };
+
+        // HashLife is a lot faster for jumping ahead, but needs powers of two as grid width and height
+        let engine = if EngineKind::HashLife.supports(grid_width, grid_height, topology) {
+            EngineKind::HashLife
+        } else {
+            EngineKind::BitPacked
+        };
+
         let controls = Controls {
             evolution_rate_slider: slider::State::new(),
             evolution_rate,
-            show_grid_lines: true,
             is_paused,
             toggle_play_button: button::State::new(),
+            pause_on_cycle,
+            pause_on_cycle_button: button::State::new(),
             evolve_button: button::State::new(),
+            step_back_button: button::State::new(),
+            timeline_slider: slider::State::new(),
+            history_range: (0, 0),
             evolve_count,
             evolve_input_field: text_input::State::new(),
             evolve_input_text: evolve_count.to_string(),
+            jump_button: button::State::new(),
+            jump_target: 0,
+            jump_input_field: text_input::State::new(),
+            jump_input_text: String::new(),
+            rule: rule.clone(),
+            rule_button: button::State::new(),
+            rule_input_field: text_input::State::new(),
+            rule_input_text: rule.to_string(),
+            rule_error: None,
+            engine,
+            grid_width,
+            grid_height,
+            engine_button: button::State::new(),
+            topology,
+            horizontal_edge_button: button::State::new(),
+            vertical_edge_button: button::State::new(),
+            pan_buttons: Default::default(),
+            reset_view_button: button::State::new(),
+            threads: max_threads,
+            max_threads,
+            thread_button: button::State::new(),
+            file_path_input_field: text_input::State::new(),
+            file_path: String::new(),
+            offset_input_field: text_input::State::new(),
+            offset_text: String::new(),
+            open_button: button::State::new(),
+            save_button: button::State::new(),
+            census_button: button::State::new(),
+            file_status: None,
+            seed_input_field: text_input::State::new(),
+            seed_text: soup.seed.to_string(),
+            density_input_field: text_input::State::new(),
+            density_text: soup.density.to_string(),
+            symmetry: soup.symmetry,
+            symmetry_button: button::State::new(),
+            soup_button: button::State::new(),
+            turmite_input_field: text_input::State::new(),
+            turmite_text: "RL".to_string(),
+            ant_button: button::State::new(),
+            remove_ants_button: button::State::new(),
+            ant_steps_input_field: text_input::State::new(),
+            ant_steps_text: "1".to_string(),
+            turmite: None,
+            ant_status: None,
         };
         let statistics = Statistics {
-            cell_count: grid_size * grid_size,
+            cell_count: Some(grid_width * grid_height),
             live_cell_count: 0,
             generation: 0,
+            soup: Some(soup),
+            cycle: None,
+            spaceship: None,
+            census: None,
+            ant_count: 0,
+            ant_steps: 0,
         };
         let (ui, backend) = util::ThreadChannel::new_pair();
 
+        let config = simulation::Config {
+            width: grid_width,
+            height: grid_height,
+            target_refresh_rate,
+            evolution_rate,
+            is_paused,
+            rule,
+            engine,
+            topology,
+            threads: max_threads,
+            soup,
+            history_budget,
+            pause_on_cycle,
+        };
+        let mut simulation = simulation::Simulation::new(ui, config).unwrap(); // The settings above are fixed, so this only fails if they are changed to something invalid
+
         thread::Builder::new()
             .name("Game of Life Simulation".to_string())
-            .spawn(move || {
-                let mut simulation = simulation::Simulation::new(
-                    ui,
-                    grid_size,
-                    target_refresh_rate,
-                    evolution_rate,
-                    is_paused,
-                );
-                simulation.run();
-            })
+            .spawn(move || simulation.run())
             .unwrap(); // Not sure what to do here besides this unwrap, as I'm not the one calling this outer function
 
         let ui = UI {
             backend,
-            cell_grid: CellGrid::new(cell_size, grid_size, show_grid_lines, grid_line_width),
+            cell_grid: CellGrid::new(
+                cell_size,
+                grid_width,
+                grid_height,
+                show_grid_lines,
+                grid_line_width,
+            ),
             target_refresh_rate,
             controls,
             statistics,
@@ -106,7 +233,7 @@ impl Application for UI {
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
-            Message::Tick(_) => {
+            Message::Tick => {
                 let backend_updates = self.backend.receive();
                 if !backend_updates.is_empty() {
                     self.cell_grid.frame_content.clear();
@@ -114,16 +241,51 @@ impl Application for UI {
                 for update in backend_updates {
                     match update {
                         simulation::Message::CellTransitions(transitions) => {
-                            for (position, state) in transitions {
-                                self.cell_grid.cells[position.y][position.x].state = state;
-
-                                match state {
-                                    CellState::Alive => self.statistics.live_cell_count += 1,
-                                    CellState::Dead => self.statistics.live_cell_count -= 1,
-                                }
-                            }
+                            self.apply_transitions(transitions);
                             self.statistics.generation += 1;
                         }
+                        simulation::Message::SetGeneration(generation, transitions) => {
+                            self.apply_transitions(transitions);
+                            self.statistics.generation = generation;
+                        }
+                        simulation::Message::Reset(population) => {
+                            self.cell_grid.clear();
+                            self.statistics.live_cell_count = population;
+                            self.statistics.generation = 0;
+                            self.statistics.cycle = None;
+                            self.statistics.spaceship = None;
+                        }
+                        simulation::Message::Window(corner, width, height, cells) => {
+                            self.cell_grid.show_window(corner, width, height, cells);
+                        }
+                        simulation::Message::HistoryRange(oldest, newest) => {
+                            self.controls.history_range = (oldest, newest);
+                        }
+                        simulation::Message::Cycle(cycle) => {
+                            // The simulation pauses itself, so only the button needs to catch up
+                            if cycle.is_some() && self.controls.pause_on_cycle {
+                                self.controls.is_paused = true;
+                            }
+                            self.statistics.cycle = cycle;
+                        }
+                        simulation::Message::Spaceship(spaceship) => {
+                            if spaceship.is_some() && self.controls.pause_on_cycle {
+                                self.controls.is_paused = true;
+                            }
+                            self.statistics.spaceship = spaceship;
+                        }
+                        simulation::Message::Census(census) => {
+                            self.statistics.census = Some(census);
+                        }
+                        simulation::Message::Ants(transitions, ants, steps) => {
+                            self.apply_transitions(transitions);
+                            if ants.is_empty() {
+                                self.controls.turmite = None;
+                            }
+                            self.statistics.ant_count = ants.len();
+                            self.statistics.ant_steps = steps;
+                            self.cell_grid.ants = ants;
+                        }
                         _ => (),
                     }
                 }
@@ -137,6 +299,16 @@ impl Application for UI {
                 self.controls.is_paused = !self.controls.is_paused;
                 self.backend.send(simulation::Message::TogglePlay);
             }
+            Message::TogglePauseOnCycle => {
+                self.controls.pause_on_cycle = !self.controls.pause_on_cycle;
+                self.backend.send(simulation::Message::PauseOnCycleChange(
+                    self.controls.pause_on_cycle,
+                ));
+            }
+            Message::StepBack => self.backend.send(simulation::Message::StepBack),
+            Message::SeekTo(generation) => {
+                self.backend.send(simulation::Message::SeekTo(generation));
+            }
             Message::Evolve(generations) => {
                 self.backend.send(simulation::Message::Evolve(generations));
             }
@@ -144,23 +316,157 @@ impl Application for UI {
                 self.controls.evolve_count = count;
                 self.controls.evolve_input_text = text;
             }
+            Message::JumpTo(generation) => {
+                self.backend.send(simulation::Message::JumpTo(generation));
+            }
+            Message::SetJumpTarget(generation, text) => {
+                self.controls.jump_target = generation;
+                self.controls.jump_input_text = text;
+            }
+            Message::SetRuleText(text) => {
+                self.controls.rule_input_text = text;
+            }
+            Message::ApplyRule => match self.controls.rule_input_text.parse::<Rule>() {
+                Ok(rule) if rule.births(0) && !self.controls.engine.is_bounded() => {
+                    self.controls.rule_error = Some(UNBOUNDED_B0_ERROR.to_string());
+                }
+                Ok(rule) => self.set_rule(rule),
+                Err(error) => self.controls.rule_error = Some(error.to_string()),
+            },
+            Message::EngineChange(engine) => {
+                if self.controls.rule.births(0) && !engine.is_bounded() {
+                    self.controls.rule_error = Some(UNBOUNDED_B0_ERROR.to_string());
+                } else {
+                    self.controls.engine = engine;
+                    self.statistics.cell_count = if engine.is_bounded() {
+                        self.cell_grid.reset_view();
+                        self.request_window();
+                        Some(self.cell_grid.width * self.cell_grid.height)
+                    } else {
+                        None
+                    };
+                    self.backend.send(simulation::Message::EngineChange(engine));
+                }
+            }
+            Message::TopologyChange(topology) => {
+                self.controls.topology = topology;
+                if !self.controls.engine.supports(
+                    self.controls.grid_width,
+                    self.controls.grid_height,
+                    topology,
+                ) {
+                    let engine = if EngineKind::BitPacked.supports_rule(&self.controls.rule) {
+                        EngineKind::BitPacked
+                    } else {
+                        EngineKind::Naive
+                    };
+                    self.controls.engine = engine;
+                    self.backend.send(simulation::Message::EngineChange(engine));
+                }
+                self.backend
+                    .send(simulation::Message::TopologyChange(topology));
+            }
+            Message::ThreadCountChange(threads) => {
+                self.controls.threads = threads;
+                self.backend
+                    .send(simulation::Message::ThreadCountChange(threads));
+            }
+            // The simulation sends back the cells that changed, so the grid is only updated once the engine has them
+            Message::EditCells(cells) => {
+                self.backend.send(simulation::Message::EditCells(cells));
+            }
+            Message::SetFilePath(path) => self.controls.file_path = path,
+            Message::SetOffsetText(text) => self.controls.offset_text = text,
+            Message::OpenPattern => {
+                let result = if UI::is_session(&self.controls.file_path) {
+                    self.open_session()
+                } else if UI::is_rule_table(&self.controls.file_path) {
+                    self.open_rule_table()
+                } else {
+                    self.open_pattern()
+                };
+                self.controls.file_status = Some(match result {
+                    Ok(()) => format!("Opened {}", self.controls.file_path),
+                    Err(error) => error.to_string(),
+                });
+            }
+            Message::SavePattern => {
+                let result = if UI::is_session(&self.controls.file_path) {
+                    self.save_session()
+                } else if UI::census_format(&self.controls.file_path).is_some() {
+                    self.save_census()
+                } else {
+                    self.save_pattern()
+                };
+                self.controls.file_status = Some(match result {
+                    Ok(()) => format!("Saved {}", self.controls.file_path),
+                    Err(error) => error.to_string(),
+                });
+            }
+            Message::TakeCensus => self.backend.send(simulation::Message::TakeCensus),
+            Message::SetSeedText(text) => self.controls.seed_text = text,
+            Message::SetDensityText(text) => self.controls.density_text = text,
+            Message::SymmetryChange(symmetry) => self.controls.symmetry = symmetry,
+            Message::NewSoup => {
+                if let Err(error) = self.new_soup() {
+                    self.controls.file_status = Some(error.to_string());
+                }
+            }
+            Message::Pan(offset) => {
+                self.cell_grid.pan(offset);
+                self.request_window();
+            }
+            Message::ResetView => {
+                self.cell_grid.reset_view();
+                self.request_window();
+            }
+            Message::SetTurmiteText(text) => self.controls.turmite_text = text,
+            Message::AddAnt => match self.controls.turmite_text.parse::<Turmite>() {
+                Ok(_) if self.cell_grid.tiling != Tiling::Square => {
+                    self.controls.ant_status = Some(SQUARE_ANTS_ERROR.to_string());
+                }
+                Ok(_) => {
+                    self.cell_grid.places_ant = true;
+                    self.controls.ant_status =
+                        Some("Click the cell to place the ant on".to_string());
+                }
+                Err(error) => self.controls.ant_status = Some(error.to_string()),
+            },
+            Message::PlaceAnt(position) => {
+                self.controls.ant_status = self
+                    .place_ant(position)
+                    .err()
+                    .map(|error| error.to_string());
+            }
+            Message::RemoveAnts => self.remove_ants(),
+            Message::SetAntSteps(steps, text) => {
+                self.controls.ant_steps_text = text;
+                if steps > 0 {
+                    self.backend
+                        .send(simulation::Message::AntStepsChange(steps));
+                }
+            }
         }
 
         // Async command thingy. No touchy.
         Command::none()
     }
 
-    fn view(&mut self) -> Element<Self::Message> {
-        let canvas_width = self.cell_grid.cell_size * self.cell_grid.grid_size
-            + self.cell_grid.line_width as usize;
-        let canvas = Canvas::new(&self.cell_grid)
+    fn view(&mut self) -> Element<'_, Self::Message> {
+        let (canvas_width, canvas_height) = self.cell_grid.canvas_size();
+        let canvas = Canvas::new(&mut self.cell_grid)
             .width(Length::Units(canvas_width as u16))
-            .height(Length::Units(canvas_width as u16));
+            .height(Length::Units(canvas_height as u16));
 
+        let generation = self.statistics.generation;
         let statistics = self.statistics.view();
 
-        let content = Row::new().spacing(10).push(canvas).push(statistics);
-        let (bottom_controls, right_controls) = self.controls.view(canvas_width as u16);
+        let (bottom_controls, right_controls) = self.controls.view(canvas_width as u16, generation);
+        let side = Column::new()
+            .spacing(10)
+            .push(statistics)
+            .push(right_controls);
+        let content = Row::new().spacing(10).push(canvas).push(side);
         let content = Column::new().push(content).push(bottom_controls);
 
         Container::new(content)
@@ -174,24 +480,420 @@ impl Application for UI {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        time::every(Duration::from_micros(1_000_000 / self.target_refresh_rate)).map(Message::Tick)
+        time::every(Duration::from_micros(1_000_000 / self.target_refresh_rate))
+            .map(|_| Message::Tick)
+    }
+}
+
+const UNBOUNDED_B0_ERROR: &str = "Rules with B0 only work on bounded grids";
+const SQUARE_ANTS_ERROR: &str = "Ants only walk on square cells";
+
+impl UI {
+    fn apply_transitions(&mut self, transitions: Vec<(Position, CellState)>) {
+        for (position, state) in transitions {
+            let previous_state = self.cell_grid.set(position, state);
+
+            // Dying cells don't count as live
+            match (previous_state, state) {
+                (CellState::Alive, CellState::Alive) => (),
+                (_, CellState::Alive) => self.statistics.live_cell_count += 1,
+                (CellState::Alive, _) => self.statistics.live_cell_count -= 1,
+                _ => (),
+            }
+        }
+    }
+
+    // After a macrocell load, the UI only knows the cells it was shown, so it asks for those that came into view
+    fn request_window(&mut self) {
+        if self.cell_grid.known_windows.is_some() {
+            let (corner, width, height) = self.cell_grid.window();
+            self.backend
+                .send(simulation::Message::ShowWindow(corner, width, height));
+        }
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        self.controls.rule = rule.clone();
+        self.controls.rule_input_text = rule.to_string();
+        self.controls.rule_error = None;
+        self.cell_grid.state_count = rule.states();
+        self.cell_grid.cycles_states = rule.table().is_some();
+        self.cell_grid.palette = style::Palette::new(&rule, self.ant_colors());
+        self.cell_grid.frame_content.clear();
+        if rule.tiling() != Tiling::Square && self.controls.turmite.is_some() {
+            self.remove_ants();
+            self.controls.ant_status = Some(SQUARE_ANTS_ERROR.to_string());
+        }
+        if self.cell_grid.tiling != rule.tiling() {
+            // The cells shown in each row shift with the tiling
+            self.cell_grid.tiling = rule.tiling();
+            self.cell_grid.pan(Position { x: 0, y: 0 });
+            self.request_window();
+        }
+        // Dying states need an engine that stores more than a bit per cell, and arrangements of live neighbors one
+        // that looks at more than their number
+        if !self.controls.engine.supports_rule(&rule)
+            || !self.controls.engine.supports_states(self.ant_colors())
+        {
+            let (width, height) = (self.controls.grid_width, self.controls.grid_height);
+            let engine = if !self.controls.engine.is_bounded() {
+                EngineKind::Sparse
+            } else if EngineKind::HashLife.supports(width, height, self.controls.topology)
+                && EngineKind::HashLife.supports_rule(&rule)
+                && EngineKind::HashLife.supports_states(self.ant_colors())
+            {
+                EngineKind::HashLife
+            } else {
+                EngineKind::Naive
+            };
+            self.update(Message::EngineChange(engine));
+        }
+        self.backend.send(simulation::Message::RuleChange(rule));
+    }
+
+    // Puts an ant facing north on the cell. All ants follow the turmite that was added last, and turmites with more
+    // than two colors need an engine that stores more than a bit per cell
+    fn place_ant(&mut self, position: Position) -> Result<()> {
+        let turmite: Turmite = self.controls.turmite_text.parse()?;
+        self.controls.turmite = Some(turmite.clone());
+        if !self.controls.engine.supports_states(turmite.colors()) {
+            let engine = if self.controls.engine.is_bounded() {
+                EngineKind::Naive
+            } else {
+                EngineKind::Sparse
+            };
+            self.update(Message::EngineChange(engine));
+        }
+        self.cell_grid.palette = style::Palette::new(&self.controls.rule, turmite.colors());
+        self.cell_grid.frame_content.clear();
+        self.backend.send(simulation::Message::AddAnt(
+            turmite,
+            Ant::new(position, Heading::North),
+        ));
+        Ok(())
+    }
+
+    fn remove_ants(&mut self) {
+        self.controls.turmite = None;
+        self.cell_grid.places_ant = false;
+        self.backend.send(simulation::Message::RemoveAnts);
+    }
+
+    // Colors the ants on the grid paint cells in, which are two without any ants
+    fn ant_colors(&self) -> u8 {
+        self.controls.turmite.as_ref().map_or(2, Turmite::colors)
+    }
+
+    // Replaces the grid with the pattern file. Its top left corner goes to the offset, or it is centered without one
+    fn open_pattern(&mut self) -> Result<()> {
+        let format = UI::pattern_format(&self.controls.file_path)?;
+        let text = fs::read_to_string(&self.controls.file_path)?;
+        let mut pattern = match format {
+            // Macrocell patterns too large for the grid stay a quadtree, which only the cells in view are taken from
+            Format::Macrocell => {
+                let macrocell = macrocell::parse(&text)?;
+                let fits = macrocell.bounds().is_none_or(|(min, max)| {
+                    max.x - min.x < self.cell_grid.width as isize
+                        && max.y - min.y < self.cell_grid.height as isize
+                });
+                if !fits && macrocell.is_multi_state() {
+                    bail!("Multi-state macrocell patterns have to fit the grid, as HashLife only runs two states");
+                }
+                if !fits {
+                    return self.open_macrocell(macrocell);
+                }
+                macrocell.to_pattern()
+            }
+            format => format.parse(&text)?,
+        };
+        let path = Path::new(&self.controls.file_path);
+        pattern.read_rule_table(path.parent().unwrap_or_else(|| Path::new("")))?;
+
+        let offset = match self.controls.offset_text.trim() {
+            "" => {
+                let width = self.cell_grid.width as isize - pattern.width as isize;
+                let height = self.cell_grid.height as isize - pattern.height as isize;
+                self.cell_grid.origin
+                    + Position {
+                        x: width / 2,
+                        y: height / 2,
+                    }
+            }
+            text => match text
+                .split(',')
+                .map(|part| part.trim().parse())
+                .collect::<Vec<_>>()[..]
+            {
+                [Ok(x), Ok(y)] => Position { x, y },
+                _ => bail!("Offset should be two numbers like \"10, 20\""),
+            },
+        };
+
+        let rule = pattern.rule.as_ref().unwrap_or(&self.controls.rule);
+        if pattern.states() > rule.states() as usize {
+            bail!(
+                "The pattern has cells in {} states, but {} only has {}",
+                pattern.states(),
+                rule,
+                rule.states()
+            );
+        }
+        if let Some(rule) = pattern.rule {
+            if rule.births(0) && !self.controls.engine.is_bounded() {
+                bail!(UNBOUNDED_B0_ERROR);
+            }
+            self.set_rule(rule);
+        }
+
+        let cells = pattern
+            .cells
+            .iter()
+            .map(|&(position, state)| (position + offset, state))
+            .collect();
+        self.statistics.soup = None;
+        self.statistics.census = None;
+        self.backend.send(simulation::Message::LoadPattern(cells));
+        Ok(())
+    }
+
+    // Moves to the unbounded HashLife engine with the macrocell's quadtree and centers the view on the pattern
+    fn open_macrocell(&mut self, macrocell: Macrocell) -> Result<()> {
+        let rule = match &macrocell.rule {
+            Some(rulestring) => match rulestring.parse::<Rule>() {
+                Ok(rule) => rule,
+                Err(_) => bail!(
+                    "HashLife can't run the macrocell's rule \"{}\", only rulestrings of two-state rules",
+                    rulestring
+                ),
+            },
+            None => self.controls.rule.clone(),
+        };
+        if rule.births(0) {
+            bail!(UNBOUNDED_B0_ERROR);
+        }
+        if !EngineKind::UnboundedHashLife.supports_rule(&rule) {
+            bail!(
+                "HashLife only runs two-state rules of live neighbors on squares, not {}",
+                rule
+            );
+        }
+        self.set_rule(rule);
+
+        // The center of the quadtree ends up on (0, 0)
+        if let Some((min, max)) = macrocell.bounds() {
+            let half = 1 << (macrocell.level() - 1);
+            let origin = Position {
+                x: (min.x + max.x - self.cell_grid.width as isize) / 2 - half,
+                y: (min.y + max.y - self.cell_grid.height as isize) / 2 - half,
+            };
+            self.cell_grid.pan(Position {
+                x: origin.x - self.cell_grid.origin.x,
+                y: origin.y - self.cell_grid.origin.y,
+            });
+        }
+
+        self.controls.engine = EngineKind::UnboundedHashLife;
+        self.statistics.cell_count = None;
+        self.statistics.soup = None;
+        self.statistics.census = None;
+        let (corner, width, height) = self.cell_grid.window();
+        self.backend.send(simulation::Message::LoadMacrocell(
+            macrocell, corner, width, height,
+        ));
+        Ok(())
+    }
+
+    fn save_pattern(&self) -> Result<()> {
+        let format = UI::pattern_format(&self.controls.file_path)?;
+        if self.cell_grid.known_windows.is_some() {
+            bail!("Only the cells in view are known of macrocell patterns too large for the grid");
+        }
+        let cells: Vec<(Position, CellState)> = self
+            .cell_grid
+            .states
+            .iter()
+            .map(|(&position, &state)| (position, state))
+            .collect();
+        let pattern = Pattern::from_cells(&cells, Some(self.controls.rule.clone()));
+        fs::write(&self.controls.file_path, format.write(&pattern))?;
+        Ok(())
+    }
+
+    // Replaces the grid with a soup from the seed and density fields, filling the visible part of the grid.
+    // Without a seed, a random one is picked
+    fn new_soup(&mut self) -> Result<()> {
+        let seed = match self.controls.seed_text.trim() {
+            "" => rand::random(),
+            text => match text.parse() {
+                Ok(seed) => seed,
+                Err(_) => bail!("Seed should be a whole number from 0 to {}", u64::MAX),
+            },
+        };
+        let density = match self
+            .controls
+            .density_text
+            .trim()
+            .trim_end_matches('%')
+            .parse()
+        {
+            Ok(density) if density <= 100 => density,
+            _ => bail!("Density should be a percentage from 0 to 100"),
+        };
+
+        let soup = Soup {
+            seed,
+            density,
+            symmetry: self.controls.symmetry,
+            origin: self.cell_grid.origin,
+            width: self.cell_grid.width,
+            height: self.cell_grid.height,
+        };
+        self.load_soup(soup);
+        Ok(())
+    }
+
+    fn load_soup(&mut self, soup: Soup) {
+        self.controls.seed_text = soup.seed.to_string();
+        self.controls.density_text = soup.density.to_string();
+        self.controls.symmetry = soup.symmetry;
+        self.statistics.soup = Some(soup);
+        self.statistics.census = None;
+        self.backend.send(simulation::Message::NewSoup(soup));
+    }
+
+    // Recreates the soup of the session and runs it to the session's generation
+    fn open_session(&mut self) -> Result<()> {
+        let text = fs::read_to_string(&self.controls.file_path)?;
+        let session = session::parse(&text)?;
+
+        let (width, height) = (self.cell_grid.width, self.cell_grid.height);
+        if self.controls.engine.is_bounded() && (session.width, session.height) != (width, height) {
+            bail!(
+                "The session ran on a {}x{} grid, but this grid is {}x{}",
+                session.width,
+                session.height,
+                width,
+                height
+            );
+        }
+        if session.rule.births(0) && !self.controls.engine.is_bounded() {
+            bail!(UNBOUNDED_B0_ERROR);
+        }
+
+        self.set_rule(session.rule);
+        if session.topology != self.controls.topology {
+            let _ = self.update(Message::TopologyChange(session.topology));
+        }
+        self.load_soup(session.soup);
+        self.backend
+            .send(simulation::Message::JumpTo(session.generation));
+        Ok(())
+    }
+
+    // Only grids that started out as a soup can be saved as a session, as a session doesn't list any cells
+    fn save_session(&self) -> Result<()> {
+        let soup = match self.statistics.soup {
+            Some(soup) => soup,
+            None => bail!("Only grids that started out as a random soup can be saved as a session"),
+        };
+        if self.controls.rule.table().is_some() {
+            bail!("Sessions can't refer to rule tables, only to rulestrings");
+        }
+        let session = Session {
+            rule: self.controls.rule.clone(),
+            topology: self.controls.topology,
+            width: self.cell_grid.width,
+            height: self.cell_grid.height,
+            soup,
+            generation: self.statistics.generation,
+        };
+        fs::write(&self.controls.file_path, session::write(&session))?;
+        Ok(())
+    }
+
+    fn save_census(&self) -> Result<()> {
+        let census = match &self.statistics.census {
+            Some(census) => census,
+            None => bail!("Take a census before saving it"),
+        };
+        let write = UI::census_format(&self.controls.file_path).unwrap();
+        fs::write(&self.controls.file_path, write(census))?;
+        Ok(())
+    }
+
+    // Function that writes a census in the format the file extension stands for, if it stands for one
+    fn census_format(path: &str) -> Option<fn(&Census) -> String> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(census::write_csv),
+            "json" => Some(census::write_json),
+            _ => None,
+        }
+    }
+
+    // Switches to the rule of a Golly rule table, which keeps the cells on the grid
+    fn open_rule_table(&mut self) -> Result<()> {
+        let text = fs::read_to_string(&self.controls.file_path)?;
+        let rule = Rule::from_table(RuleTable::parse(&text)?);
+        if rule.births(0) && !self.controls.engine.is_bounded() {
+            bail!(UNBOUNDED_B0_ERROR);
+        }
+        self.set_rule(rule);
+        Ok(())
+    }
+
+    fn is_rule_table(path: &str) -> bool {
+        Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("rule"))
+    }
+
+    fn is_session(path: &str) -> bool {
+        Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("session"))
+    }
+
+    fn pattern_format(path: &str) -> Result<Format> {
+        match Format::from_path(Path::new(path)) {
+            Some(format) => Ok(format),
+            None => bail!("Files need to end in .rle, .cells, .lif, .mc, .rule or .session, or .csv or .json for a census"),
+        }
     }
 }
 
 struct CellGrid {
-    cell_size: usize, // Edge length of cell in pixels
-    grid_size: usize, // Edge length of grid in cells
-    cells: Vec<Vec<Cell>>,
+    cell_size: usize,                     // Edge length of cell in pixels
+    width: usize,                         // Width of grid in cells
+    height: usize,                        // Height of grid in cells
+    cells: Vec<Vec<Cell>>,                // Cells that are visible on the canvas
+    states: HashMap<Position, CellState>, // All cells that aren't dead, including those outside the visible window
+    known_windows: Option<Vec<(Position, usize, usize)>>, // Windows whose cells are known after a macrocell load
+    state_count: u16,                                     // Number of states of the rule
+    cycles_states: bool, // Clicks go through every state of a rule table instead of toggling
+    palette: style::Palette, // Colors of the states of the rule
+    tiling: Tiling,      // Shape of the cells of the rule
+    origin: Position,    // Position of the top left visible cell
+    ants: Vec<Ant>,      // Ants drawn over the cells
+    places_ant: bool,    // The next click places an ant instead of editing a cell
+    brush: Option<CellState>, // State that is drawn while the mouse button is held down
+    last_edited: Option<Position>, // Cell the mouse was over at the last edit
     frame_content: Cache,
     show_grid_lines: bool,
     line_width: f32,
 }
 
 impl CellGrid {
-    fn new(cell_size: usize, grid_size: usize, show_grid_lines: bool, line_width: f32) -> Self {
-        let cells: Vec<Vec<Cell>> = (0..grid_size)
+    fn new(
+        cell_size: usize,
+        width: usize,
+        height: usize,
+        show_grid_lines: bool,
+        line_width: f32,
+    ) -> Self {
+        let cells: Vec<Vec<Cell>> = (0..height as isize)
             .map(|y| {
-                (0..grid_size)
+                (0..width as isize)
                     .map(|x| Cell::new(CellState::Dead, Position { x, y }))
                     .collect()
             })
@@ -199,69 +901,486 @@ impl CellGrid {
 
         Self {
             cell_size,
-            grid_size,
+            width,
+            height,
             cells,
+            states: HashMap::new(),
+            known_windows: None,
+            state_count: 2,
+            cycles_states: false,
+            palette: style::Palette::default(),
+            tiling: Tiling::Square,
+            origin: Position { x: 0, y: 0 },
+            ants: vec![],
+            places_ant: false,
+            brush: None,
+            last_edited: None,
             frame_content: Cache::new(),
             show_grid_lines,
             line_width,
         }
     }
+
+    // Returns the state the cell was in before. Cells that weren't shown since a macrocell load are still as they
+    // were loaded, alive or dead, so one that changes was alive unless it comes to life. Dead cells are kept then, so
+    // that they are known from then on
+    fn set(&mut self, position: Position, state: CellState) -> CellState {
+        let previous_state = match state {
+            CellState::Dead if self.known_windows.is_none() => self.states.remove(&position),
+            state => self.states.insert(position, state),
+        };
+        let is_known = |(corner, width, height): &(Position, usize, usize)| {
+            (corner.x..corner.x + *width as isize).contains(&position.x)
+                && (corner.y..corner.y + *height as isize).contains(&position.y)
+        };
+        let previous_state = match (previous_state, &self.known_windows) {
+            (None, Some(windows)) if !windows.iter().any(is_known) => match state {
+                CellState::Alive => CellState::Dead,
+                _ => CellState::Alive,
+            },
+            (previous_state, _) => previous_state.unwrap_or(CellState::Dead),
+        };
+
+        let slot = self.slot_of(position);
+        if (0..self.width as isize).contains(&slot.x) && (0..self.height as isize).contains(&slot.y)
+        {
+            self.cells[slot.y as usize][slot.x as usize].state = state;
+        }
+        previous_state
+    }
+
+    // Drops all cells, which a macrocell load follows up with the cells in the visible window
+    fn clear(&mut self) {
+        self.states.clear();
+        self.known_windows = None;
+        self.pan(Position { x: 0, y: 0 });
+    }
+
+    // Replaces the cells in a window by those of a loaded macrocell
+    fn show_window(
+        &mut self,
+        corner: Position,
+        width: usize,
+        height: usize,
+        cells: Vec<(Position, CellState)>,
+    ) {
+        let (xs, ys) = (
+            corner.x..corner.x + width as isize,
+            corner.y..corner.y + height as isize,
+        );
+        self.states
+            .retain(|position, _| !xs.contains(&position.x) || !ys.contains(&position.y));
+        self.states.extend(cells);
+        self.known_windows
+            .get_or_insert_with(Vec::new)
+            .push((corner, width, height));
+        self.pan(Position { x: 0, y: 0 });
+    }
+
+    // Smallest rectangle holding all visible cells, as its top left corner, width and height
+    fn window(&self) -> (Position, usize, usize) {
+        let (right, bottom) = (self.width as isize - 1, self.height as isize - 1);
+        let corners: Vec<Position> = [(0, 0), (right, 0), (0, bottom), (right, bottom)]
+            .iter()
+            .map(|&(x, y)| self.position_of(Position { x, y }))
+            .collect();
+        let min_x = corners.iter().map(|corner| corner.x).min().unwrap();
+        let max_x = corners.iter().map(|corner| corner.x).max().unwrap();
+        let corner = Position {
+            x: min_x,
+            y: self.origin.y,
+        };
+        (corner, (max_x - min_x + 1) as usize, self.height)
+    }
+
+    // Position of the cell shown in a slot of the canvas. Hexagons are squares of a skewed grid, so each row shows
+    // cells further right than the row above every other row to keep the visible window rectangular
+    fn position_of(&self, slot: Position) -> Position {
+        let y = self.origin.y + slot.y;
+        let x = match self.tiling {
+            Tiling::Hexagonal => self.origin.x + slot.x + skew(y) - skew(self.origin.y),
+            _ => self.origin.x + slot.x,
+        };
+        Position { x, y }
+    }
+
+    // Slot of the canvas a cell is shown in, which may lie outside the canvas
+    fn slot_of(&self, position: Position) -> Position {
+        let x = match self.tiling {
+            Tiling::Hexagonal => position.x - skew(position.y) + skew(self.origin.y),
+            _ => position.x,
+        };
+        Position {
+            x: x - self.origin.x,
+            y: position.y - self.origin.y,
+        }
+    }
+
+    // Hexagons and triangles stick out past the edges of the squares they replace, so the canvas grows to fit them
+    fn canvas_size(&self) -> (usize, usize) {
+        let (width, height) = (self.cell_size * self.width, self.cell_size * self.height);
+        let (width, height) = match self.tiling {
+            Tiling::Square => (width, height),
+            Tiling::Hexagonal => (width + self.cell_size / 2, height + self.cell_size / 4),
+            Tiling::Triangular => (width + self.cell_size, height),
+        };
+        (
+            width + self.line_width as usize,
+            height + self.line_width as usize,
+        )
+    }
+
+    // Moves the visible window across the plane
+    fn pan(&mut self, offset: Position) {
+        self.origin = self.origin + offset;
+
+        for row in 0..self.cells.len() {
+            for column in 0..self.cells[row].len() {
+                let position = self.position_of(self.cells[row][column].position);
+                self.cells[row][column].state = self
+                    .states
+                    .get(&position)
+                    .copied()
+                    .unwrap_or(CellState::Dead);
+            }
+        }
+        self.frame_content.clear();
+    }
+
+    fn reset_view(&mut self) {
+        self.pan(Position {
+            x: -self.origin.x,
+            y: -self.origin.y,
+        });
+    }
+
+    // Cell under the cursor. Cells are drawn half a grid line in from their top left corner
+    fn cell_at(&self, bounds: Rectangle, cursor: Cursor) -> Option<Position> {
+        let point = cursor.position_in(&bounds)?;
+        let offset = self.line_width / 2.0;
+        let size = self.cell_size as f32;
+        let (x, y) = ((point.x - offset) / size, (point.y - offset) / size);
+
+        let slot = match self.tiling {
+            Tiling::Square => Position {
+                x: x.floor() as isize,
+                y: y.floor() as isize,
+            },
+            // Hexagons are the points closest to their center
+            Tiling::Hexagonal => {
+                let y = y - HEXAGON_MARGIN;
+                let row = y.floor() as isize;
+                (row - 1..=row + 1)
+                    .flat_map(|row| {
+                        let shift = self.row_shift(row);
+                        let column = (x - shift).floor() as isize;
+                        (column - 1..=column + 1).map(move |column| {
+                            let (dx, dy) =
+                                (x - (column as f32 + 0.5 + shift), y - (row as f32 + 0.5));
+                            (Position { x: column, y: row }, dx * dx + dy * dy)
+                        })
+                    })
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(slot, _)| slot)?
+            }
+            // A triangle covers the middle of its column down to a depth as wide as it is there, and its neighbors
+            // cover the rest
+            Tiling::Triangular => {
+                let x = x - 0.5;
+                let (row, column) = (y.floor() as isize, x.floor() as isize);
+                let depth = y - row as f32;
+                let half_width =
+                    if Tiling::points_up(self.position_of(Position { x: column, y: row })) {
+                        depth
+                    } else {
+                        1.0 - depth
+                    };
+                let dx = x - (column as f32 + 0.5);
+                let column = if dx.abs() <= half_width {
+                    column
+                } else if dx < 0.0 {
+                    column - 1
+                } else {
+                    column + 1
+                };
+                Position { x: column, y: row }
+            }
+        };
+
+        if (0..self.width as isize).contains(&slot.x) && (0..self.height as isize).contains(&slot.y)
+        {
+            Some(self.position_of(slot))
+        } else {
+            None
+        }
+    }
+
+    // How far right the hexagons in a row of slots are drawn, in cells
+    fn row_shift(&self, row: isize) -> f32 {
+        if (self.origin.y + row).rem_euclid(2) == 1 {
+            0.5
+        } else {
+            0.0
+        }
+    }
+
+    // Arrow on the ant's cell that points where it is heading, or None if the cell isn't visible
+    fn ant_path(&self, ant: &Ant) -> Option<canvas::Path> {
+        let slot = self.slot_of(ant.position);
+        if !(0..self.width as isize).contains(&slot.x)
+            || !(0..self.height as isize).contains(&slot.y)
+        {
+            return None;
+        }
+
+        let size = self.cell_size as f32;
+        let offset = self.line_width / 2.0;
+        let center = Point::new(
+            offset + (slot.x as f32 + 0.5) * size,
+            offset + (slot.y as f32 + 0.5) * size,
+        );
+        let ahead = ant.heading.offset();
+        let (dx, dy) = (ahead.x as f32 * size, ahead.y as f32 * size);
+        let tip = Point::new(center.x + 0.4 * dx, center.y + 0.4 * dy);
+        let left = Point::new(
+            center.x - 0.3 * dx + 0.35 * dy,
+            center.y - 0.3 * dy - 0.35 * dx,
+        );
+        let right = Point::new(
+            center.x - 0.3 * dx - 0.35 * dy,
+            center.y - 0.3 * dy + 0.35 * dx,
+        );
+
+        Some(canvas::Path::new(|builder| {
+            builder.move_to(tip);
+            builder.line_to(left);
+            builder.line_to(right);
+            builder.close();
+        }))
+    }
+
+    // Cells on a straight line from one cell to another, so fast drags don't leave gaps
+    fn line(from: Position, to: Position) -> Vec<Position> {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        let steps = dx.abs().max(dy.abs()).max(1);
+        (1..=steps)
+            .map(|step| Position {
+                x: from.x + (dx as f32 * step as f32 / steps as f32).round() as isize,
+                y: from.y + (dy as f32 * step as f32 / steps as f32).round() as isize,
+            })
+            .collect()
+    }
 }
 
-impl canvas::Program<Message> for &CellGrid {
+impl canvas::Program<Message> for CellGrid {
+    // Clicking a cell toggles it, dragging draws the state the first cell was toggled to
+    fn update(
+        &mut self,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let position = self.cell_at(bounds, cursor);
+        match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if let (true, Some(position)) = (self.places_ant, position) {
+                    self.places_ant = false;
+                    return (event::Status::Captured, Some(Message::PlaceAnt(position)));
+                }
+                if let Some(position) = position {
+                    let state = self
+                        .states
+                        .get(&position)
+                        .copied()
+                        .unwrap_or(CellState::Dead);
+                    let state = if self.cycles_states {
+                        state.cycled(self.state_count)
+                    } else {
+                        state.toggled()
+                    };
+                    self.brush = Some(state);
+                    self.last_edited = Some(position);
+                    let message = Message::EditCells(vec![(position, state)]);
+                    return (event::Status::Captured, Some(message));
+                }
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let (Some(state), Some(position)) = (self.brush, position) {
+                    let from = self.last_edited.unwrap_or(position);
+                    if from != position {
+                        self.last_edited = Some(position);
+                        let cells = CellGrid::line(from, position)
+                            .into_iter()
+                            .map(|position| (position, state))
+                            .collect();
+                        return (event::Status::Captured, Some(Message::EditCells(cells)));
+                    }
+                }
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                self.brush = None;
+                self.last_edited = None;
+            }
+            _ => (),
+        }
+        (event::Status::Ignored, None)
+    }
+
+    fn mouse_interaction(&self, bounds: Rectangle, cursor: Cursor) -> mouse::Interaction {
+        match self.cell_at(bounds, cursor) {
+            Some(_) => mouse::Interaction::Crosshair,
+            None => mouse::Interaction::default(),
+        }
+    }
+
     fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
         let frame_conent = self.frame_content.draw(bounds.size(), |frame| {
             for row in &self.cells {
                 for cell in row {
-                    cell.draw(frame, self.cell_size, self.line_width / 2.0);
+                    cell.draw(frame, self);
                 }
             }
 
-            if self.show_grid_lines {
-                let line_size = (self.grid_size * self.cell_size) as f32 + self.line_width;
-
+            // Hexagons and triangles outline themselves
+            if self.show_grid_lines && self.tiling != Tiling::Square {
+                let stroke = Stroke {
+                    color: style::GRID_LINE,
+                    width: self.line_width,
+                    ..Stroke::default()
+                };
+                for cell in self.cells.iter().flatten() {
+                    if let Some(path) = cell.path(self) {
+                        frame.stroke(&path, stroke);
+                    }
+                }
+            } else if self.show_grid_lines {
                 let vertical_size = Size {
                     width: self.line_width,
-                    height: line_size,
+                    height: (self.height * self.cell_size) as f32 + self.line_width,
                 };
                 let horizontal_size = Size {
-                    width: line_size,
+                    width: (self.width * self.cell_size) as f32 + self.line_width,
                     height: self.line_width,
                 };
 
-                for line in 0..=self.grid_size {
-                    let vertical_top_left =
-                        Point::from(Position { x: line, y: 0 } * self.cell_size);
-                    let horizontal_top_left =
-                        Point::from(Position { x: 0, y: line } * self.cell_size);
-
-                    frame.fill_rectangle(vertical_top_left, vertical_size, style::GRID_LINE);
-                    frame.fill_rectangle(horizontal_top_left, horizontal_size, style::GRID_LINE);
+                for x in 0..=self.width as isize {
+                    let top_left = to_point(Position { x, y: 0 } * self.cell_size);
+                    frame.fill_rectangle(top_left, vertical_size, style::GRID_LINE);
+                }
+                for y in 0..=self.height as isize {
+                    let top_left = to_point(Position { x: 0, y } * self.cell_size);
+                    frame.fill_rectangle(top_left, horizontal_size, style::GRID_LINE);
                 }
             }
         });
 
-        vec![frame_conent]
+        // Ants move every update, so they are drawn anew each time
+        let mut overlay = Frame::new(bounds.size());
+        for ant in &self.ants {
+            if let Some(path) = self.ant_path(ant) {
+                overlay.fill(&path, style::ANT);
+            }
+        }
+
+        vec![frame_conent, overlay.into_geometry()]
     }
 }
 
+// Cell on the canvas
+#[derive(Debug)]
+struct Cell {
+    position: Position, // Top left corner position
+    state: CellState,
+}
+
 impl Cell {
-    fn draw(&self, frame: &mut Frame, size: usize, offset: f32) {
-        let mut top_left = Point::from(self.position * size);
-        top_left.x += offset;
-        top_left.y += offset;
-        let size = size as f32;
-        let size = Size {
-            width: size,
-            height: size,
-        };
+    fn new(state: CellState, position: Position) -> Cell {
+        Cell { state, position }
+    }
+
+    fn draw(&self, frame: &mut Frame, grid: &CellGrid) {
+        let color = grid.palette.cell(self.state);
 
-        let color = match self.state {
-            CellState::Alive => style::LIVE_CELL,
-            CellState::Dead => style::DEAD_CELL,
+        match self.path(grid) {
+            Some(path) => frame.fill(&path, color),
+            None => {
+                let offset = grid.line_width / 2.0;
+                let mut top_left = to_point(self.position * grid.cell_size);
+                top_left.x += offset;
+                top_left.y += offset;
+                let size = grid.cell_size as f32;
+                let size = Size {
+                    width: size,
+                    height: size,
+                };
+                frame.fill_rectangle(top_left, size, color);
+            }
+        }
+    }
+
+    // Outline of the cell on hexagons and triangles, None on squares. Hexagons in neighboring rows interlock, and
+    // triangles reach half a cell into the columns on either side
+    fn path(&self, grid: &CellGrid) -> Option<canvas::Path> {
+        let size = grid.cell_size as f32;
+        let offset = grid.line_width / 2.0;
+        let (column, row) = (self.position.x as f32, self.position.y as f32);
+        let corners = match grid.tiling {
+            Tiling::Square => return None,
+            Tiling::Hexagonal => {
+                let x = offset + (column + 0.5 + grid.row_shift(self.position.y)) * size;
+                let y = offset + (row + 0.5 + HEXAGON_MARGIN) * size;
+                let (half_width, tip, side) = (0.5 * size, 0.625 * size, 0.375 * size);
+                vec![
+                    Point::new(x, y - tip),
+                    Point::new(x + half_width, y - side),
+                    Point::new(x + half_width, y + side),
+                    Point::new(x, y + tip),
+                    Point::new(x - half_width, y + side),
+                    Point::new(x - half_width, y - side),
+                ]
+            }
+            Tiling::Triangular => {
+                let x = offset + (column + 1.0) * size;
+                let (top, bottom) = (offset + row * size, offset + (row + 1.0) * size);
+                if Tiling::points_up(grid.position_of(self.position)) {
+                    vec![
+                        Point::new(x, top),
+                        Point::new(x + size, bottom),
+                        Point::new(x - size, bottom),
+                    ]
+                } else {
+                    vec![
+                        Point::new(x - size, top),
+                        Point::new(x + size, top),
+                        Point::new(x, bottom),
+                    ]
+                }
+            }
         };
 
-        frame.fill_rectangle(top_left, size, color);
+        Some(canvas::Path::new(|builder| {
+            builder.move_to(corners[0]);
+            for &corner in &corners[1..] {
+                builder.line_to(corner);
+            }
+            builder.close();
+        }))
+    }
+}
+
+// Hexagons reach this far above and below the row they are in, in cells. Their points are where rows of hexagons
+// meet cells closer to the next row's centers
+const HEXAGON_MARGIN: f32 = 0.125;
+
+// Offset of the hexagon rows that keeps the visible window rectangular, see CellGrid::position_of
+fn skew(y: isize) -> isize {
+    (y + 1).div_euclid(2)
+}
+
+fn to_point(position: Position) -> Point {
+    Point {
+        x: position.x as f32,
+        y: position.y as f32,
     }
 }
 
@@ -269,20 +1388,71 @@ impl Cell {
 struct Controls {
     evolution_rate_slider: slider::State,
     evolution_rate: u128,
-    show_grid_lines: bool,
     is_paused: bool,
     toggle_play_button: button::State,
+    pause_on_cycle: bool, // Pauses once the pattern becomes still, periodic or a spaceship
+    pause_on_cycle_button: button::State,
     evolve_button: button::State,
+    step_back_button: button::State,
+    timeline_slider: slider::State,
+    history_range: (usize, usize), // Oldest and newest generation the timeline reaches
     evolve_input_field: text_input::State,
     evolve_input_text: String,
     evolve_count: usize,
-    // Add x random cells
-    // Toggle grid button
-    // Click to toggle state of cell
+    jump_button: button::State,
+    jump_input_field: text_input::State,
+    jump_input_text: String,
+    jump_target: usize,
+    rule: Rule,
+    rule_button: button::State,
+    rule_input_field: text_input::State,
+    rule_input_text: String,
+    rule_error: Option<String>,
+    engine: EngineKind,
+    grid_width: usize,
+    grid_height: usize,
+    engine_button: button::State,
+    topology: Topology,
+    horizontal_edge_button: button::State,
+    vertical_edge_button: button::State,
+    pan_buttons: [button::State; 4],
+    reset_view_button: button::State,
+    threads: usize,
+    max_threads: usize, // Number of threads the machine can run in parallel
+    thread_button: button::State,
+    file_path_input_field: text_input::State,
+    file_path: String,
+    offset_input_field: text_input::State,
+    offset_text: String, // Where the top left corner of an opened pattern goes, e.g. "10, 20"
+    open_button: button::State,
+    save_button: button::State,
+    census_button: button::State,
+    file_status: Option<String>, // Outcome of the last open or save
+    seed_input_field: text_input::State,
+    seed_text: String,
+    density_input_field: text_input::State,
+    density_text: String, // Percentage of live cells in a new soup
+    symmetry: Symmetry,
+    symmetry_button: button::State,
+    soup_button: button::State,
+    turmite_input_field: text_input::State,
+    turmite_text: String, // Turns of an ant on each color such as RL, or a turmite in Golly's notation
+    ant_button: button::State,
+    remove_ants_button: button::State,
+    ant_steps_input_field: text_input::State,
+    ant_steps_text: String,   // Turns the ants take per update
+    turmite: Option<Turmite>, // Turmite the ants on the grid follow, None without ants
+    ant_status: Option<String>, // Error or hint from the last ant that was added
+                              // Add x random cells
+                              // Toggle grid button
 }
 
 impl Controls {
-    fn view(&mut self, slider_width: u16) -> (Element<Message>, Element<Message>) {
+    fn view(
+        &mut self,
+        slider_width: u16,
+        generation: usize,
+    ) -> (Element<'_, Message>, Element<'_, Message>) {
         let speed_slider = Slider::new(
             &mut self.evolution_rate_slider,
             1.0..=200.0,
@@ -301,18 +1471,15 @@ impl Controls {
         .on_press(Message::TogglePlay)
         .style(style::Button);
 
-        let evolve_button = Button::new(
-            &mut self.evolve_button,
-            Text::new(format!("Evolve by:")).size(18),
-        )
-        .on_press(Message::Evolve(self.evolve_count))
-        .style(style::Button);
+        let evolve_button = Button::new(&mut self.evolve_button, Text::new("Evolve by:").size(18))
+            .on_press(Message::Evolve(self.evolve_count))
+            .style(style::Button);
 
         let evolve_input_field = TextInput::new(
             &mut self.evolve_input_field,
             "Evolve X generations",
             &self.evolve_input_text,
-            move |input| Controls::input_evolve_count(input),
+            Controls::input_evolve_count,
         )
         .padding(5)
         .style(style::InputField);
@@ -330,24 +1497,365 @@ impl Controls {
             .padding(5)
             .style(style::TextSnippet);
 
+        let step_back_button =
+            Button::new(&mut self.step_back_button, Text::new("Step back").size(18))
+                .on_press(Message::StepBack)
+                .style(style::Button);
+
+        let pause_on_cycle_button = Button::new(
+            &mut self.pause_on_cycle_button,
+            Text::new(match self.pause_on_cycle {
+                true => "Pause on cycle: On",
+                false => "Pause on cycle: Off",
+            })
+            .size(18),
+        )
+        .on_press(Message::TogglePauseOnCycle)
+        .style(style::Button);
+
         let evolution_controls = Row::new()
             .width(Length::Units(slider_width))
             .align_items(Align::Center)
             .spacing(5)
             .push(play_button)
+            .push(step_back_button)
+            .push(pause_on_cycle_button)
             .push(evolve_button)
             .push(evolve_input_field)
             .push(Space::with_width(Length::Fill))
             .push(evolution_rate);
 
-        let bottom = Column::new()
+        let mut bottom = Column::new()
             .width(Length::Units(slider_width))
             .spacing(5)
             .push(speed_slider)
-            .push(evolution_controls)
-            .into();
+            .push(evolution_controls);
+
+        // Dragging the timeline goes back and forth through the generations that are still in the history
+        let (oldest, newest) = self.history_range;
+        if oldest < newest {
+            let timeline_slider = Slider::new(
+                &mut self.timeline_slider,
+                oldest as f64..=newest as f64,
+                generation.clamp(oldest, newest) as f64,
+                |generation| Message::SeekTo(generation as usize),
+            )
+            .step(1.0)
+            .style(style::Slider);
+            let timeline = Row::new()
+                .align_items(Align::Center)
+                .spacing(5)
+                .push(Text::new(format!("History: {}", oldest)).size(18))
+                .push(timeline_slider)
+                .push(Text::new(newest.to_string()).size(18));
+            bottom = bottom.push(timeline);
+        }
+        let bottom = bottom.into();
+
+        let rule_input_field = TextInput::new(
+            &mut self.rule_input_field,
+            "Rule, e.g. B36/S23",
+            &self.rule_input_text,
+            Message::SetRuleText,
+        )
+        .on_submit(Message::ApplyRule)
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let rule_button = Button::new(&mut self.rule_button, Text::new("Set rule").size(18))
+            .on_press(Message::ApplyRule)
+            .style(style::Button);
+
+        let rule_status = Text::new(match &self.rule_error {
+            Some(error) => error.clone(),
+            None => format!("Rule: {}", self.rule),
+        })
+        .size(18);
+        let rule_status = Container::new(rule_status)
+            .padding(5)
+            .style(style::TextSnippet);
+
+        let rule_controls = Row::new()
+            .align_items(Align::Center)
+            .spacing(5)
+            .push(rule_input_field)
+            .push(rule_button);
+
+        let jump_button = Button::new(&mut self.jump_button, Text::new("Jump to:").size(18))
+            .on_press(Message::JumpTo(self.jump_target))
+            .style(style::Button);
+
+        let jump_input_field = TextInput::new(
+            &mut self.jump_input_field,
+            "Generation",
+            &self.jump_input_text,
+            Controls::input_jump_target,
+        )
+        .on_submit(Message::JumpTo(self.jump_target))
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let jump_controls = Row::new()
+            .align_items(Align::Center)
+            .spacing(5)
+            .push(jump_button)
+            .push(jump_input_field);
 
-        let side = Column::new().into();
+        // Pressing the engine button switches to the next engine that works with the grid size, topology and rule
+        let (engine, topology, rule) = (self.engine, self.topology, self.rule.clone());
+        let ant_colors = self.turmite.as_ref().map_or(2, Turmite::colors);
+        let (grid_width, grid_height) = (self.grid_width, self.grid_height);
+        let next_engine = EngineKind::ALL
+            .iter()
+            .cycle()
+            .skip_while(|&&kind| kind != engine)
+            .skip(1)
+            .find(|kind| {
+                kind.supports(grid_width, grid_height, topology)
+                    && kind.supports_rule(&rule)
+                    && kind.supports_states(ant_colors)
+            })
+            .copied()
+            .unwrap_or(engine);
+        let engine_button = Button::new(
+            &mut self.engine_button,
+            Text::new(format!("Engine: {}", self.engine)).size(18),
+        )
+        .on_press(Message::EngineChange(next_engine))
+        .style(style::Button);
+
+        // Pressing the thread button doubles the thread count, until it starts over at 1
+        let next_threads = if self.threads >= self.max_threads {
+            1
+        } else {
+            (self.threads * 2).min(self.max_threads)
+        };
+        let thread_button = Button::new(
+            &mut self.thread_button,
+            Text::new(format!("Threads: {}", self.threads)).size(18),
+        )
+        .on_press(Message::ThreadCountChange(next_threads))
+        .style(style::Button);
+
+        let mut side = Column::new()
+            .width(Length::Units(250))
+            .spacing(5)
+            .push(rule_controls)
+            .push(rule_status)
+            .push(jump_controls)
+            .push(engine_button)
+            .push(thread_button);
+
+        if self.engine.is_bounded() {
+            let horizontal_edge_button = Button::new(
+                &mut self.horizontal_edge_button,
+                Text::new(format!("Left/right edges: {}", topology.horizontal)).size(18),
+            )
+            .on_press(Message::TopologyChange(Topology {
+                horizontal: topology.horizontal.next(),
+                ..topology
+            }))
+            .style(style::Button);
+
+            let vertical_edge_button = Button::new(
+                &mut self.vertical_edge_button,
+                Text::new(format!("Top/bottom edges: {}", topology.vertical)).size(18),
+            )
+            .on_press(Message::TopologyChange(Topology {
+                vertical: topology.vertical.next(),
+                ..topology
+            }))
+            .style(style::Button);
+
+            let topology_status =
+                Container::new(Text::new(format!("Topology: {}", topology)).size(18))
+                    .padding(5)
+                    .style(style::TextSnippet);
+
+            side = side
+                .push(horizontal_edge_button)
+                .push(vertical_edge_button)
+                .push(topology_status);
+        } else {
+            let (x_distance, y_distance) = ((grid_width / 4) as isize, (grid_height / 4) as isize);
+            let directions = [
+                ("Left", -x_distance, 0),
+                ("Up", 0, -y_distance),
+                ("Down", 0, y_distance),
+                ("Right", x_distance, 0),
+            ];
+            let pan_controls = self.pan_buttons.iter_mut().zip(directions.iter()).fold(
+                Row::new().spacing(5),
+                |row, (state, &(label, x, y))| {
+                    row.push(
+                        Button::new(state, Text::new(label).size(18))
+                            .on_press(Message::Pan(Position { x, y }))
+                            .style(style::Button),
+                    )
+                },
+            );
+            let reset_view_button = Button::new(
+                &mut self.reset_view_button,
+                Text::new("Back to origin").size(18),
+            )
+            .on_press(Message::ResetView)
+            .style(style::Button);
+
+            side = side.push(pan_controls).push(reset_view_button);
+        }
+
+        let file_path_input_field = TextInput::new(
+            &mut self.file_path_input_field,
+            "Pattern file, e.g. glider.rle",
+            &self.file_path,
+            Message::SetFilePath,
+        )
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let offset_input_field = TextInput::new(
+            &mut self.offset_input_field,
+            "Offset x, y",
+            &self.offset_text,
+            Message::SetOffsetText,
+        )
+        .on_submit(Message::OpenPattern)
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let open_button = Button::new(&mut self.open_button, Text::new("Open").size(18))
+            .on_press(Message::OpenPattern)
+            .style(style::Button);
+
+        let save_button = Button::new(&mut self.save_button, Text::new("Save").size(18))
+            .on_press(Message::SavePattern)
+            .style(style::Button);
+
+        // The census shows up in the statistics and can then be saved as .csv or .json
+        let census_button = Button::new(&mut self.census_button, Text::new("Census").size(18))
+            .on_press(Message::TakeCensus)
+            .style(style::Button);
+
+        let file_controls = Row::new()
+            .align_items(Align::Center)
+            .spacing(5)
+            .push(offset_input_field)
+            .push(open_button)
+            .push(save_button)
+            .push(census_button);
+
+        let seed_input_field = TextInput::new(
+            &mut self.seed_input_field,
+            "Seed, or empty for a random one",
+            &self.seed_text,
+            Message::SetSeedText,
+        )
+        .on_submit(Message::NewSoup)
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let density_input_field = TextInput::new(
+            &mut self.density_input_field,
+            "%",
+            &self.density_text,
+            Message::SetDensityText,
+        )
+        .on_submit(Message::NewSoup)
+        .width(Length::Units(40))
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let soup_button = Button::new(&mut self.soup_button, Text::new("New soup").size(18))
+            .on_press(Message::NewSoup)
+            .style(style::Button);
+
+        let symmetry_button = Button::new(
+            &mut self.symmetry_button,
+            Text::new(format!("Symmetry: {}", self.symmetry)).size(18),
+        )
+        .on_press(Message::SymmetryChange(self.symmetry.next()))
+        .style(style::Button);
+
+        let soup_controls = Row::new()
+            .align_items(Align::Center)
+            .spacing(5)
+            .push(seed_input_field)
+            .push(density_input_field)
+            .push(soup_button);
+
+        // Ants take the place of the rule until they are removed again
+        let turmite_input_field = TextInput::new(
+            &mut self.turmite_input_field,
+            "Ant, e.g. RL or RRLLLRLLLRRR",
+            &self.turmite_text,
+            Message::SetTurmiteText,
+        )
+        .on_submit(Message::AddAnt)
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let ant_button = Button::new(&mut self.ant_button, Text::new("Add ant").size(18))
+            .on_press(Message::AddAnt)
+            .style(style::Button);
+
+        let ant_controls = Row::new()
+            .align_items(Align::Center)
+            .spacing(5)
+            .push(turmite_input_field)
+            .push(ant_button);
+
+        let ant_steps_input_field = TextInput::new(
+            &mut self.ant_steps_input_field,
+            "Ant steps per update",
+            &self.ant_steps_text,
+            Controls::input_ant_steps,
+        )
+        .padding(5)
+        .size(18)
+        .style(style::InputField);
+
+        let mut ant_step_controls = Row::new()
+            .align_items(Align::Center)
+            .spacing(5)
+            .push(Text::new("Steps:").size(18))
+            .push(ant_steps_input_field);
+        if self.turmite.is_some() {
+            let remove_ants_button = Button::new(
+                &mut self.remove_ants_button,
+                Text::new("Remove ants").size(18),
+            )
+            .on_press(Message::RemoveAnts)
+            .style(style::Button);
+            ant_step_controls = ant_step_controls.push(remove_ants_button);
+        }
+
+        side = side
+            .push(soup_controls)
+            .push(symmetry_button)
+            .push(ant_controls)
+            .push(ant_step_controls);
+        if let Some(status) = &self.ant_status {
+            let ant_status = Container::new(Text::new(status.clone()).size(18))
+                .padding(5)
+                .style(style::TextSnippet);
+            side = side.push(ant_status);
+        }
+        side = side.push(file_path_input_field).push(file_controls);
+        if let Some(status) = &self.file_status {
+            let file_status = Container::new(Text::new(status.clone()).size(18))
+                .padding(5)
+                .style(style::TextSnippet);
+            side = side.push(file_status);
+        }
+
+        let side = side.into();
 
         (bottom, side)
     }
@@ -359,33 +1867,117 @@ impl Controls {
             Err(_) => Message::SetEvolveCount(0, input),
         }
     }
+
+    fn input_ant_steps(input: String) -> Message {
+        match input.parse::<usize>() {
+            Ok(number) => Message::SetAntSteps(number, number.to_string()),
+            Err(_) => Message::SetAntSteps(0, input),
+        }
+    }
+
+    fn input_jump_target(input: String) -> Message {
+        let generation = input.parse::<usize>();
+        match generation {
+            Ok(number) => Message::SetJumpTarget(number, number.to_string()),
+            Err(_) => Message::SetJumpTarget(0, input),
+        }
+    }
 }
 
 struct Statistics {
-    cell_count: usize,
+    cell_count: Option<usize>, // None for an unbounded plane
     live_cell_count: usize,
     generation: usize,
-    // FPS
+    soup: Option<Soup>, // Soup the grid started out as, None after a pattern was opened
+    cycle: Option<Cycle>, // Cycle the pattern settled into, if any
+    spaceship: Option<Spaceship>,
+    census: Option<Census>, // Last census the simulation took
+    ant_count: usize,
+    ant_steps: usize, // Steps the ants took since the first one was added
+                      // FPS
 }
 
 impl Statistics {
-    fn view(&mut self) -> Element<Message> {
-        let total_cells = self.cell_count;
+    fn view(&mut self) -> Element<'_, Message> {
         let live_cells = self.live_cell_count;
-        let dead_cells = total_cells - live_cells;
-        let live_cell_percent = (live_cells as f64) / (total_cells as f64) * 100.0;
-        let dead_cell_percent = (dead_cells as f64) / (total_cells as f64) * 100.0;
-
-        let statistics = Text::new(format!(
-            "Generation: {}\n\nTotal cells: {}\nLive cells: {} ≈ {:.2}%\nDead cells: {} ≈ {:.2}%",
-            self.generation,
-            total_cells,
-            live_cells,
-            live_cell_percent,
-            dead_cells,
-            dead_cell_percent
-        ))
-        .size(18);
+
+        let statistics = match self.cell_count {
+            Some(total_cells) => {
+                let dead_cells = total_cells - live_cells;
+                let live_cell_percent = (live_cells as f64) / (total_cells as f64) * 100.0;
+                let dead_cell_percent = (dead_cells as f64) / (total_cells as f64) * 100.0;
+
+                format!(
+                    "Generation: {}\n\nTotal cells: {}\nLive cells: {} ≈ {:.2}%\nDead cells: {} ≈ {:.2}%",
+                    self.generation,
+                    total_cells,
+                    live_cells,
+                    live_cell_percent,
+                    dead_cells,
+                    dead_cell_percent
+                )
+            }
+            None => format!(
+                "Generation: {}\n\nTotal cells: ∞\nLive cells: {}",
+                self.generation, live_cells
+            ),
+        };
+        let statistics = match self.ant_count {
+            0 => statistics,
+            ants => format!(
+                "{}\n\nAnts: {}\nAnt steps: {}",
+                statistics, ants, self.ant_steps
+            ),
+        };
+        let statistics = match &self.soup {
+            Some(soup) => format!(
+                "{}\n\nSeed: {}\nDensity: {}%\nSymmetry: {}",
+                statistics, soup.seed, soup.density, soup.symmetry
+            ),
+            None => statistics,
+        };
+        let statistics = match self.cycle {
+            Some(Cycle { start, period: 1 }) => {
+                format!("{}\n\nStill life since generation {}", statistics, start)
+            }
+            Some(Cycle { start, period }) => format!(
+                "{}\n\nPeriod {} since generation {}",
+                statistics, period, start
+            ),
+            None => statistics,
+        };
+        let statistics = match self.spaceship {
+            Some(spaceship) => format!(
+                "{}\n\nSpaceship since generation {}:\n{}",
+                statistics, spaceship.start, spaceship
+            ),
+            None => statistics,
+        };
+        // The most common objects, as the whole census can be long
+        let statistics = match &self.census {
+            Some(census) => {
+                let objects: Vec<String> = census
+                    .objects
+                    .iter()
+                    .take(8)
+                    .map(|object| {
+                        format!(
+                            "{} {}",
+                            object.count,
+                            object.name.unwrap_or(&object.apgcode)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{}\n\nCensus: {} objects\n{}",
+                    statistics,
+                    census.total(),
+                    objects.join("\n")
+                )
+            }
+            None => statistics,
+        };
+        let statistics = Text::new(statistics).size(18);
 
         Container::new(statistics)
             .padding(5)
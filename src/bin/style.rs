@@ -1,11 +1,16 @@
-use iced::{button, container, slider, text_input, Background, Color};
+#![allow(clippy::eq_op)]
+
+use conway::simulation::CellState;
+use conway::Rule;
 
-#[allow(clippy::eq_op)]
+use iced::{button, container, slider, text_input, Background, Color};
 
 // Cell grid colors
 pub const LIVE_CELL: Color = Color::from_rgba(255.0 / 255.0, 0.0 / 255.0, 128.0 / 255.0, 1.0);
 pub const DEAD_CELL: Color = Color::from_rgba(36.0 / 255.0, 36.0 / 255.0, 36.0 / 255.0, 1.0);
+pub const DYING_CELL: Color = Color::from_rgba(255.0 / 255.0, 150.0 / 255.0, 0.0 / 255.0, 1.0);
 pub const GRID_LINE: Color = Color::from_rgba(125.0 / 255.0, 0.0 / 255.0, 175.0 / 255.0, 1.0);
+pub const ANT: Color = Color::from_rgba(0.0 / 255.0, 220.0 / 255.0, 255.0 / 255.0, 1.0);
 
 // Control colors
 pub const ACTIVE: Color = Color::from_rgba(230.0 / 255.0, 0.0 / 255.0, 100.0 / 255.0, 1.0);
@@ -15,6 +20,58 @@ pub const BACKGROUND: Color = DEAD_CELL;
 pub const TEXT: Color = Color::WHITE;
 pub const BORDER: Color = TEXT;
 
+// Colors of the states of a rule. Rule tables pick theirs in their @COLORS section, and any state they leave out
+// takes the color it would have under a Generations rule. Turmites can paint cells in more states than the rule has
+pub struct Palette {
+    colors: Vec<Color>, // colors[n]: Color of state n, see CellState::index
+}
+
+impl Palette {
+    pub fn new(rule: &Rule, states: u8) -> Palette {
+        let states = u16::from(states).max(rule.states());
+        let dying_states = states - 2;
+        let colors = (0..states)
+            .map(|index| index as u8)
+            .map(|index| {
+                let color = rule.table().and_then(|table| table.color(index));
+                match (color, CellState::from_index(index)) {
+                    (Some([red, green, blue]), _) => Color::from_rgb8(red, green, blue),
+                    (None, CellState::Dead) => DEAD_CELL,
+                    (None, CellState::Alive) => LIVE_CELL,
+                    (None, CellState::Dying(age)) => dying_cell(age, dying_states),
+                }
+            })
+            .collect();
+        Palette { colors }
+    }
+
+    pub fn cell(&self, state: CellState) -> Color {
+        self.colors
+            .get(state.index() as usize)
+            .copied()
+            .unwrap_or(DEAD_CELL)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new(&Rule::default(), 2)
+    }
+}
+
+// Color of a cell in the dying state of the age, out of the number of dying states of the rule. Dying states fade from
+// the dying color towards the dead color
+fn dying_cell(age: u8, dying_states: u16) -> Color {
+    let fraction = age as f32 / (dying_states as f32 + 1.0);
+    let mix = |from: f32, to: f32| from + (to - from) * fraction;
+    Color::from_rgba(
+        mix(DYING_CELL.r, DEAD_CELL.r),
+        mix(DYING_CELL.g, DEAD_CELL.g),
+        mix(DYING_CELL.b, DEAD_CELL.b),
+        1.0,
+    )
+}
+
 // Settings
 pub const BORDER_WIDTH: f32 = 2.0;
 pub const BORDER_RADIUS: f32 = 5.0;
@@ -0,0 +1,533 @@
+use crate::engine::Engine;
+use crate::rule::{Rule, NEIGHBORS};
+use crate::simulation::{CellState, Position};
+use crate::topology::Topology;
+
+use crossbeam_channel::Sender;
+
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+const WORD_BITS: usize = 64;
+
+/// Grid storing one bit per cell. Column x of a row lives in bit x % 64 of word x / 64.
+/// Neighbor counts are computed for 64 cells at a time with bitwise adders, treating the grid as a torus.
+/// For other topologies, the cells along the edges are then computed again one by one.
+/// With several threads, every thread handles a band of consecutive rows, so the result doesn't depend on the thread count.
+/// The threads stay alive between generations, so small grids don't pay for starting threads every step.
+pub struct BitGrid {
+    cells: Words,
+    threads: usize,
+    pool: WorkerPool,
+}
+
+// The words of a grid along with its size, which the worker threads share while they compute a generation
+#[derive(Clone)]
+struct Words {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    words_per_row: usize,
+    words: Arc<Vec<u64>>,
+}
+
+// Next words of a band of rows, and the cells in it that change state
+type Band = (Vec<u64>, Vec<(Position, CellState)>);
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize, topology: Topology) -> BitGrid {
+        let words_per_row = width.div_ceil(WORD_BITS);
+
+        BitGrid {
+            cells: Words {
+                width,
+                height,
+                topology,
+                words_per_row,
+                words: Arc::new(vec![0; words_per_row * height]),
+            },
+            threads: 1,
+            pool: WorkerPool::new(0),
+        }
+    }
+
+    // Rows handled by each thread
+    fn bands(&self) -> Vec<Range<usize>> {
+        let height = self.cells.height;
+        let band_height = height.div_ceil(self.threads).max(1);
+        (0..height)
+            .step_by(band_height)
+            .map(|first_y| first_y..(first_y + band_height).min(height))
+            .collect()
+    }
+}
+
+impl Words {
+    // Index of the word holding the cell, and the mask selecting its bit within that word
+    fn locate(&self, position: Position) -> (usize, u64) {
+        let (x, y) = (position.x as usize, position.y as usize);
+        (y * self.words_per_row + x / WORD_BITS, 1 << (x % WORD_BITS))
+    }
+
+    fn get(&self, position: Position) -> CellState {
+        match Words::bit(self.row(position.y as usize), position.x as usize) {
+            0 => CellState::Dead,
+            _ => CellState::Alive,
+        }
+    }
+
+    // Cells along the edges of the grid within the rows
+    fn border(&self, rows: Range<usize>) -> Vec<Position> {
+        let (last_x, last_y) = (self.width as isize - 1, self.height as isize - 1);
+        rows.map(|y| y as isize)
+            .flat_map(|y| {
+                let xs: Vec<isize> = if y == 0 || y == last_y {
+                    (0..=last_x).collect()
+                } else if last_x > 0 {
+                    vec![0, last_x]
+                } else {
+                    vec![0]
+                };
+                xs.into_iter().map(move |x| Position { x, y })
+            })
+            .collect()
+    }
+
+    // Live neighbors of the cell as bits, see NEIGHBORS
+    fn neighborhood(&self, position: Position) -> u8 {
+        let mut neighborhood = 0;
+        for (bit, &offset) in NEIGHBORS.iter().enumerate() {
+            match self
+                .topology
+                .wrap(position + offset, self.width, self.height)
+            {
+                Some(neighbor)
+                    if neighbor != position && self.get(neighbor) == CellState::Alive =>
+                {
+                    neighborhood |= 1 << bit
+                }
+                _ => (),
+            }
+        }
+        neighborhood
+    }
+
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    // Mask of the bits in word w that belong to the grid
+    fn valid_bits(&self, w: usize) -> u64 {
+        let bits = (self.width - w * WORD_BITS).min(WORD_BITS);
+        if bits == WORD_BITS {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        }
+    }
+
+    fn bit(row: &[u64], x: usize) -> u64 {
+        (row[x / WORD_BITS] >> (x % WORD_BITS)) & 1
+    }
+
+    // Bit i holds the cell to the left of column 64 * w + i, wrapping around the grid edge
+    fn west(&self, row: &[u64], w: usize) -> u64 {
+        let carry = if w == 0 {
+            Words::bit(row, self.width - 1)
+        } else {
+            row[w - 1] >> (WORD_BITS - 1)
+        };
+        (row[w] << 1) | carry
+    }
+
+    // Bit i holds the cell to the right of column 64 * w + i, wrapping around the grid edge
+    fn east(&self, row: &[u64], w: usize) -> u64 {
+        let carry = if w + 1 == self.words_per_row {
+            let last_bit = (self.width - 1) % WORD_BITS;
+            Words::bit(row, 0) << last_bit
+        } else {
+            row[w + 1] << (WORD_BITS - 1)
+        };
+        (row[w] >> 1) | carry
+    }
+
+    fn next_row(&self, y: usize, rule: &Rule, next: &mut [u64]) {
+        let up = self.row((y + self.height - 1) % self.height);
+        let center = self.row(y);
+        let down = self.row((y + 1) % self.height);
+
+        // Masks selecting the cells with exactly n live neighbors for every n that leads to a live cell
+        let births: Vec<usize> = (0..=8).filter(|&count| rule.births(count)).collect();
+        let survivals: Vec<usize> = (0..=8).filter(|&count| rule.survives(count)).collect();
+
+        for w in 0..self.words_per_row {
+            let neighbors = [
+                self.west(up, w),
+                up[w],
+                self.east(up, w),
+                self.west(center, w),
+                self.east(center, w),
+                self.west(down, w),
+                down[w],
+                self.east(down, w),
+            ];
+            let count = count_neighbors(&neighbors);
+
+            let born = births
+                .iter()
+                .fold(0, |mask, &n| mask | count_equals(&count, n));
+            let survived = survivals
+                .iter()
+                .fold(0, |mask, &n| mask | count_equals(&count, n));
+
+            next[w] = ((!center[w] & born) | (center[w] & survived)) & self.valid_bits(w);
+        }
+    }
+
+    // Computes the rows of the band, then the cells along the edges again where the grid isn't a torus
+    fn next_band(&self, rule: &Rule, rows: Range<usize>) -> Band {
+        let first_index = rows.start * self.words_per_row;
+        let mut next = vec![0; rows.len() * self.words_per_row];
+        for (y, row) in rows.clone().zip(next.chunks_mut(self.words_per_row)) {
+            self.next_row(y, rule, row);
+        }
+
+        if !self.topology.is_torus() {
+            for position in self.border(rows) {
+                let state = rule.next_state(self.get(position), self.neighborhood(position));
+                let (index, mask) = self.locate(position);
+                match state {
+                    CellState::Alive => next[index - first_index] |= mask,
+                    _ => next[index - first_index] &= !mask,
+                }
+            }
+        }
+
+        let transitions = self.transitions(first_index, &next);
+        (next, transitions)
+    }
+
+    // Cells that change state between the words starting at first_index and the next words
+    fn transitions(&self, first_index: usize, next: &[u64]) -> Vec<(Position, CellState)> {
+        let mut transitions = vec![];
+        let old = &self.words[first_index..first_index + next.len()];
+        for (offset, (&old, &new)) in old.iter().zip(next).enumerate() {
+            let index = first_index + offset;
+            let mut changed = old ^ new;
+            while changed != 0 {
+                let bit = changed.trailing_zeros() as usize;
+                changed &= changed - 1;
+
+                let position = Position {
+                    x: ((index % self.words_per_row) * WORD_BITS + bit) as isize,
+                    y: (index / self.words_per_row) as isize,
+                };
+                let state = if (new >> bit) & 1 == 1 {
+                    CellState::Alive
+                } else {
+                    CellState::Dead
+                };
+                transitions.push((position, state));
+            }
+        }
+        transitions
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Threads that wait for jobs until the pool is dropped
+struct WorkerPool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    fn new(threads: usize) -> WorkerPool {
+        let (jobs, receiver) = crossbeam_channel::unbounded::<Job>();
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = receiver.clone();
+                thread::spawn(move || {
+                    // A job that panics only loses its own result, and the worker goes on with the next one
+                    for job in receiver {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                })
+            })
+            .collect();
+        WorkerPool {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    fn run(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            jobs.send(job).unwrap(); // Workers only stop once the pool is dropped
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Closing the channel lets every worker finish its loop
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn half_add(a: u64, b: u64) -> (u64, u64) {
+    (a ^ b, a & b)
+}
+
+fn full_add(a: u64, b: u64, c: u64) -> (u64, u64) {
+    let partial = a ^ b;
+    (partial ^ c, (a & b) | (partial & c))
+}
+
+// Adds up 8 words bit by bit, returning the 4 bits of every count from least significant bit up
+fn count_neighbors(neighbors: &[u64; 8]) -> [u64; 4] {
+    let (sum_a, carry_a) = full_add(neighbors[0], neighbors[1], neighbors[2]);
+    let (sum_b, carry_b) = full_add(neighbors[3], neighbors[4], neighbors[5]);
+    let (sum_c, carry_c) = half_add(neighbors[6], neighbors[7]);
+
+    let (ones, carry_d) = full_add(sum_a, sum_b, sum_c);
+    let (partial_twos, fours_a) = full_add(carry_a, carry_b, carry_c);
+    let (twos, fours_b) = half_add(partial_twos, carry_d);
+    let (fours, eights) = half_add(fours_a, fours_b);
+
+    [ones, twos, fours, eights]
+}
+
+fn count_equals(count: &[u64; 4], n: usize) -> u64 {
+    count
+        .iter()
+        .enumerate()
+        .fold(u64::MAX, |mask, (bit, &word)| {
+            if (n >> bit) & 1 == 1 {
+                mask & word
+            } else {
+                mask & !word
+            }
+        })
+}
+
+impl Engine for BitGrid {
+    fn step(&mut self, rule: &Rule) -> Vec<(Position, CellState)> {
+        let bands = self.bands();
+        if bands.is_empty() {
+            return vec![];
+        }
+
+        // The workers take every band but the first, which the calling thread takes itself
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let shared_rule = Arc::new(rule.clone());
+        for (band, rows) in bands.iter().cloned().enumerate().skip(1) {
+            let (cells, rule, sender) = (self.cells.clone(), shared_rule.clone(), sender.clone());
+            self.pool.run(Box::new(move || {
+                let _ = sender.send((band, cells.next_band(&rule, rows)));
+            }));
+        }
+        // With the senders only left in the jobs, the results end once every job has sent its band or panicked
+        drop(sender);
+        let mut results: Vec<Option<Band>> = vec![None; bands.len()];
+        results[0] = Some(self.cells.next_band(rule, bands[0].clone()));
+        for (band, result) in receiver {
+            results[band] = Some(result);
+        }
+        let results: Vec<Band> = results
+            .into_iter()
+            .collect::<Option<_>>()
+            .expect("A worker thread panicked while computing its band of rows");
+
+        // Joining the bands in order keeps the transitions sorted by row
+        let mut words = Vec::with_capacity(self.cells.words.len());
+        let mut transitions = vec![];
+        for (next, band_transitions) in results {
+            words.extend(next);
+            transitions.extend(band_transitions);
+        }
+        self.cells.words = Arc::new(words);
+        transitions
+    }
+
+    fn get(&self, position: Position) -> CellState {
+        self.cells.get(position)
+    }
+
+    fn set(&mut self, position: Position, state: CellState) {
+        // A bit per cell only has room for two states, so dying cells are dead here
+        let (index, mask) = self.cells.locate(position);
+        let words = Arc::make_mut(&mut self.cells.words);
+        match state {
+            CellState::Alive => words[index] |= mask,
+            _ => words[index] &= !mask,
+        }
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        if threads != self.threads {
+            self.threads = threads;
+            self.pool = WorkerPool::new(threads - 1);
+        }
+    }
+
+    fn live_cells(&self) -> Vec<Position> {
+        let mut live_cells = vec![];
+        for (index, &word) in self.cells.words.iter().enumerate() {
+            let mut remaining = word;
+            while remaining != 0 {
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                live_cells.push(Position {
+                    x: ((index % self.cells.words_per_row) * WORD_BITS + bit) as isize,
+                    y: (index / self.cells.words_per_row) as isize,
+                });
+            }
+        }
+        live_cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::NaiveGrid;
+    use crate::topology::Edge;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const EDGES: [Edge; 3] = [Edge::Dead, Edge::Wrapped, Edge::Twisted];
+
+    fn random_cells(seed: u64, width: usize, height: usize) -> Vec<Position> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..height as isize)
+            .flat_map(|y| (0..width as isize).map(move |x| Position { x, y }))
+            .filter(|_| rng.gen_ratio(35, 100))
+            .collect()
+    }
+
+    fn sorted(mut transitions: Vec<(Position, CellState)>) -> Vec<(Position, CellState)> {
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+        transitions
+    }
+
+    // Grids a word wide and several words wide, with rows that don't fill their last word
+    #[test]
+    fn matches_naive_grid_on_every_topology() {
+        let rules: Vec<Rule> = ["B3/S23", "B36/S23", "B2/S", "B3678/S34678"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+        for &(width, height) in &[(64, 16), (70, 23), (130, 9), (5, 7)] {
+            for &horizontal in &EDGES {
+                for &vertical in &EDGES {
+                    let topology = Topology {
+                        horizontal,
+                        vertical,
+                    };
+                    for (seed, rule) in rules.iter().enumerate() {
+                        let mut naive = NaiveGrid::new(width, height, topology);
+                        let mut bit_grid = BitGrid::new(width, height, topology);
+                        for position in random_cells(seed as u64, width, height) {
+                            naive.set(position, CellState::Alive);
+                            bit_grid.set(position, CellState::Alive);
+                        }
+
+                        for generation in 0..20 {
+                            assert_eq!(
+                                sorted(bit_grid.step(rule)),
+                                sorted(naive.step(rule)),
+                                "{}x{} {} under {}, generation {}",
+                                width,
+                                height,
+                                topology,
+                                rule,
+                                generation + 1
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Uneven bands, bands of a single row and more threads than rows
+    #[test]
+    fn thread_count_doesnt_change_the_result() {
+        let rule: Rule = "B3/S23".parse().unwrap();
+        let topology = Topology {
+            horizontal: Edge::Twisted,
+            vertical: Edge::Dead,
+        };
+        for &(width, height) in &[(100, 37), (70, 5)] {
+            let mut single = BitGrid::new(width, height, topology);
+            let mut grids: Vec<BitGrid> = [2, 3, 8]
+                .iter()
+                .map(|&threads| {
+                    let mut grid = BitGrid::new(width, height, topology);
+                    grid.set_threads(threads);
+                    grid
+                })
+                .collect();
+            for position in random_cells(7, width, height) {
+                single.set(position, CellState::Alive);
+                for grid in &mut grids {
+                    grid.set(position, CellState::Alive);
+                }
+            }
+
+            for _ in 0..20 {
+                let transitions = single.step(&rule);
+                for grid in &mut grids {
+                    assert_eq!(grid.step(&rule), transitions);
+                    assert_eq!(grid.cells.words, single.cells.words);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn glider_crosses_the_torus() {
+        let rule: Rule = "B3/S23".parse().unwrap();
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut grid = BitGrid::new(8, 8, Topology::TORUS);
+        for &(x, y) in &glider {
+            grid.set(Position { x, y }, CellState::Alive);
+        }
+
+        // A glider moves one cell diagonally every 4 generations, so after 32 it is back where it started
+        for _ in 0..32 {
+            grid.step(&rule);
+        }
+        let mut live_cells = grid.live_cells();
+        live_cells.sort_by_key(|position| (position.y, position.x));
+        let mut expected: Vec<Position> = glider.iter().map(|&(x, y)| Position { x, y }).collect();
+        expected.sort_by_key(|position| (position.y, position.x));
+        assert_eq!(live_cells, expected);
+    }
+
+    #[test]
+    fn workers_outlive_jobs_that_panic() {
+        let pool = WorkerPool::new(1);
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        for job in 0..3 {
+            let sender = sender.clone();
+            pool.run(Box::new(move || {
+                assert_ne!(job, 1, "job 1 fails");
+                sender.send(job).unwrap();
+            }));
+        }
+
+        // The failed job drops its sender too, so receiving ends instead of waiting for its result
+        drop(sender);
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), vec![0, 2]);
+    }
+}
@@ -0,0 +1,591 @@
+//! Census of the objects on a grid that has settled down. The live cells are split into islands of cells that touch,
+//! and each island is run on its own until it repeats, which tells whether it is a still life, an oscillator or a
+//! spaceship. Islands are named by their apgcode, the same encoding apgsearch and Catagolue use:
+//!
+//! ```text
+//! xs4_33     block, a still life with 4 cells
+//! xp2_7      blinker, an oscillator with period 2
+//! xq4_153    glider, a spaceship with period 4
+//! ```
+//!
+//! The part after the underscore encodes the cells in strips of 5 rows, with each column of a strip written as one
+//! character. Out of all orientations and phases of an object, the shortest encoding is used, and the
+//! alphabetically first one among those.
+//! An island only counts as an object if it evolves the same on its own as it does among the rest of the cells.
+//! Some objects are made of parts that don't touch, like the lightweight spaceship in some phases, or the pulsar, so
+//! islands that don't count are grouped again with those at most one empty cell away. Whatever still doesn't count
+//! is PATHOLOGICAL.
+
+use crate::cycle::{CycleDetector, SpaceshipDetector};
+use crate::engine::Engine;
+use crate::rule::Rule;
+use crate::simulation::{CellState, Position};
+use crate::sparse::SparseGrid;
+use crate::tiling::Tiling;
+
+use std::collections::{HashMap, HashSet};
+
+// Generations an island is run for to find out whether it repeats
+const MAX_GENERATIONS: usize = 256;
+
+// Characters for the columns of a strip, and for the lengths of runs of empty columns
+const COLUMN_CHARACTERS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+// Names of common objects in Conway's Game of Life
+const NAMES: [(&str, &str); 26] = [
+    ("xs4_33", "block"),
+    ("xs4_252", "tub"),
+    ("xs5_253", "boat"),
+    ("xs6_696", "beehive"),
+    ("xs6_356", "ship"),
+    ("xs6_25a4", "barge"),
+    ("xs6_bd", "snake"),
+    ("xs6_39c", "aircraft carrier"),
+    ("xs7_2596", "loaf"),
+    ("xs7_25ac", "long boat"),
+    ("xs7_178c", "eater 1"),
+    ("xs8_6996", "pond"),
+    ("xs8_69ic", "mango"),
+    ("xs8_25ak8", "long barge"),
+    ("xs8_35ac", "long ship"),
+    ("xs9_4aar", "hat"),
+    ("xp2_7", "blinker"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xp2_2a54", "clock"),
+    ("xp3_co9nas0san9oczgoldlo0oldlogz1047210127401", "pulsar"),
+    ("xp15_4r4z4r4", "pentadecathlon"),
+    ("xq4_153", "glider"),
+    ("xq4_6frc", "lightweight spaceship"),
+    ("xq4_27dee6", "middleweight spaceship"),
+    ("xq4_27deee6", "heavyweight spaceship"),
+];
+
+/// Objects on a grid, with the most common ones first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Census {
+    pub rule: Rule,
+    pub objects: Vec<CensusEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CensusEntry {
+    pub apgcode: String,
+    pub name: Option<&'static str>, // Only known for Conway's Game of Life
+    pub count: usize,
+}
+
+impl Census {
+    /// Sorts the islands of live cells into objects. Rules with B0 can't be run on an unbounded plane, and
+    /// Generations rules leave dying cells around objects, so all islands count as PATHOLOGICAL under them
+    pub fn take(live_cells: &[Position], rule: Rule) -> Census {
+        let mut surroundings = Surroundings::new(live_cells, rule.clone());
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut leftover_cells = vec![];
+        for island in islands(live_cells, 1) {
+            match classify(&island, &rule) {
+                Some((apgcode, phases)) if surroundings.agree(&phases) => {
+                    *counts.entry(apgcode).or_insert(0) += 1
+                }
+                _ => leftover_cells.extend(island),
+            }
+        }
+        for island in islands(&leftover_cells, 2) {
+            let apgcode = match classify(&island, &rule) {
+                Some((apgcode, phases)) if surroundings.agree(&phases) => apgcode,
+                _ => "PATHOLOGICAL".to_string(),
+            };
+            *counts.entry(apgcode).or_insert(0) += 1;
+        }
+
+        let is_conway = rule == Rule::default();
+        let mut objects: Vec<CensusEntry> = counts
+            .into_iter()
+            .map(|(apgcode, count)| CensusEntry {
+                name: match is_conway {
+                    true => name(&apgcode),
+                    false => None,
+                },
+                apgcode,
+                count,
+            })
+            .collect();
+        objects.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.apgcode.cmp(&b.apgcode))
+        });
+
+        Census { rule, objects }
+    }
+
+    /// Number of objects of all kinds
+    pub fn total(&self) -> usize {
+        self.objects.iter().map(|object| object.count).sum()
+    }
+}
+
+/// Census as CSV with the columns apgcode, name and count
+pub fn write_csv(census: &Census) -> String {
+    let mut text = String::from("apgcode,name,count\n");
+    for object in &census.objects {
+        text.push_str(&format!(
+            "{},{},{}\n",
+            object.apgcode,
+            object.name.unwrap_or(""),
+            object.count
+        ));
+    }
+    text
+}
+
+/// Census as a JSON object with the rule and a list of objects
+pub fn write_json(census: &Census) -> String {
+    let objects: Vec<String> = census
+        .objects
+        .iter()
+        .map(|object| {
+            let name = match object.name {
+                Some(name) => format!("\"{}\"", name),
+                None => "null".to_string(),
+            };
+            format!(
+                "    {{ \"apgcode\": \"{}\", \"name\": {}, \"count\": {} }}",
+                object.apgcode, name, object.count
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"rule\": \"{}\",\n  \"objects\": [\n{}\n  ]\n}}\n",
+        census.rule,
+        objects.join(",\n")
+    )
+}
+
+/// Name of an object in Conway's Game of Life
+pub fn name(apgcode: &str) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|(code, _)| *code == apgcode)
+        .map(|&(_, name)| name)
+}
+
+/// Groups live cells into islands of cells that lie at most reach cells apart, horizontally, vertically or
+/// diagonally. With a reach of 1, islands are the cells that touch
+pub fn islands(live_cells: &[Position], reach: isize) -> Vec<Vec<Position>> {
+    let mut unvisited: HashSet<Position> = live_cells.iter().copied().collect();
+    let mut islands = vec![];
+
+    let mut sorted_cells = live_cells.to_vec();
+    sorted_cells.sort_by_key(|position| (position.y, position.x));
+    for start in sorted_cells {
+        if !unvisited.remove(&start) {
+            continue;
+        }
+
+        let mut island = vec![start];
+        let mut index = 0;
+        while index < island.len() {
+            let position = island[index];
+            for y_offset in -reach..=reach {
+                for x_offset in -reach..=reach {
+                    let neighbor = position
+                        + Position {
+                            x: x_offset,
+                            y: y_offset,
+                        };
+                    if unvisited.remove(&neighbor) {
+                        island.push(neighbor);
+                    }
+                }
+            }
+            index += 1;
+        }
+        islands.push(island);
+    }
+    islands
+}
+
+/// Runs an island on its own and names it by how it repeats: xs followed by the population for still lifes, xp and
+/// xq followed by the period for oscillators and spaceships. None if it doesn't repeat from the start within
+/// MAX_GENERATIONS
+pub fn apgcode(island: &[Position], rule: &Rule) -> Option<String> {
+    classify(island, rule).map(|(apgcode, _)| apgcode)
+}
+
+// Apgcode of an island along with the live cells of each generation in its period, starting with the island itself
+fn classify(island: &[Position], rule: &Rule) -> Option<(String, Vec<Vec<Position>>)> {
+    if rule.births(0)
+        || rule.states() > 2
+        || rule.larger_than_life().is_some()
+        || rule.tiling() != Tiling::Square
+        || rule.table().is_some()
+        || island.is_empty()
+    {
+        return None;
+    }
+
+    let mut grid = SparseGrid::new();
+    let mut transitions = vec![];
+    for &position in island {
+        grid.set(position, CellState::Alive);
+        transitions.push((position, CellState::Alive));
+    }
+    let previous_states = vec![CellState::Dead; transitions.len()];
+    let mut cycles = CycleDetector::new();
+    let mut spaceships = SpaceshipDetector::new();
+    cycles.restart(0, &transitions, &previous_states);
+    spaceships.restart(0, &transitions, &previous_states);
+
+    let mut phases = vec![island.to_vec()];
+    for generation in 1..=MAX_GENERATIONS {
+        let transitions = grid.step(rule);
+        let previous_states: Vec<CellState> = transitions
+            .iter()
+            .map(|&(_, state)| rule.previous_state(state))
+            .collect();
+        let cycle = cycles.step(generation, &transitions, &previous_states);
+        let spaceship = spaceships.step(generation, &transitions, &previous_states);
+
+        let prefix = match (cycle, spaceship) {
+            (Some(cycle), _) if cycle.start == 0 && cycle.period == 1 => {
+                format!("xs{}", island.len())
+            }
+            (Some(cycle), _) if cycle.start == 0 => format!("xp{}", cycle.period),
+            (_, Some(spaceship)) if spaceship.start == 0 => format!("xq{}", spaceship.period),
+            (None, None) => {
+                phases.push(grid.live_cells());
+                continue;
+            }
+            _ => break, // Settles into something else, so it's not an object by itself
+        };
+        let code = phases
+            .iter()
+            .flat_map(|phase| orientations(phase))
+            .map(|cells| wechsler(&cells))
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+            .unwrap();
+        return Some((format!("{}_{}", prefix, code), phases));
+    }
+    None
+}
+
+// Generations of all live cells on the grid together, computed as far as they are needed
+struct Surroundings {
+    grid: SparseGrid,
+    rule: Rule,
+    generations: Vec<HashSet<Position>>,
+}
+
+impl Surroundings {
+    fn new(live_cells: &[Position], rule: Rule) -> Surroundings {
+        let mut grid = SparseGrid::new();
+        for &position in live_cells {
+            grid.set(position, CellState::Alive);
+        }
+        Surroundings {
+            generations: vec![live_cells.iter().copied().collect()],
+            grid,
+            rule,
+        }
+    }
+
+    // Whether each generation of an object run on its own matches all cells in and right around it
+    fn agree(&mut self, phases: &[Vec<Position>]) -> bool {
+        while self.generations.len() < phases.len() {
+            self.grid.step(&self.rule);
+            self.generations
+                .push(self.grid.live_cells().into_iter().collect());
+        }
+
+        phases
+            .iter()
+            .zip(&self.generations)
+            .all(|(phase, generation)| {
+                let phase: HashSet<Position> = phase.iter().copied().collect();
+                phase.iter().all(|&position| {
+                    (-1..=1).all(|y| {
+                        (-1..=1).all(|x| {
+                            let neighbor = position + Position { x, y };
+                            phase.contains(&neighbor) == generation.contains(&neighbor)
+                        })
+                    })
+                })
+            })
+    }
+}
+
+// All 8 rotations and reflections of the cells
+fn orientations(cells: &[Position]) -> Vec<Vec<Position>> {
+    let transforms: [fn(Position) -> Position; 8] = [
+        |p| Position { x: p.x, y: p.y },
+        |p| Position { x: -p.x, y: p.y },
+        |p| Position { x: p.x, y: -p.y },
+        |p| Position { x: -p.x, y: -p.y },
+        |p| Position { x: p.y, y: p.x },
+        |p| Position { x: -p.y, y: p.x },
+        |p| Position { x: p.y, y: -p.x },
+        |p| Position { x: -p.y, y: -p.x },
+    ];
+    transforms
+        .iter()
+        .map(|transform| cells.iter().map(|&position| transform(position)).collect())
+        .collect()
+}
+
+// Extended Wechsler format of the cells: strips of 5 rows separated by z, with each column of a strip as one
+// character for the 5 bits of its cells, top bit lowest. Trailing empty columns are left out, and runs of empty
+// columns are shortened to w for 2, x for 3 and y followed by a character for 4 to 39
+fn wechsler(cells: &[Position]) -> String {
+    let min_x = cells.iter().map(|position| position.x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|position| position.y).min().unwrap_or(0);
+    let width = cells
+        .iter()
+        .map(|position| position.x - min_x + 1)
+        .max()
+        .unwrap_or(0) as usize;
+    let height = cells
+        .iter()
+        .map(|position| position.y - min_y + 1)
+        .max()
+        .unwrap_or(0) as usize;
+
+    let strip_count = height.div_ceil(5);
+    let mut columns = vec![vec![0; width]; strip_count];
+    for position in cells {
+        let (x, y) = ((position.x - min_x) as usize, (position.y - min_y) as usize);
+        columns[y / 5][x] |= 1 << (y % 5);
+    }
+
+    let strips: Vec<String> = columns
+        .iter()
+        .map(|strip| {
+            let last = strip
+                .iter()
+                .rposition(|&column| column != 0)
+                .map_or(0, |index| index + 1);
+            let mut text = String::new();
+            let mut empty_run = 0;
+            for &column in &strip[..last] {
+                if column == 0 {
+                    empty_run += 1;
+                    continue;
+                }
+                push_empty_run(&mut text, empty_run);
+                empty_run = 0;
+                text.push(COLUMN_CHARACTERS[column] as char);
+            }
+            text
+        })
+        .collect();
+    strips.join("z")
+}
+
+fn push_empty_run(text: &mut String, mut length: usize) {
+    while length > 0 {
+        match length {
+            1 => text.push('0'),
+            2 => text.push('w'),
+            3 => text.push('x'),
+            _ => {
+                let run = length.min(39);
+                text.push('y');
+                text.push(COLUMN_CHARACTERS[run - 4] as char);
+                length -= run;
+                continue;
+            }
+        }
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: [(isize, isize); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+    const BLINKER: [(isize, isize); 3] = [(0, 0), (1, 0), (2, 0)];
+    const GLIDER: [(isize, isize); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+    const LIGHTWEIGHT_SPACESHIP: [(isize, isize); 9] = [
+        (1, 0),
+        (4, 0),
+        (0, 1),
+        (0, 2),
+        (4, 2),
+        (0, 3),
+        (1, 3),
+        (2, 3),
+        (3, 3),
+    ];
+
+    type Cells<'a> = &'a [(isize, isize)];
+
+    // Cells of the patterns, each moved by its offset
+    fn cells(patterns: &[(Cells, (isize, isize))]) -> Vec<Position> {
+        patterns
+            .iter()
+            .flat_map(|&(pattern, (x_offset, y_offset))| {
+                pattern.iter().map(move |&(x, y)| Position {
+                    x: x + x_offset,
+                    y: y + y_offset,
+                })
+            })
+            .collect()
+    }
+
+    fn entry(apgcode: &str, name: Option<&'static str>, count: usize) -> CensusEntry {
+        CensusEntry {
+            apgcode: apgcode.to_string(),
+            name,
+            count,
+        }
+    }
+
+    // A bi-block, a blinker and a glider, far enough apart not to interact
+    fn small_soup() -> Census {
+        Census::take(
+            &cells(&[
+                (&BLOCK, (0, 0)),
+                (&BLOCK, (3, 0)),
+                (&BLINKER, (20, 0)),
+                (&GLIDER, (0, 20)),
+            ]),
+            Rule::default(),
+        )
+    }
+
+    #[test]
+    fn names_known_objects_by_their_apgcodes() {
+        let rule = Rule::default();
+        let known = [
+            (&BLOCK[..], "xs4_33"),
+            (&BLINKER[..], "xp2_7"),
+            (&GLIDER[..], "xq4_153"),
+            (&LIGHTWEIGHT_SPACESHIP[..], "xq4_6frc"),
+        ];
+        for &(pattern, code) in &known {
+            let island = cells(&[(pattern, (0, 0))]);
+            assert_eq!(apgcode(&island, &rule).as_deref(), Some(code));
+
+            // Any orientation, phase or place gives the same code
+            let mut grid = SparseGrid::new();
+            for position in island {
+                grid.set(
+                    Position {
+                        x: 7 - position.y,
+                        y: position.x - 3,
+                    },
+                    CellState::Alive,
+                );
+            }
+            grid.step(&rule);
+            assert_eq!(apgcode(&grid.live_cells(), &rule).as_deref(), Some(code));
+        }
+        assert_eq!(name("xs4_33"), Some("block"));
+        assert_eq!(name("xq4_6frc"), Some("lightweight spaceship"));
+        assert_eq!(name("xs4_34"), None);
+    }
+
+    #[test]
+    fn splits_objects_that_touch_or_nearly_touch() {
+        // Two blocks one empty column apart are two objects, not one bi-block
+        assert_eq!(small_soup().objects[0], entry("xs4_33", Some("block"), 2));
+
+        // Blocks that touch at a corner form a beacon, which is one object
+        let beacon = Census::take(
+            &cells(&[(&BLOCK, (0, 0)), (&BLOCK, (2, 2))]),
+            Rule::default(),
+        );
+        assert_eq!(beacon.objects, vec![entry("xp2_318c", Some("beacon"), 1)]);
+
+        // A block and a blinker that touch aren't objects on their own, and together they don't repeat
+        let crowded = Census::take(
+            &cells(&[(&BLOCK, (0, 0)), (&BLINKER, (2, 1))]),
+            Rule::default(),
+        );
+        assert_eq!(crowded.objects, vec![entry("PATHOLOGICAL", None, 1)]);
+
+        // The parts of a lightweight spaceship that don't touch are grouped into one object
+        let mut grid = SparseGrid::new();
+        for position in cells(&[(&LIGHTWEIGHT_SPACESHIP, (0, 0))]) {
+            grid.set(position, CellState::Alive);
+        }
+        let phases: Vec<Vec<Position>> = (0..4)
+            .map(|_| {
+                grid.step(&Rule::default());
+                grid.live_cells()
+            })
+            .collect();
+        let split_phase = phases
+            .iter()
+            .find(|phase| islands(phase, 1).len() > 1)
+            .expect("The lightweight spaceship has a phase made of parts that don't touch");
+        assert_eq!(
+            Census::take(split_phase, Rule::default()).objects,
+            vec![entry("xq4_6frc", Some("lightweight spaceship"), 1)]
+        );
+    }
+
+    #[test]
+    fn counts_objects_of_each_kind() {
+        let census = small_soup();
+        assert_eq!(
+            census.objects,
+            vec![
+                entry("xs4_33", Some("block"), 2),
+                entry("xp2_7", Some("blinker"), 1),
+                entry("xq4_153", Some("glider"), 1),
+            ]
+        );
+        assert_eq!(census.total(), 4);
+
+        // Names are only known for Conway's Game of Life
+        let highlife = Census::take(
+            &cells(&[(&BLOCK, (0, 0))]),
+            Rule::new(&[3, 6], &[2, 3]).unwrap(),
+        );
+        assert_eq!(highlife.objects, vec![entry("xs4_33", None, 1)]);
+    }
+
+    #[test]
+    fn writes_csv() {
+        assert_eq!(
+            write_csv(&small_soup()),
+            "apgcode,name,count\n\
+             xs4_33,block,2\n\
+             xp2_7,blinker,1\n\
+             xq4_153,glider,1\n"
+        );
+    }
+
+    #[test]
+    fn writes_json() {
+        let highlife = Census::take(
+            &cells(&[(&BLOCK, (0, 0)), (&BLINKER, (10, 0))]),
+            Rule::new(&[3, 6], &[2, 3]).unwrap(),
+        );
+        assert_eq!(
+            write_json(&small_soup()),
+            r#"{
+  "rule": "B3/S23",
+  "objects": [
+    { "apgcode": "xs4_33", "name": "block", "count": 2 },
+    { "apgcode": "xp2_7", "name": "blinker", "count": 1 },
+    { "apgcode": "xq4_153", "name": "glider", "count": 1 }
+  ]
+}
+"#
+        );
+        assert_eq!(
+            write_json(&highlife),
+            r#"{
+  "rule": "B36/S23",
+  "objects": [
+    { "apgcode": "xp2_7", "name": null, "count": 1 },
+    { "apgcode": "xs4_33", "name": null, "count": 1 }
+  ]
+}
+"#
+        );
+    }
+}
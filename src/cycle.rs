@@ -0,0 +1,516 @@
+use crate::simulation::{CellState, Position};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+// Longest period that is looked for. Every generation in this window keeps its hash around
+const MAX_PERIOD: usize = 4096;
+
+// Prime modulus 2^61 - 1 and bases of the translation-invariant hash
+const MODULUS: u64 = (1 << 61) - 1;
+const X_BASE: u64 = 0x1d8e_4e27_c47d_124f % MODULUS;
+const Y_BASE: u64 = 0x0b3a_9f5c_6e21_d7a3 % MODULUS;
+
+/// Pattern that keeps repeating itself. A still life has period 1, and a pattern that died out counts as a still life
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize, // First generation of the cycle
+    pub period: usize,
+}
+
+/// Pattern that repeats itself in a different place, like a glider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spaceship {
+    pub start: usize, // First generation of the cycle
+    pub period: usize,
+    pub displacement: Position, // How far the pattern moves each period
+}
+
+impl Spaceship {
+    /// Speed in the usual notation, where c is one cell per generation: a glider moves at c/4, and a lightweight
+    /// spaceship moving 2 cells every 4 generations at c/2. Oblique ships give both parts of their displacement,
+    /// the longer one first, so a knightship moving 2 cells one way and 1 the other every 6 generations is (2,1)c/6
+    pub fn speed(&self) -> String {
+        let (x, y) = (
+            self.displacement.x.unsigned_abs(),
+            self.displacement.y.unsigned_abs(),
+        );
+        let (long, short) = (x.max(y), x.min(y));
+        if short != 0 && short != long {
+            let divisor = gcd(gcd(long, short), self.period);
+            return format!(
+                "({},{})c/{}",
+                long / divisor,
+                short / divisor,
+                self.period / divisor
+            );
+        }
+
+        let divisor = gcd(long, self.period);
+        match (long / divisor, self.period / divisor) {
+            (1, 1) => "c".to_string(),
+            (1, period) => format!("c/{}", period),
+            (distance, period) => format!("{}c/{}", distance, period),
+        }
+    }
+}
+
+impl fmt::Display for Spaceship {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "period {}, displacement ({}, {}), speed {}",
+            self.period,
+            self.displacement.x,
+            self.displacement.y,
+            self.speed()
+        )
+    }
+}
+
+/// Recognizes still lifes and oscillators by hashing every generation. The hash of a grid combines the hashes of its
+/// cells that aren't dead with XOR, so the transitions of a generation are enough to update it.
+/// Two different grids can have the same hash, but with 64 bits that is unlikely enough to ignore
+pub struct CycleDetector {
+    hash: u64,
+    window: Window<()>,
+    cycle: Option<Cycle>,
+}
+
+impl CycleDetector {
+    pub fn new() -> CycleDetector {
+        CycleDetector {
+            hash: 0,
+            window: Window::new(),
+            cycle: None,
+        }
+    }
+
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    /// Takes in the cells that changed to reach the generation, which has to follow the last one the rule produced,
+    /// along with the states they changed from. Returns the cycle the first time it shows up
+    pub fn step(
+        &mut self,
+        generation: usize,
+        transitions: &[(Position, CellState)],
+        previous_states: &[CellState],
+    ) -> Option<Cycle> {
+        self.apply(transitions, previous_states);
+        if self.cycle.is_some() {
+            return None;
+        }
+
+        if let Some((start, ())) = self.window.get(self.hash) {
+            self.cycle = Some(Cycle {
+                start,
+                period: generation - start,
+            });
+            return self.cycle;
+        }
+
+        self.window.remember(generation, self.hash, ());
+        None
+    }
+
+    /// Starts over from the generation after the cells changed some other way than by the rule, like an edit.
+    /// Generations before then can't tell whether the grid repeats anymore
+    pub fn restart(
+        &mut self,
+        generation: usize,
+        transitions: &[(Position, CellState)],
+        previous_states: &[CellState],
+    ) {
+        self.apply(transitions, previous_states);
+        self.window.clear();
+        self.cycle = None;
+        self.window.remember(generation, self.hash, ());
+    }
+
+    fn apply(&mut self, transitions: &[(Position, CellState)], previous_states: &[CellState]) {
+        // A cell leaving a state flips back the same bits it flipped when it entered it
+        for (&(position, state), &previous_state) in transitions.iter().zip(previous_states) {
+            self.hash ^= cell_hash(position, previous_state) ^ cell_hash(position, state);
+        }
+    }
+}
+
+impl Default for CycleDetector {
+    fn default() -> Self {
+        CycleDetector::new()
+    }
+}
+
+/// Recognizes spaceships by hashing the shape of every generation, wherever it lies. Each live cell at (x, y) adds
+/// X_BASE^x * Y_BASE^y to a sum modulo a prime, and dying cells add a multiple of that for their age. Dividing the sum
+/// by the term of the top left corner of the cells gives the same hash for the same shape anywhere on the grid.
+/// Only a pattern that moves as a whole counts, so a glider next to a block is no spaceship, and neither is one that
+/// wraps around the edge of a torus
+pub struct SpaceshipDetector {
+    sum: u64,
+    columns: BTreeMap<isize, usize>, // Number of cells that aren't dead in each column that has any
+    rows: BTreeMap<isize, usize>,
+    window: Window<Position>, // Top left corner of each generation
+    spaceship: Option<Spaceship>,
+    is_done: bool, // Whether the pattern repeated, in place or not
+}
+
+impl SpaceshipDetector {
+    pub fn new() -> SpaceshipDetector {
+        SpaceshipDetector {
+            sum: 0,
+            columns: BTreeMap::new(),
+            rows: BTreeMap::new(),
+            window: Window::new(),
+            spaceship: None,
+            is_done: false,
+        }
+    }
+
+    pub fn spaceship(&self) -> Option<Spaceship> {
+        self.spaceship
+    }
+
+    /// Takes in the cells that changed to reach the generation, which has to follow the last one the rule produced,
+    /// along with the states they changed from. Returns the spaceship the first time it shows up. Patterns that
+    /// repeat in place are left to [`CycleDetector`]
+    pub fn step(
+        &mut self,
+        generation: usize,
+        transitions: &[(Position, CellState)],
+        previous_states: &[CellState],
+    ) -> Option<Spaceship> {
+        self.apply(transitions, previous_states);
+        if self.is_done {
+            return None;
+        }
+
+        let (hash, corner) = self.shape();
+        if let Some((start, old_corner)) = self.window.get(hash) {
+            self.is_done = true;
+            let displacement = Position {
+                x: corner.x - old_corner.x,
+                y: corner.y - old_corner.y,
+            };
+            if displacement != (Position { x: 0, y: 0 }) {
+                self.spaceship = Some(Spaceship {
+                    start,
+                    period: generation - start,
+                    displacement,
+                });
+            }
+            return self.spaceship;
+        }
+
+        self.window.remember(generation, hash, corner);
+        None
+    }
+
+    /// Starts over from the generation after the cells changed some other way than by the rule, like an edit
+    pub fn restart(
+        &mut self,
+        generation: usize,
+        transitions: &[(Position, CellState)],
+        previous_states: &[CellState],
+    ) {
+        self.apply(transitions, previous_states);
+        self.window.clear();
+        self.spaceship = None;
+        self.is_done = false;
+        let (hash, corner) = self.shape();
+        self.window.remember(generation, hash, corner);
+    }
+
+    fn apply(&mut self, transitions: &[(Position, CellState)], previous_states: &[CellState]) {
+        for (&(position, state), &previous_state) in transitions.iter().zip(previous_states) {
+            let term = mul_mod(pow_mod(X_BASE, position.x), pow_mod(Y_BASE, position.y));
+            self.sum = (self.sum + mul_mod(term, state_weight(state))) % MODULUS;
+            self.sum = (self.sum + MODULUS - mul_mod(term, state_weight(previous_state))) % MODULUS;
+
+            match (previous_state, state) {
+                (CellState::Dead, CellState::Dead) => (),
+                (CellState::Dead, _) => {
+                    *self.columns.entry(position.x).or_insert(0) += 1;
+                    *self.rows.entry(position.y).or_insert(0) += 1;
+                }
+                (_, CellState::Dead) => {
+                    remove_one(&mut self.columns, position.x);
+                    remove_one(&mut self.rows, position.y);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Hash of the cells moved so that their top left corner is on (0, 0), along with that corner
+    fn shape(&self) -> (u64, Position) {
+        let corner = Position {
+            x: self.columns.keys().next().copied().unwrap_or(0),
+            y: self.rows.keys().next().copied().unwrap_or(0),
+        };
+        let hash = mul_mod(
+            self.sum,
+            mul_mod(pow_mod(X_BASE, -corner.x), pow_mod(Y_BASE, -corner.y)),
+        );
+        (hash, corner)
+    }
+}
+
+impl Default for SpaceshipDetector {
+    fn default() -> Self {
+        SpaceshipDetector::new()
+    }
+}
+
+// Hashes of the last MAX_PERIOD generations, with a value kept for the first generation each hash showed up in
+struct Window<T> {
+    generations: VecDeque<(usize, u64)>, // Generations in the window and their hashes, oldest first
+    first_seen: HashMap<u64, (usize, T)>,
+}
+
+impl<T: Copy> Window<T> {
+    fn new() -> Window<T> {
+        Window {
+            generations: VecDeque::new(),
+            first_seen: HashMap::new(),
+        }
+    }
+
+    fn get(&self, hash: u64) -> Option<(usize, T)> {
+        self.first_seen.get(&hash).copied()
+    }
+
+    fn remember(&mut self, generation: usize, hash: u64, value: T) {
+        self.generations.push_back((generation, hash));
+        self.first_seen.entry(hash).or_insert((generation, value));
+
+        if self.generations.len() > MAX_PERIOD {
+            if let Some((oldest, hash)) = self.generations.pop_front() {
+                if self
+                    .first_seen
+                    .get(&hash)
+                    .map(|&(generation, _)| generation)
+                    == Some(oldest)
+                {
+                    self.first_seen.remove(&hash);
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.generations.clear();
+        self.first_seen.clear();
+    }
+}
+
+// Hash of a cell in a state. Dead cells hash to 0, so they leave the hash of the grid alone
+fn cell_hash(position: Position, state: CellState) -> u64 {
+    let age = match state {
+        CellState::Dead => return 0,
+        CellState::Alive => 0,
+        CellState::Dying(age) => age,
+    };
+    // The odd constant keeps a live cell on (0, 0) from hashing to 0 as well
+    mix((position.x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (position.y as u64)
+        ^ (age as u64 + 1).wrapping_mul(0xd6e8_feb8_6659_fd93))
+}
+
+// Multiple of a cell's term in the translation-invariant hash
+fn state_weight(state: CellState) -> u64 {
+    match state {
+        CellState::Dead => 0,
+        CellState::Alive => 1,
+        CellState::Dying(age) => mix(age as u64) % MODULUS,
+    }
+}
+
+// SplitMix64 finalizer, which spreads the bits of a value over all 64 bits
+fn mix(mut hash: u64) -> u64 {
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+fn mul_mod(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % MODULUS as u128) as u64
+}
+
+// Base to the power of a coordinate, which can be negative. Powers of a negative exponent use the inverse of the
+// base, which by Fermat's little theorem is base^(MODULUS - 2)
+fn pow_mod(base: u64, exponent: isize) -> u64 {
+    let mut base = if exponent < 0 {
+        pow_mod(base, (MODULUS - 2) as isize)
+    } else {
+        base
+    };
+    let mut exponent = exponent.unsigned_abs();
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base);
+        }
+        base = mul_mod(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn remove_one(counts: &mut BTreeMap<isize, usize>, key: isize) {
+    if let Some(count) = counts.get_mut(&key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&key);
+        }
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::rule::Rule;
+    use crate::sparse::SparseGrid;
+
+    const GLIDER: [(isize, isize); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+    const LIGHTWEIGHT_SPACESHIP: [(isize, isize); 9] = [
+        (1, 0),
+        (4, 0),
+        (0, 1),
+        (0, 2),
+        (4, 2),
+        (0, 3),
+        (1, 3),
+        (2, 3),
+        (3, 3),
+    ];
+
+    // Cells that changed state, along with the states they changed from
+    type Changes = (Vec<(Position, CellState)>, Vec<CellState>);
+
+    // Runs the pattern under Life. Returns its cells and the changes of each generation
+    fn evolve(
+        cells: &[(isize, isize)],
+        generations: usize,
+    ) -> (Vec<(Position, CellState)>, Vec<Changes>) {
+        let rule = Rule::default();
+        let mut grid = SparseGrid::new();
+        let cells: Vec<(Position, CellState)> = cells
+            .iter()
+            .map(|&(x, y)| (Position { x, y }, CellState::Alive))
+            .collect();
+        for &(position, state) in &cells {
+            grid.set(position, state);
+        }
+        let changes = (0..generations)
+            .map(|_| grid.step_with_previous_states(&rule))
+            .collect();
+        (cells, changes)
+    }
+
+    fn first_cycle(cells: &[(isize, isize)], generations: usize) -> Option<Cycle> {
+        let (cells, changes) = evolve(cells, generations);
+        let mut detector = CycleDetector::new();
+        detector.restart(0, &cells, &vec![CellState::Dead; cells.len()]);
+        changes
+            .iter()
+            .enumerate()
+            .find_map(|(index, (transitions, previous_states))| {
+                detector.step(index + 1, transitions, previous_states)
+            })
+    }
+
+    fn first_spaceship(cells: &[(isize, isize)], generations: usize) -> Option<Spaceship> {
+        let (cells, changes) = evolve(cells, generations);
+        let mut detector = SpaceshipDetector::new();
+        detector.restart(0, &cells, &vec![CellState::Dead; cells.len()]);
+        changes
+            .iter()
+            .enumerate()
+            .find_map(|(index, (transitions, previous_states))| {
+                detector.step(index + 1, transitions, previous_states)
+            })
+    }
+
+    #[test]
+    fn finds_still_lifes_from_when_they_settle() {
+        // Three cells of a block, which fill in the fourth in the first generation
+        assert_eq!(
+            first_cycle(&[(0, 0), (1, 0), (0, 1)], 10),
+            Some(Cycle {
+                start: 1,
+                period: 1
+            })
+        );
+    }
+
+    #[test]
+    fn finds_oscillators() {
+        assert_eq!(
+            first_cycle(&[(0, 1), (1, 1), (2, 1)], 10),
+            Some(Cycle {
+                start: 0,
+                period: 2
+            })
+        );
+    }
+
+    #[test]
+    fn patterns_that_never_repeat_in_place_have_no_cycle() {
+        assert_eq!(first_cycle(&GLIDER, 100), None);
+    }
+
+    #[test]
+    fn finds_spaceships() {
+        let glider = first_spaceship(&GLIDER, 100).unwrap();
+        assert_eq!(
+            glider,
+            Spaceship {
+                start: 0,
+                period: 4,
+                displacement: Position { x: 1, y: 1 },
+            }
+        );
+        assert_eq!(glider.speed(), "c/4");
+
+        let lightweight_spaceship = first_spaceship(&LIGHTWEIGHT_SPACESHIP, 100).unwrap();
+        assert_eq!(lightweight_spaceship.period, 4);
+        assert_eq!(lightweight_spaceship.displacement, Position { x: -2, y: 0 });
+        assert_eq!(lightweight_spaceship.speed(), "c/2");
+    }
+
+    #[test]
+    fn still_lifes_and_oscillators_are_no_spaceships() {
+        assert_eq!(first_spaceship(&[(0, 0), (1, 0), (0, 1)], 10), None);
+        assert_eq!(first_spaceship(&[(0, 1), (1, 1), (2, 1)], 10), None);
+    }
+
+    #[test]
+    fn writes_speeds_in_the_usual_notation() {
+        let speed = |x, y, period| {
+            Spaceship {
+                start: 0,
+                period,
+                displacement: Position { x, y },
+            }
+            .speed()
+        };
+        assert_eq!(speed(0, -1, 1), "c");
+        assert_eq!(speed(-2, 0, 4), "c/2");
+        assert_eq!(speed(0, 2, 3), "2c/3");
+        assert_eq!(speed(-3, 3, 12), "c/4");
+        assert_eq!(speed(2, -1, 6), "(2,1)c/6");
+        assert_eq!(speed(-1, 2, 6), "(2,1)c/6");
+        assert_eq!(speed(4, 2, 12), "(2,1)c/6");
+    }
+}
@@ -0,0 +1,470 @@
+use crate::bitgrid::BitGrid;
+use crate::hashlife::HashLife;
+use crate::rule::{LargerThanLife, Rule, NEIGHBORS};
+use crate::rule_table::RuleTable;
+use crate::simulation::{Cell, CellState, Position};
+use crate::sparse::SparseGrid;
+use crate::summed_area::SummedAreaTable;
+use crate::tiling::Tiling;
+use crate::topology::Topology;
+
+use anyhow::{bail, Result};
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Backend that stores the cell grid and computes generations for the simulation thread
+pub trait Engine: Send {
+    /// Advances the grid by one generation and returns the cells that changed state
+    fn step(&mut self, rule: &Rule) -> Vec<(Position, CellState)>;
+    /// State of a cell, which has to lie on the grid for bounded engines
+    fn get(&self, position: Position) -> CellState;
+    /// Changes the state of a cell, which has to lie on the grid for bounded engines
+    fn set(&mut self, position: Position, state: CellState);
+    /// Positions of all live cells, in no particular order
+    fn live_cells(&self) -> Vec<Position>;
+
+    /// Positions and states of all cells that aren't dead, in no particular order. Only engines that run Generations
+    /// rules have cells in other states than alive
+    fn cells(&self) -> Vec<(Position, CellState)> {
+        self.live_cells()
+            .into_iter()
+            .map(|position| (position, CellState::Alive))
+            .collect()
+    }
+
+    /// Number of live cells
+    fn population(&self) -> usize {
+        self.live_cells().len()
+    }
+
+    /// Cells that aren't dead within the rectangle with the given top left corner and size, in no particular order
+    fn cells_in(
+        &self,
+        corner: Position,
+        width: usize,
+        height: usize,
+    ) -> Vec<(Position, CellState)> {
+        let (xs, ys) = (
+            corner.x..corner.x + width as isize,
+            corner.y..corner.y + height as isize,
+        );
+        self.cells()
+            .into_iter()
+            .filter(|(position, _)| xs.contains(&position.x) && ys.contains(&position.y))
+            .collect()
+    }
+
+    /// Number of threads to split each generation across. Engines that can't split up their work ignore this
+    fn set_threads(&mut self, _threads: usize) {}
+
+    /// Advances the grid by one generation and returns the cells that changed state, along with the states they
+    /// changed from. Those come from the rule where it can tell, and from the grid before the step otherwise
+    fn step_with_previous_states(
+        &mut self,
+        rule: &Rule,
+    ) -> (Vec<(Position, CellState)>, Vec<CellState>) {
+        if rule.has_previous_states() {
+            let transitions = self.step(rule);
+            let previous_states = transitions
+                .iter()
+                .map(|&(_, state)| rule.previous_state(state))
+                .collect();
+            return (transitions, previous_states);
+        }
+
+        let states: HashMap<Position, CellState> = self.cells().into_iter().collect();
+        let transitions = self.step(rule);
+        let previous_states = transitions
+            .iter()
+            .map(|(position, _)| states.get(position).copied().unwrap_or(CellState::Dead))
+            .collect();
+        (transitions, previous_states)
+    }
+
+    /// Advances the grid by several generations and returns the cells whose state differs from before
+    fn advance(&mut self, rule: &Rule, generations: usize) -> Vec<(Position, CellState)> {
+        let mut original_states = HashMap::new();
+        for _ in 0..generations {
+            let (transitions, previous_states) = self.step_with_previous_states(rule);
+            for ((position, _), previous_state) in transitions.into_iter().zip(previous_states) {
+                // A cell's first transition tells which state it started out in
+                original_states.entry(position).or_insert(previous_state);
+            }
+        }
+
+        let mut transitions: Vec<(Position, CellState)> = original_states
+            .into_iter()
+            .map(|(position, original_state)| (position, original_state, self.get(position)))
+            .filter(|(_, original_state, state)| original_state != state)
+            .map(|(position, _, state)| (position, state))
+            .collect();
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+        transitions
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EngineKind {
+    Naive, // Reference implementation for the benchmarks, and the bounded grid for Generations rules
+    #[default]
+    BitPacked,
+    HashLife, // Only for tori with powers of two as width and height
+    Sparse,
+    UnboundedHashLife, // For patterns far too large to list every cell of, like the ones in macrocell files
+}
+
+impl EngineKind {
+    /// Engines to choose from in the UI
+    pub const ALL: [EngineKind; 5] = [
+        EngineKind::BitPacked,
+        EngineKind::HashLife,
+        EngineKind::Sparse,
+        EngineKind::UnboundedHashLife,
+        EngineKind::Naive,
+    ];
+
+    /// The topology only matters for bounded engines
+    pub fn create(
+        self,
+        width: usize,
+        height: usize,
+        topology: Topology,
+    ) -> Result<Box<dyn Engine>> {
+        if self == EngineKind::HashLife && !topology.is_torus() {
+            bail!("HashLife only works on a torus, not on a {}", topology);
+        }
+
+        Ok(match self {
+            EngineKind::Naive => Box::new(NaiveGrid::new(width, height, topology)),
+            EngineKind::BitPacked => Box::new(BitGrid::new(width, height, topology)),
+            EngineKind::HashLife => Box::new(HashLife::new(width, height)?),
+            EngineKind::Sparse => Box::new(SparseGrid::new()),
+            EngineKind::UnboundedHashLife => Box::new(HashLife::unbounded()),
+        })
+    }
+
+    /// Bounded engines have a fixed grid with edges, unbounded ones extend infinitely in every direction
+    pub fn is_bounded(self) -> bool {
+        self != EngineKind::Sparse && self != EngineKind::UnboundedHashLife
+    }
+
+    pub fn supports(self, width: usize, height: usize, topology: Topology) -> bool {
+        self != EngineKind::HashLife
+            || (width.is_power_of_two() && height.is_power_of_two() && topology.is_torus())
+    }
+
+    /// Only the naive and sparse engines store cells in other states than dead and alive
+    pub fn supports_states(self, states: u8) -> bool {
+        states == 2 || self == EngineKind::Naive || self == EngineKind::Sparse
+    }
+
+    /// The bit-packed engine and HashLife store a single bit per cell, which leaves no room for dying states, and only
+    /// look at the direct neighbors of squares. The bit-packed engine also only counts them, without telling their
+    /// arrangements apart. Unbounded engines can't run rules with B0, which would bring the entire plane to life
+    pub fn supports_rule(self, rule: &Rule) -> bool {
+        if !self.is_bounded() && rule.births(0) {
+            return false;
+        }
+        match self {
+            EngineKind::Naive | EngineKind::Sparse => true,
+            EngineKind::HashLife | EngineKind::UnboundedHashLife => {
+                rule.states() == 2
+                    && rule.larger_than_life().is_none()
+                    && rule.table().is_none()
+                    && rule.tiling() == Tiling::Square
+            }
+            EngineKind::BitPacked => rule.states() == 2 && rule.is_totalistic(),
+        }
+    }
+}
+
+impl fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            EngineKind::Naive => "Naive",
+            EngineKind::BitPacked => "Bit-packed",
+            EngineKind::HashLife => "HashLife",
+            EngineKind::Sparse => "Unbounded plane",
+            EngineKind::UnboundedHashLife => "Unbounded HashLife",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Straightforward grid of cells that looks up all 8 neighbors of every cell. Slow, but easy to verify
+pub struct NaiveGrid {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    cell_grid: Vec<Vec<Cell>>,
+}
+
+impl NaiveGrid {
+    pub fn new(width: usize, height: usize, topology: Topology) -> NaiveGrid {
+        let cell_grid: Vec<Vec<Cell>> = (0..height as isize)
+            .map(|y| {
+                (0..width as isize)
+                    .map(|x| Cell::new(CellState::Dead, Position { x, y }))
+                    .collect()
+            })
+            .collect();
+
+        NaiveGrid {
+            width,
+            height,
+            topology,
+            cell_grid,
+        }
+    }
+
+    // Counts the live cells around each cell in a table that reaches past the edges to the cells the topology wraps
+    // around to
+    fn step_larger_than_life(
+        &mut self,
+        rule: &Rule,
+        larger_than_life: &LargerThanLife,
+    ) -> Vec<(Position, CellState)> {
+        let radius = larger_than_life.radius;
+        let origin = Position {
+            x: -(radius as isize),
+            y: -(radius as isize),
+        };
+        let table = SummedAreaTable::new(
+            origin,
+            self.width + 2 * radius,
+            self.height + 2 * radius,
+            |position| {
+                self.topology
+                    .wrap(position, self.width, self.height)
+                    .is_some_and(|position| self.get(position) == CellState::Alive)
+            },
+        );
+
+        let half_widths = larger_than_life.half_widths();
+        let transitions: Vec<(Position, CellState)> = self
+            .cell_grid
+            .iter()
+            .flatten()
+            .filter_map(|cell| {
+                let count = table.neighborhood_count(cell.position, &half_widths)
+                    - (cell.state == CellState::Alive) as usize;
+                match rule.next_state_by_count(cell.state, count) {
+                    state if state != cell.state => Some((cell.position, state)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+
+    // Looks up the states of each cell and its neighbors in the rule table
+    fn step_table(&mut self, table: &RuleTable) -> Vec<(Position, CellState)> {
+        let transitions: Vec<(Position, CellState)> = self
+            .cell_grid
+            .iter()
+            .flatten()
+            .filter_map(|cell| {
+                let mut states = vec![cell.state.index()];
+                states.extend(table.neighborhood().offsets().iter().map(|&offset| {
+                    self.topology
+                        .wrap(cell.position + offset, self.width, self.height)
+                        .map_or(0, |neighbor| self.get(neighbor).index())
+                }));
+                match CellState::from_index(table.next_state(&states)) {
+                    state if state != cell.state => Some((cell.position, state)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+
+    // Counts the live neighbors of each cell on hexagons or triangles
+    fn step_on_tiling(&mut self, rule: &Rule, tiling: Tiling) -> Vec<(Position, CellState)> {
+        let transitions: Vec<(Position, CellState)> = self
+            .cell_grid
+            .iter()
+            .flatten()
+            .filter_map(|cell| {
+                let count = tiling
+                    .neighbors(cell.position)
+                    .iter()
+                    .filter_map(|&offset| {
+                        self.topology
+                            .wrap(cell.position + offset, self.width, self.height)
+                    })
+                    .filter(|&neighbor| {
+                        neighbor != cell.position && self.get(neighbor) == CellState::Alive
+                    })
+                    .count();
+                match rule.next_state_by_count(cell.state, count) {
+                    state if state != cell.state => Some((cell.position, state)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+}
+
+impl Engine for NaiveGrid {
+    fn step(&mut self, rule: &Rule) -> Vec<(Position, CellState)> {
+        if let Some(table) = rule.table() {
+            return self.step_table(table);
+        }
+        if let Some(larger_than_life) = rule.larger_than_life() {
+            return self.step_larger_than_life(rule, &larger_than_life);
+        }
+        if rule.tiling() != Tiling::Square {
+            return self.step_on_tiling(rule, rule.tiling());
+        }
+
+        let transitions: Vec<Vec<(Position, CellState)>> = self
+            .cell_grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .filter_map(|cell| {
+                        let mut neighborhood = 0;
+                        for (bit, &offset) in NEIGHBORS.iter().enumerate() {
+                            let neighbor =
+                                self.topology
+                                    .wrap(cell.position + offset, self.width, self.height);
+
+                            match neighbor {
+                                Some(neighbor)
+                                    if neighbor != cell.position
+                                        && self.get(neighbor) == CellState::Alive =>
+                                {
+                                    neighborhood |= 1 << bit
+                                }
+                                _ => (),
+                            }
+                        }
+
+                        let position = cell.position;
+
+                        match rule.next_state(cell.state, neighborhood) {
+                            state if state != cell.state => Some((position, state)),
+                            _ => None,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let transitions: Vec<(Position, CellState)> = transitions.into_iter().flatten().collect();
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+
+    fn get(&self, position: Position) -> CellState {
+        self.cell_grid[position.y as usize][position.x as usize].state
+    }
+
+    fn set(&mut self, position: Position, state: CellState) {
+        self.cell_grid[position.y as usize][position.x as usize].state = state;
+    }
+
+    fn live_cells(&self) -> Vec<Position> {
+        self.cell_grid
+            .iter()
+            .flatten()
+            .filter(|cell| cell.state == CellState::Alive)
+            .map(|cell| cell.position)
+            .collect()
+    }
+
+    fn cells(&self) -> Vec<(Position, CellState)> {
+        self.cell_grid
+            .iter()
+            .flatten()
+            .filter(|cell| cell.state != CellState::Dead)
+            .map(|cell| (cell.position, cell.state))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Edge;
+
+    const GLIDER: [Position; 5] = [
+        Position { x: 1, y: 0 },
+        Position { x: 2, y: 1 },
+        Position { x: 0, y: 2 },
+        Position { x: 1, y: 2 },
+        Position { x: 2, y: 2 },
+    ];
+
+    fn run_glider(topology: Topology, generations: usize) -> Vec<Position> {
+        let mut grid = NaiveGrid::new(8, 8, topology);
+        for &position in &GLIDER {
+            grid.set(position, CellState::Alive);
+        }
+        grid.advance(&Rule::default(), generations);
+        let mut cells = grid.live_cells();
+        cells.sort_by_key(|position| (position.y, position.x));
+        cells
+    }
+
+    #[test]
+    fn glider_comes_back_around_a_torus() {
+        assert_eq!(run_glider(Topology::TORUS, 32), GLIDER);
+    }
+
+    #[test]
+    fn glider_comes_back_upside_down_on_a_klein_bottle() {
+        let klein_bottle = Topology {
+            horizontal: Edge::Twisted,
+            vertical: Edge::Wrapped,
+        };
+        let mut upside_down: Vec<Position> = GLIDER
+            .iter()
+            .map(|&position| Position {
+                x: position.x,
+                y: 7 - position.y,
+            })
+            .collect();
+        upside_down.sort_by_key(|position| (position.y, position.x));
+        assert_eq!(run_glider(klein_bottle, 32), upside_down);
+        assert_eq!(run_glider(klein_bottle, 64), GLIDER);
+    }
+
+    #[test]
+    fn glider_settles_into_a_block_in_a_dead_corner() {
+        let bounded_plane = Topology {
+            horizontal: Edge::Dead,
+            vertical: Edge::Dead,
+        };
+        let block = [
+            Position { x: 6, y: 6 },
+            Position { x: 7, y: 6 },
+            Position { x: 6, y: 7 },
+            Position { x: 7, y: 7 },
+        ];
+        assert_eq!(run_glider(bounded_plane, 24), block);
+        assert_eq!(run_glider(bounded_plane, 64), block);
+    }
+}
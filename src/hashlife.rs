@@ -0,0 +1,756 @@
+use crate::engine::Engine;
+use crate::macrocell::{Macrocell, MacrocellNode};
+use crate::rule::{Rule, NEIGHBORS};
+use crate::simulation::{CellState, Position};
+
+use anyhow::{bail, Result};
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+type NodeId = usize;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+// Once the node store grows past this, everything not reachable from the current grid is dropped
+const MAX_NODES: usize = 1 << 22;
+
+// The unbounded plane stops growing here, so coordinates still fit into an isize
+const MAX_PLANE_LEVEL: u8 = 60;
+
+// Quadtree node. Every node is stored only once, so identical regions share a NodeId
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Leaf(bool),
+    Branch { level: u8, children: [NodeId; 4] }, // Children ordered nw, ne, sw, se
+}
+
+/// HashLife on a torus. The grid is a single quadtree node, and the results of advancing a node are memoized,
+/// so repeating regions and repeating generations are only ever computed once.
+/// Advancing by 2^n generations costs about as much as advancing by one.
+/// Quadtree nodes are square, so a rectangular grid is repeated along its shorter side to fill the root node.
+/// On the unbounded plane, the root is centered on (0, 0) and grows whenever the pattern gets close to its edges.
+pub struct HashLife {
+    nodes: Vec<Node>,
+    ids: HashMap<Node, NodeId>,
+    results: HashMap<(NodeId, u8), NodeId>, // (node, n) -> center of node advanced by 2^n generations
+    rule: Rule,
+    empty_nodes: Vec<NodeId>, // Node without live cells for every level up to the grid's
+    root: NodeId,
+    level: u8, // The root node is 2^level cells wide
+    width: usize,
+    height: usize,
+    is_unbounded: bool,
+}
+
+impl HashLife {
+    pub fn new(width: usize, height: usize) -> Result<HashLife> {
+        if !width.is_power_of_two() || !height.is_power_of_two() {
+            bail!(
+                "HashLife needs powers of two as grid width and height, but got {}x{}",
+                width,
+                height
+            );
+        }
+
+        let mut hashlife = HashLife {
+            nodes: vec![],
+            ids: HashMap::new(),
+            results: HashMap::new(),
+            rule: Rule::default(),
+            empty_nodes: vec![],
+            root: DEAD,
+            level: width.max(height).trailing_zeros() as u8,
+            width,
+            height,
+            is_unbounded: false,
+        };
+        hashlife.intern_constants();
+        hashlife.root = hashlife.empty_nodes[hashlife.level as usize];
+
+        Ok(hashlife)
+    }
+
+    pub fn unbounded() -> HashLife {
+        let mut hashlife = HashLife {
+            nodes: vec![],
+            ids: HashMap::new(),
+            results: HashMap::new(),
+            rule: Rule::default(),
+            empty_nodes: vec![],
+            root: DEAD,
+            level: 3,
+            width: 0,
+            height: 0,
+            is_unbounded: true,
+        };
+        hashlife.intern_constants();
+        hashlife.root = hashlife.empty_nodes[hashlife.level as usize];
+        hashlife
+    }
+
+    /// Unbounded plane holding the macrocell's quadtree as it is, with its center on (0, 0). HashLife only stores
+    /// dead and live cells, so patterns with cells in other states are refused
+    pub fn from_macrocell(macrocell: &Macrocell) -> Result<HashLife> {
+        if macrocell.is_multi_state() {
+            bail!("HashLife only runs patterns with two states, but the macrocell has more");
+        }
+        let mut hashlife = HashLife::unbounded();
+
+        // Nodes only refer to nodes before them, and 0 stands for an empty node
+        let mut ids: Vec<NodeId> = vec![];
+        for node in &macrocell.nodes {
+            let id = match *node {
+                MacrocellNode::Leaf(bits) => hashlife.leaf_block(bits, 0, 0, 3),
+                MacrocellNode::States(states) => {
+                    let mut children = [DEAD; 4];
+                    for (child, &state) in children.iter_mut().zip(states.iter()) {
+                        *child = hashlife.leaf(state == 1);
+                    }
+                    hashlife.branch(children)
+                }
+                MacrocellNode::Branch { level, children } => {
+                    let mut child_ids = [DEAD; 4];
+                    for (id, &child) in child_ids.iter_mut().zip(children.iter()) {
+                        *id = match child {
+                            0 => hashlife.empty(level - 1),
+                            index => ids[index - 1],
+                        };
+                    }
+                    hashlife.branch(child_ids)
+                }
+            };
+            ids.push(id);
+        }
+
+        if let Some(&root) = ids.last() {
+            let level = hashlife.level_of(root).max(3);
+            hashlife.root = hashlife.centered(root, level);
+            hashlife.level = level;
+        }
+        Ok(hashlife)
+    }
+
+    // Node of the given level holding 8x8 cells, with bit y * 8 + x set for every live cell
+    fn leaf_block(&mut self, bits: u64, x: usize, y: usize, level: u8) -> NodeId {
+        if level == 0 {
+            return self.leaf((bits >> (y * 8 + x)) & 1 == 1);
+        }
+        let half = 1 << (level - 1);
+        let mut children = [DEAD; 4];
+        for (index, child) in children.iter_mut().enumerate() {
+            let (x, y) = (x + (index % 2) * half, y + (index / 2) * half);
+            *child = self.leaf_block(bits, x, y, level - 1);
+        }
+        self.branch(children)
+    }
+
+    // Interns the two leaves first, so they get the ids DEAD and ALIVE, then the empty nodes
+    fn intern_constants(&mut self) {
+        self.leaf(false);
+        self.leaf(true);
+
+        let mut node = DEAD;
+        self.empty_nodes = vec![node];
+        for _ in 0..self.level {
+            node = self.branch([node; 4]);
+            self.empty_nodes.push(node);
+        }
+    }
+
+    // Node without live cells of any level
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty_nodes.len() <= level as usize {
+            let node = *self.empty_nodes.last().unwrap();
+            let node = self.branch([node; 4]);
+            self.empty_nodes.push(node);
+        }
+        self.empty_nodes[level as usize]
+    }
+
+    fn intern(&mut self, node: Node) -> NodeId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.ids.insert(node, id);
+        id
+    }
+
+    fn leaf(&mut self, alive: bool) -> NodeId {
+        self.intern(Node::Leaf(alive))
+    }
+
+    fn branch(&mut self, children: [NodeId; 4]) -> NodeId {
+        let level = self.level_of(children[0]) + 1;
+        self.intern(Node::Branch { level, children })
+    }
+
+    fn level_of(&self, node: NodeId) -> u8 {
+        match self.nodes[node] {
+            Node::Leaf(_) => 0,
+            Node::Branch { level, .. } => level,
+        }
+    }
+
+    fn children(&self, node: NodeId) -> [NodeId; 4] {
+        match self.nodes[node] {
+            Node::Leaf(_) => panic!("Leaf nodes have no children"),
+            Node::Branch { children, .. } => children,
+        }
+    }
+
+    fn state(node: NodeId) -> CellState {
+        if node == ALIVE {
+            CellState::Alive
+        } else {
+            CellState::Dead
+        }
+    }
+
+    // Node of the next lower level made up of the centers of the four given nodes
+    fn center(&mut self, [nw, ne, sw, se]: [NodeId; 4]) -> NodeId {
+        let nw = self.children(nw)[3];
+        let ne = self.children(ne)[2];
+        let sw = self.children(sw)[1];
+        let se = self.children(se)[0];
+        self.branch([nw, ne, sw, se])
+    }
+
+    fn horizontal_center(&mut self, west: NodeId, east: NodeId) -> NodeId {
+        let [_, west_ne, _, west_se] = self.children(west);
+        let [east_nw, _, east_sw, _] = self.children(east);
+        self.branch([west_ne, east_nw, west_se, east_sw])
+    }
+
+    fn vertical_center(&mut self, north: NodeId, south: NodeId) -> NodeId {
+        let [_, _, north_sw, north_se] = self.children(north);
+        let [south_nw, south_ne, _, _] = self.children(south);
+        self.branch([north_sw, north_se, south_nw, south_ne])
+    }
+
+    // Center of the node, half its size, advanced by 2^step generations. step may be at most level - 2
+    fn successor(&mut self, node: NodeId, step: u8) -> NodeId {
+        if let Some(&result) = self.results.get(&(node, step)) {
+            return result;
+        }
+
+        let level = self.level_of(node);
+        let result = if level == 2 {
+            self.successor_by_rule(node)
+        } else {
+            let [nw, ne, sw, se] = self.children(node);
+
+            // Nine overlapping subnodes, each a quarter of the node, arranged in a 3x3 grid
+            let n00 = nw;
+            let n01 = self.horizontal_center(nw, ne);
+            let n02 = ne;
+            let n10 = self.vertical_center(nw, sw);
+            let n11 = self.center([nw, ne, sw, se]);
+            let n12 = self.vertical_center(ne, se);
+            let n20 = sw;
+            let n21 = self.horizontal_center(sw, se);
+            let n22 = se;
+            let subnodes = [n00, n01, n02, n10, n11, n12, n20, n21, n22];
+
+            // A full step takes two half steps: one on the nine subnodes, one on the four nodes built from them
+            let full_step = step == level - 2;
+            let mut advanced = [DEAD; 9];
+            for (index, &subnode) in subnodes.iter().enumerate() {
+                advanced[index] = if full_step {
+                    self.successor(subnode, step - 1)
+                } else {
+                    let children = self.children(subnode);
+                    self.center(children)
+                };
+            }
+
+            let second_step = if full_step { step - 1 } else { step };
+            let [a00, a01, a02, a10, a11, a12, a20, a21, a22] = advanced;
+            let quadrants = [
+                [a00, a01, a10, a11],
+                [a01, a02, a11, a12],
+                [a10, a11, a20, a21],
+                [a11, a12, a21, a22],
+            ];
+            let mut children = [DEAD; 4];
+            for (child, quadrant) in children.iter_mut().zip(quadrants.iter()) {
+                let quadrant = self.branch(*quadrant);
+                *child = self.successor(quadrant, second_step);
+            }
+            self.branch(children)
+        };
+
+        self.results.insert((node, step), result);
+        result
+    }
+
+    // Advances the center 2x2 cells of a 4x4 node by one generation, cell by cell
+    fn successor_by_rule(&mut self, node: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (quadrant, &child) in self.children(node).iter().enumerate() {
+            for (index, &leaf) in self.children(child).iter().enumerate() {
+                let x = (quadrant % 2) * 2 + index % 2;
+                let y = (quadrant / 2) * 2 + index / 2;
+                cells[y][x] = leaf == ALIVE;
+            }
+        }
+
+        let mut next = [DEAD; 4];
+        for (index, cell) in next.iter_mut().enumerate() {
+            let (x, y) = (1 + index % 2, 1 + index / 2);
+            let neighborhood = NEIGHBORS
+                .iter()
+                .enumerate()
+                .filter(|(_, offset)| {
+                    cells[(y as isize + offset.y) as usize][(x as isize + offset.x) as usize]
+                })
+                .fold(0, |neighborhood, (bit, _)| neighborhood | 1 << bit);
+            let state = if cells[y][x] {
+                CellState::Alive
+            } else {
+                CellState::Dead
+            };
+            *cell = match self.rule.next_state(state, neighborhood) {
+                CellState::Alive => ALIVE,
+                _ => DEAD,
+            };
+        }
+        self.branch(next)
+    }
+
+    // Advances the torus by 2^step generations, with step at most level.
+    // Tiling the grid 4x4 gives a node whose center is the torus after advancing, repeated twice in each direction
+    fn step_torus(&mut self, step: u8) {
+        let tile = self.branch([self.root; 4]);
+        let tiles = self.branch([tile; 4]);
+        let center = self.successor(tiles, step);
+        self.root = self.children(center)[0];
+    }
+
+    // Node of the given level with the node in its center and empty space around it
+    fn centered(&mut self, node: NodeId, level: u8) -> NodeId {
+        let mut node = node;
+        for node_level in self.level_of(node)..level {
+            let empty = self.empty(node_level - 1);
+            let [nw, ne, sw, se] = self.children(node);
+            let nw = self.branch([empty, empty, empty, nw]);
+            let ne = self.branch([empty, empty, ne, empty]);
+            let sw = self.branch([empty, sw, empty, empty]);
+            let se = self.branch([se, empty, empty, empty]);
+            node = self.branch([nw, ne, sw, se]);
+        }
+        self.empty(level);
+        node
+    }
+
+    // Whether all live cells are in the central quarter of the root, the part made up of its inner grandchildren
+    fn is_padded(&mut self) -> bool {
+        let empty = self.empty(self.level - 2);
+        let [nw, ne, sw, se] = self.children(self.root);
+        let [nw, ne, sw, se] = [
+            self.children(nw),
+            self.children(ne),
+            self.children(sw),
+            self.children(se),
+        ];
+        let outer = [
+            nw[0], nw[1], nw[2], ne[0], ne[1], ne[3], sw[0], sw[2], sw[3], se[1], se[2], se[3],
+        ];
+        outer.iter().all(|&node| node == empty)
+    }
+
+    // Advances the plane by 2^step generations. The root is padded with empty space first, so the pattern
+    // can't grow past the center of the root, which is all that is left after advancing
+    fn step_plane(&mut self, step: u8) {
+        while self.level < step + 2 || !self.is_padded() {
+            self.level += 1;
+            self.root = self.centered(self.root, self.level);
+        }
+        self.level += 1;
+        self.root = self.centered(self.root, self.level);
+
+        self.root = self.successor(self.root, step);
+        self.level -= 1;
+    }
+
+    // Where the top left corner of the root is
+    fn root_origin(&self) -> isize {
+        if self.is_unbounded {
+            -(1 << (self.level - 1))
+        } else {
+            0
+        }
+    }
+
+    // Copies everything reachable from the grid into a fresh node store, dropping the memoized results
+    fn collect_garbage(&mut self) {
+        let old_nodes = std::mem::take(&mut self.nodes);
+        self.ids.clear();
+        self.results.clear();
+        self.intern_constants();
+
+        let mut copied = HashMap::new();
+        self.root = self.copy(&old_nodes, self.root, &mut copied);
+    }
+
+    fn copy(
+        &mut self,
+        old_nodes: &[Node],
+        node: NodeId,
+        copied: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        if let Some(&id) = copied.get(&node) {
+            return id;
+        }
+        let id = match old_nodes[node] {
+            Node::Leaf(alive) => self.leaf(alive),
+            Node::Branch { children, .. } => {
+                let mut new_children = [DEAD; 4];
+                for (new_child, &child) in new_children.iter_mut().zip(children.iter()) {
+                    *new_child = self.copy(old_nodes, child, copied);
+                }
+                self.branch(new_children)
+            }
+        };
+        copied.insert(node, id);
+        id
+    }
+
+    // Appends the cells that differ between the two nodes, which are placed with their top left corner at (x, y)
+    fn diff(
+        &self,
+        old: NodeId,
+        new: NodeId,
+        x: isize,
+        y: isize,
+        transitions: &mut Vec<(Position, CellState)>,
+    ) {
+        // Everything beyond the torus is just a copy of it
+        let is_beyond_torus =
+            !self.is_unbounded && (x >= self.width as isize || y >= self.height as isize);
+        if old == new || is_beyond_torus {
+            return;
+        }
+
+        match self.nodes[new] {
+            Node::Leaf(_) => transitions.push((Position { x, y }, HashLife::state(new))),
+            Node::Branch { level, children } => {
+                let half = 1 << (level - 1);
+                let old_children = self.children(old);
+                for (index, (&old, &new)) in old_children.iter().zip(children.iter()).enumerate() {
+                    let x = x + (index % 2) as isize * half;
+                    let y = y + (index / 2) as isize * half;
+                    self.diff(old, new, x, y, transitions);
+                }
+            }
+        }
+    }
+
+    // Appends the cells that aren't dead in the part of the node, which is placed with its top left corner at (x, y),
+    // that overlaps the rectangle. Empty nodes and those outside the rectangle are skipped without looking inside
+    fn collect_cells_in(
+        &self,
+        node: NodeId,
+        x: isize,
+        y: isize,
+        rectangle: &(Range<isize>, Range<isize>),
+        cells: &mut Vec<(Position, CellState)>,
+    ) {
+        let level = self.level_of(node);
+        let size = 1 << level;
+        let (xs, ys) = rectangle;
+        let is_beyond_torus =
+            !self.is_unbounded && (x >= self.width as isize || y >= self.height as isize);
+        let is_outside = x + size <= xs.start || x >= xs.end || y + size <= ys.start || y >= ys.end;
+        if node == self.empty_nodes[level as usize] || is_beyond_torus || is_outside {
+            return;
+        }
+
+        match self.nodes[node] {
+            Node::Leaf(_) => cells.push((Position { x, y }, HashLife::state(node))),
+            Node::Branch { children, .. } => {
+                let half = size / 2;
+                for (index, &child) in children.iter().enumerate() {
+                    let x = x + (index % 2) as isize * half;
+                    let y = y + (index / 2) as isize * half;
+                    self.collect_cells_in(child, x, y, rectangle, cells);
+                }
+            }
+        }
+    }
+
+    // Number of live cells in the node, counting each distinct node only once
+    fn node_population(&self, node: NodeId, populations: &mut HashMap<NodeId, usize>) -> usize {
+        if let Some(&population) = populations.get(&node) {
+            return population;
+        }
+        let population = match self.nodes[node] {
+            Node::Leaf(alive) => alive as usize,
+            Node::Branch { children, .. } => children.iter().fold(0, |sum: usize, &child| {
+                sum.saturating_add(self.node_population(child, populations))
+            }),
+        };
+        populations.insert(node, population);
+        population
+    }
+
+    fn set_node(&mut self, node: NodeId, x: usize, y: usize, alive: bool) -> NodeId {
+        match self.nodes[node] {
+            Node::Leaf(_) => self.leaf(alive),
+            Node::Branch { level, children } => {
+                let half = 1 << (level - 1);
+                let index = (y / half) * 2 + x / half;
+                let mut children = children;
+                children[index] = self.set_node(children[index], x % half, y % half, alive);
+                self.branch(children)
+            }
+        }
+    }
+}
+
+impl Engine for HashLife {
+    fn step(&mut self, rule: &Rule) -> Vec<(Position, CellState)> {
+        self.advance(rule, 1)
+    }
+
+    fn advance(&mut self, rule: &Rule, generations: usize) -> Vec<(Position, CellState)> {
+        if *rule != self.rule {
+            self.rule = rule.clone();
+            self.results.clear();
+        }
+
+        let old_root = self.root;
+        let mut remaining = generations;
+        while remaining > 0 {
+            if self.is_unbounded {
+                let step = (remaining.ilog2() as u8).min(MAX_PLANE_LEVEL - 3);
+                self.step_plane(step);
+                remaining -= 1 << step;
+            } else {
+                let step = (remaining.ilog2() as u8).min(self.level);
+                self.step_torus(step);
+                remaining -= 1 << step;
+            }
+        }
+
+        // The plane may have grown, and both roots are centered on (0, 0)
+        let old_root = self.centered(old_root, self.level);
+        let mut transitions = vec![];
+        let origin = self.root_origin();
+        self.diff(old_root, self.root, origin, origin, &mut transitions);
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+
+        if self.nodes.len() > MAX_NODES {
+            self.collect_garbage();
+        }
+
+        transitions
+    }
+
+    fn get(&self, position: Position) -> CellState {
+        let origin = self.root_origin();
+        let (x, y) = (position.x - origin, position.y - origin);
+        let size = 1 << self.level;
+        if !(0..size).contains(&x) || !(0..size).contains(&y) {
+            return CellState::Dead;
+        }
+
+        let mut node = self.root;
+        let (mut x, mut y) = (x as usize, y as usize);
+        while let Node::Branch { level, children } = self.nodes[node] {
+            let half = 1 << (level - 1);
+            node = children[(y / half) * 2 + x / half];
+            x %= half;
+            y %= half;
+        }
+
+        HashLife::state(node)
+    }
+
+    fn set(&mut self, position: Position, state: CellState) {
+        if self.is_unbounded {
+            let fits = |level: u8| {
+                let half = 1 << (level - 1);
+                (-half..half).contains(&position.x) && (-half..half).contains(&position.y)
+            };
+            while !fits(self.level) {
+                if self.level == MAX_PLANE_LEVEL {
+                    return;
+                }
+                self.level += 1;
+                self.root = self.centered(self.root, self.level);
+            }
+
+            let origin = self.root_origin();
+            let (x, y) = (
+                (position.x - origin) as usize,
+                (position.y - origin) as usize,
+            );
+            self.root = self.set_node(self.root, x, y, state == CellState::Alive);
+            return;
+        }
+
+        let size = 1 << self.level;
+        for y in (position.y as usize..size).step_by(self.height) {
+            for x in (position.x as usize..size).step_by(self.width) {
+                self.root = self.set_node(self.root, x, y, state == CellState::Alive);
+            }
+        }
+    }
+
+    // Counted straight from the quadtree, so patterns far too large to list every cell of are never expanded
+    fn population(&self) -> usize {
+        if !self.is_unbounded {
+            return self.live_cells().len();
+        }
+        self.node_population(self.root, &mut HashMap::new())
+    }
+
+    fn cells_in(
+        &self,
+        corner: Position,
+        width: usize,
+        height: usize,
+    ) -> Vec<(Position, CellState)> {
+        let rectangle = (
+            corner.x..corner.x + width as isize,
+            corner.y..corner.y + height as isize,
+        );
+        let mut cells = vec![];
+        let origin = self.root_origin();
+        self.collect_cells_in(self.root, origin, origin, &rectangle, &mut cells);
+        cells
+    }
+
+    fn live_cells(&self) -> Vec<Position> {
+        let mut live_cells = vec![];
+        let origin = self.root_origin();
+        self.diff(
+            self.empty_nodes[self.level as usize],
+            self.root,
+            origin,
+            origin,
+            &mut live_cells,
+        );
+        live_cells
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::NaiveGrid;
+    use crate::sparse::SparseGrid;
+    use crate::topology::Topology;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn sorted(mut cells: Vec<Position>) -> Vec<Position> {
+        cells.sort_by_key(|position| (position.y, position.x));
+        cells
+    }
+
+    fn life() -> Rule {
+        "B3/S23".parse().unwrap()
+    }
+
+    #[test]
+    fn matches_naive_grid_on_a_torus() {
+        for &(width, height) in &[(32, 32), (64, 16)] {
+            let mut rng = StdRng::seed_from_u64(width as u64);
+            let mut hashlife = HashLife::new(width, height).unwrap();
+            let mut naive = NaiveGrid::new(width, height, Topology::TORUS);
+            for y in 0..height as isize {
+                for x in 0..width as isize {
+                    if rng.gen_ratio(1, 3) {
+                        hashlife.set(Position { x, y }, CellState::Alive);
+                        naive.set(Position { x, y }, CellState::Alive);
+                    }
+                }
+            }
+
+            for _ in 0..10 {
+                let mut transitions = hashlife.step(&life());
+                transitions.sort_by_key(|(position, _)| (position.y, position.x));
+                assert_eq!(transitions, naive.step(&life()));
+            }
+
+            // Jumping ahead lands on the same generation as stepping there one at a time
+            hashlife.advance(&life(), 100);
+            for _ in 0..100 {
+                naive.step(&life());
+            }
+            assert_eq!(sorted(hashlife.live_cells()), sorted(naive.live_cells()));
+        }
+    }
+
+    #[test]
+    fn counts_and_shows_patterns_too_large_to_list() {
+        // A block in the top left corner of every 8x8 leaf, 2^30 cells wide, so 2^56 live cells
+        let mut nodes = vec![MacrocellNode::Leaf(0b11 | 0b11 << 8)];
+        for level in 4..=30 {
+            let child = nodes.len();
+            nodes.push(MacrocellNode::Branch {
+                level,
+                children: [child; 4],
+            });
+        }
+        let macrocell = Macrocell {
+            rule: None,
+            comments: vec![],
+            nodes,
+        };
+        let hashlife = HashLife::from_macrocell(&macrocell).unwrap();
+        assert_eq!(hashlife.population(), 1 << 56);
+
+        // The quadtree is centered on (0, 0), and its corner lies on a multiple of 8
+        let mut cells = hashlife.cells_in(Position { x: -7, y: 5 }, 16, 4);
+        cells.sort_by_key(|(position, _)| (position.y, position.x));
+        let expected: Vec<(Position, CellState)> = [-7, 0, 1, 8]
+            .iter()
+            .map(|&x| (Position { x, y: 8 }, CellState::Alive))
+            .collect();
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn matches_sparse_grid_on_the_plane() {
+        // R-pentomino, which keeps changing for over a thousand generations
+        let r_pentomino = [(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)];
+        let mut hashlife = HashLife::unbounded();
+        let mut sparse = SparseGrid::new();
+        for &(x, y) in &r_pentomino {
+            hashlife.set(Position { x, y }, CellState::Alive);
+            sparse.set(Position { x, y }, CellState::Alive);
+        }
+
+        hashlife.advance(&life(), 200);
+        for _ in 0..200 {
+            sparse.step(&life());
+        }
+        assert_eq!(sorted(hashlife.live_cells()), sorted(sparse.live_cells()));
+    }
+
+    #[test]
+    fn glider_moves_one_cell_every_four_generations() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut hashlife = HashLife::unbounded();
+        for &(x, y) in &glider {
+            hashlife.set(Position { x, y }, CellState::Alive);
+        }
+
+        hashlife.advance(&life(), 4);
+        let moved: Vec<Position> = glider
+            .iter()
+            .map(|&(x, y)| Position { x: x + 1, y: y + 1 })
+            .collect();
+        assert_eq!(sorted(hashlife.live_cells()), sorted(moved));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::simulation::{CellState, Position};
+
+use std::collections::VecDeque;
+use std::mem;
+
+/// Cells that changed between two generations. Edits change cells without a new generation, so from and to are equal
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub from: usize,                             // Generation before the change
+    pub to: usize,                               // Generation after the change
+    pub transitions: Vec<(Position, CellState)>, // New states of the cells that changed
+    pub previous_states: Vec<CellState>,         // States the same cells were in before the change
+}
+
+impl Change {
+    /// Transitions that take the cells back to how they were before the change
+    pub fn reversed(&self) -> Vec<(Position, CellState)> {
+        self.transitions
+            .iter()
+            .zip(&self.previous_states)
+            .map(|(&(position, _), &previous_state)| (position, previous_state))
+            .collect()
+    }
+
+    /// States the cells are in before the change is undone
+    pub fn states(&self) -> Vec<CellState> {
+        self.transitions.iter().map(|&(_, state)| state).collect()
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<Change>()
+            + self.transitions.capacity() * mem::size_of::<(Position, CellState)>()
+            + self.previous_states.capacity() * mem::size_of::<CellState>()
+    }
+}
+
+/// Changes that can be undone and redone, from the oldest to the newest. Once the changes take up more memory than
+/// the budget allows, the oldest ones are forgotten
+pub struct History {
+    past: VecDeque<Change>, // Changes that lead up to the current generation
+    future: Vec<Change>,    // Undone changes, with the next one to redo at the end
+    size: usize,            // Bytes taken up by all changes
+    budget: usize,
+}
+
+impl History {
+    pub fn new(budget: usize) -> History {
+        History {
+            past: VecDeque::new(),
+            future: vec![],
+            size: 0,
+            budget,
+        }
+    }
+
+    /// Remembers a change, which replaces any changes that were undone
+    pub fn record(
+        &mut self,
+        from: usize,
+        to: usize,
+        transitions: &[(Position, CellState)],
+        previous_states: &[CellState],
+    ) {
+        if transitions.is_empty() && from == to {
+            return;
+        }
+
+        for change in self.future.drain(..) {
+            self.size -= change.size();
+        }
+        let change = Change {
+            from,
+            to,
+            transitions: transitions.to_vec(),
+            previous_states: previous_states.to_vec(),
+        };
+        self.size += change.size();
+        self.past.push_back(change);
+
+        while self.size > self.budget {
+            match self.past.pop_front() {
+                Some(change) => self.size -= change.size(),
+                None => break,
+            }
+        }
+    }
+
+    /// Takes back the newest change, which can then be redone
+    pub fn undo(&mut self) -> Option<&Change> {
+        let change = self.past.pop_back()?;
+        self.future.push(change);
+        self.future.last()
+    }
+
+    /// Makes the last undone change again
+    pub fn redo(&mut self) -> Option<&Change> {
+        let change = self.future.pop()?;
+        self.past.push_back(change);
+        self.past.back()
+    }
+
+    /// Change the next undo takes back
+    pub fn last_change(&self) -> Option<&Change> {
+        self.past.back()
+    }
+
+    /// Change the next redo makes again
+    pub fn next_change(&self) -> Option<&Change> {
+        self.future.last()
+    }
+
+    /// Oldest and newest generation that can be reached from the current one
+    pub fn range(&self, generation: usize) -> (usize, usize) {
+        let oldest = self.past.front().map_or(generation, |change| change.from);
+        let newest = self.future.first().map_or(generation, |change| change.to);
+        (oldest, newest)
+    }
+
+    pub fn clear(&mut self) {
+        self.past.clear();
+        self.future.clear();
+        self.size = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::rule::Rule;
+    use crate::sparse::SparseGrid;
+
+    fn sorted(mut cells: Vec<(Position, CellState)>) -> Vec<(Position, CellState)> {
+        cells.sort_by_key(|(position, _)| (position.y, position.x));
+        cells
+    }
+
+    // A change of a single cell, which takes up the same memory as any other
+    fn record_generation(history: &mut History, generation: usize) {
+        let transitions = [(
+            Position {
+                x: generation as isize,
+                y: 0,
+            },
+            CellState::Alive,
+        )];
+        history.record(generation, generation + 1, &transitions, &[CellState::Dead]);
+    }
+
+    #[test]
+    fn undoing_and_redoing_restores_each_generation_exactly() {
+        let rule: Rule = "/2/3".parse().unwrap();
+        let mut grid = SparseGrid::new();
+        for &(x, y) in &[(0, 0), (1, 0), (3, 1), (1, 2), (2, 2)] {
+            grid.set(Position { x, y }, CellState::Alive);
+        }
+
+        let mut history = History::new(usize::MAX);
+        let mut generations = vec![sorted(grid.cells())];
+        for generation in 0..6 {
+            let (transitions, previous_states) = grid.step_with_previous_states(&rule);
+            history.record(generation, generation + 1, &transitions, &previous_states);
+            generations.push(sorted(grid.cells()));
+        }
+        assert_eq!(history.range(6), (0, 6));
+
+        for generation in (0..6).rev() {
+            let change = history.undo().unwrap();
+            assert_eq!(change.from, generation);
+            for (position, state) in change.reversed() {
+                grid.set(position, state);
+            }
+            assert_eq!(sorted(grid.cells()), generations[generation]);
+        }
+        assert!(history.undo().is_none());
+
+        for cells in &generations[1..] {
+            let change = history.redo().unwrap();
+            for &(position, state) in &change.transitions {
+                grid.set(position, state);
+            }
+            assert_eq!(&sorted(grid.cells()), cells);
+        }
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn new_changes_drop_the_undone_ones() {
+        let mut history = History::new(usize::MAX);
+        for generation in 0..3 {
+            record_generation(&mut history, generation);
+        }
+        history.undo();
+        history.undo();
+        assert_eq!(history.range(1), (0, 3));
+
+        // An edit in generation 1 replaces generations 2 and 3
+        let edit = [(Position { x: 5, y: 5 }, CellState::Alive)];
+        history.record(1, 1, &edit, &[CellState::Dead]);
+        assert!(history.next_change().is_none());
+        assert_eq!(history.range(1), (0, 1));
+        assert_eq!(history.last_change().unwrap().transitions, edit);
+
+        // Edits that change nothing aren't worth remembering
+        history.record(1, 1, &[], &[]);
+        assert_eq!(history.last_change().unwrap().transitions, edit);
+    }
+
+    #[test]
+    fn forgets_the_oldest_changes_past_the_budget() {
+        let mut sizing = History::new(usize::MAX);
+        record_generation(&mut sizing, 0);
+        let change_size = sizing.size;
+
+        let mut history = History::new(change_size * 5 / 2);
+        for generation in 0..5 {
+            record_generation(&mut history, generation);
+        }
+        assert_eq!(history.range(5), (3, 5));
+        assert_eq!(history.size, 2 * change_size);
+
+        // A change larger than the budget isn't kept at all
+        let mut history = History::new(change_size - 1);
+        record_generation(&mut history, 0);
+        assert_eq!(history.range(1), (1, 1));
+        assert_eq!(history.size, 0);
+    }
+}
@@ -0,0 +1,60 @@
+//! Engines for Conway's Game of Life and other isotropic cellular automata, without any user interface.
+//!
+//! - A grid is any [`Engine`]. [`EngineKind::create`] builds one of a given size and [`Topology`].
+//! - A [`Rule`] such as B3/S23, or B2-a/S12 in Hensel notation, parses from its rulestring. Generations rules such as
+//!   Brian's Brain, /2/3, add dying states that only the naive and sparse engines store. Larger than Life rules such
+//!   as R5,C0,M1,S34..58,B34..45,NM count the live cells within a radius, which only those engines do as well, and
+//!   so do rules such as B2/S34H on hexagonal or triangular [`Tiling`]s.
+//! - Automata such as Wireworld load from Golly's .rule files as a [`rule_table::RuleTable`], which
+//!   [`Rule::from_table`] runs on the naive and sparse engines.
+//! - Langton's ant and other [`turmite::Turmite`]s walk over any engine's grid in a [`turmite::Colony`], painting
+//!   the cells they step on.
+//! - [`Engine::step`] computes the next generation and [`Engine::advance`] skips ahead many at once. Both return the
+//!   cells that changed.
+//! - [`Pattern`] files are read and written in the [`Format`]s RLE, plaintext, Life 1.06 and macrocell. RLE keeps
+//!   the states of multi-state rules, the other formats only live cells.
+//! - [`simulation::Simulation`] runs an engine on its own thread, talking to a front end through messages.
+//!
+//! ```
+//! use conway::{CellState, Engine, EngineKind, Format, Position, Rule, Topology};
+//!
+//! let glider = Format::Rle.parse("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+//! let mut grid = EngineKind::BitPacked.create(16, 16, Topology::TORUS).unwrap();
+//! for &(position, state) in &glider.cells {
+//!     grid.set(position, state);
+//! }
+//!
+//! let rule: Rule = "B3/S23".parse().unwrap();
+//! grid.advance(&rule, 4);
+//! assert_eq!(grid.get(Position { x: 2, y: 3 }), CellState::Alive);
+//! ```
+
+pub mod bitgrid;
+pub mod census;
+pub mod cycle;
+pub mod engine;
+pub mod hashlife;
+pub mod history;
+pub mod life106;
+pub mod macrocell;
+pub mod pattern;
+pub mod plaintext;
+pub mod rle;
+pub mod rule;
+pub mod rule_table;
+pub mod session;
+pub mod simulation;
+pub mod soup;
+pub mod sparse;
+pub mod summed_area;
+pub mod tiling;
+pub mod topology;
+pub mod turmite;
+pub mod util; // Contains channels for inter-thread communication
+
+pub use engine::{Engine, EngineKind};
+pub use pattern::{Format, Pattern, PatternError};
+pub use rule::{Rule, RuleError};
+pub use simulation::{CellState, Position};
+pub use tiling::Tiling;
+pub use topology::{Edge, Topology};
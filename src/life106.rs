@@ -0,0 +1,117 @@
+//! Life 1.06 patterns, a header followed by the coordinates of every live cell:
+//!
+//! ```text
+//! #Life 1.06
+//! 0 -1
+//! 1 0
+//! -1 1
+//! 0 1
+//! 1 1
+//! ```
+//!
+//! Other lines starting with # are comments. As coordinates can be negative, the pattern is moved so its
+//! top left corner ends up at 0, 0.
+
+use crate::pattern::{Pattern, PatternError, PatternErrorKind};
+use crate::simulation::Position;
+
+const HEADER: &str = "#Life 1.06";
+
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.trim() == HEADER => (),
+        Some((line_index, line)) => {
+            let kind = PatternErrorKind::MissingHeader(HEADER);
+            return Err(PatternError::new(line_index, line, 0, kind));
+        }
+        None => {
+            let kind = PatternErrorKind::MissingHeader(HEADER);
+            return Err(PatternError::new(0, "", 0, kind));
+        }
+    }
+
+    let mut comments = vec![];
+    let mut live_cells = vec![];
+    for (line_index, line) in lines {
+        if let Some(comment) = line.trim().strip_prefix('#') {
+            comments.push(comment.to_string());
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Points at the first number that is wrong, or at the start of the line if there aren't two
+        let invalid_coordinates = |number: Option<&str>| {
+            let index = match number {
+                Some(number) => number.as_ptr() as usize - line.as_ptr() as usize,
+                None => line.len() - line.trim_start().len(),
+            };
+            let kind = PatternErrorKind::InvalidCoordinates(line.trim().to_string());
+            PatternError::new(line_index, line, index, kind)
+        };
+        let parse_number = |number: Option<&str>| -> Result<isize, PatternError> {
+            let text = number.ok_or_else(|| invalid_coordinates(None))?;
+            text.parse().map_err(|_| invalid_coordinates(number))
+        };
+
+        let mut numbers = line.split_whitespace();
+        let x = parse_number(numbers.next())?;
+        let y = parse_number(numbers.next())?;
+        if let Some(number) = numbers.next() {
+            return Err(invalid_coordinates(Some(number)));
+        }
+        live_cells.push(Position { x, y });
+    }
+
+    let mut pattern = Pattern::from_live_cells(&live_cells, None);
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = format!("{}\n", HEADER);
+    for comment in &pattern.comments {
+        text.push_str(&format!("#{}\n", comment));
+    }
+    // Only live cells can be written down, so cells in other states are left out
+    for position in pattern.live_cells() {
+        text.push_str(&format!("{} {}\n", position.x, position.y));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_negative_coordinates_to_the_corner() {
+        let pattern = parse("#Life 1.06\n#D Glider\n0 -1\n1 0\n-1 1\n0 1\n1 1\n").unwrap();
+        assert_eq!(pattern.comments, vec!["D Glider".to_string()]);
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        let glider: Vec<Position> = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+            .iter()
+            .map(|&(x, y)| Position { x, y })
+            .collect();
+        assert_eq!(pattern.live_cells(), glider);
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn points_at_the_wrong_number() {
+        let error = parse("#Life 1.06\n0 0\n3 x\n").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 3));
+        assert_eq!(
+            error.kind,
+            PatternErrorKind::InvalidCoordinates("3 x".to_string())
+        );
+
+        let error = parse("#Life 1.06\n1 2 3\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 5));
+
+        let error = parse("x = 1, y = 1\no!").unwrap_err();
+        assert_eq!(error.kind, PatternErrorKind::MissingHeader(HEADER));
+    }
+}
@@ -0,0 +1,494 @@
+//! Golly's macrocell format, which stores a pattern as a quadtree so identical regions are written only once:
+//!
+//! ```text
+//! [M2] (conway)
+//! #R B3/S23
+//! .**$**$.*$
+//! 4 1 0 0 0
+//! ```
+//!
+//! A line of ., * and $ is a leaf of 8x8 cells, written row by row with every row ending in $. Every other line
+//! is a node: its level, then its nw, ne, sw and se children as the line numbers of earlier nodes, counting only
+//! node lines and starting at 1, with 0 for an empty child. A node of level n is 2^n cells wide and leaves are
+//! level 3. Multi-state patterns have no leaves, but level 1 nodes with the states of their four cells instead.
+//! The last node is the root.
+
+use crate::pattern::{Pattern, PatternError, PatternErrorKind};
+use crate::rule::Rule;
+use crate::rule_table;
+use crate::simulation::{CellState, Position};
+
+use std::collections::HashMap;
+
+const HEADER: &str = "[M2]";
+const MAX_LEVEL: u8 = 60; // Deeper trees would have coordinates that don't fit into an isize
+const LEAF_LEVEL: u8 = 3;
+const LEAF_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MacrocellNode {
+    Leaf(u64),                                  // Bit y * 8 + x is set for every live cell
+    States([u8; 4]),                            // Cell states in the order nw, ne, sw, se
+    Branch { level: u8, children: [usize; 4] }, // Node numbers, starting at 1, or 0 for empty
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Macrocell {
+    pub rule: Option<String>, // Kept as text, as multi-state patterns come with rules of their own
+    pub comments: Vec<String>,
+    pub nodes: Vec<MacrocellNode>,
+}
+
+impl Macrocell {
+    /// Quadtree with the top left corner of the bounding box of the cells that aren't dead in its top left corner.
+    /// Cells of rules with more than two states go into level 1 nodes with their states, the way Golly writes them
+    pub fn from_cells(cells: &[(Position, CellState)], rule: Option<Rule>) -> Macrocell {
+        let pattern = Pattern::from_cells(cells, rule.clone());
+        let is_multi_state = rule.as_ref().is_some_and(|rule| rule.states() > 2)
+            || pattern.cells.iter().any(|(_, state)| state.index() > 1);
+        let smallest = if is_multi_state { 2 } else { LEAF_SIZE };
+        let size = pattern.width.max(pattern.height).max(smallest);
+        let level = size.next_power_of_two().trailing_zeros() as u8;
+
+        let mut macrocell = Macrocell {
+            rule: rule.map(|rule| rule.to_string()),
+            comments: vec![],
+            nodes: vec![],
+        };
+        let mut numbers = HashMap::new();
+        macrocell.build(&pattern.cells, level, is_multi_state, &mut numbers);
+        macrocell
+    }
+
+    // Adds the node for the cells, which lie in a square of the level with its top left corner at (0, 0).
+    // Returns its node number, reusing identical nodes
+    fn build(
+        &mut self,
+        cells: &[(Position, CellState)],
+        level: u8,
+        is_multi_state: bool,
+        numbers: &mut HashMap<MacrocellNode, usize>,
+    ) -> usize {
+        if cells.is_empty() {
+            return 0;
+        }
+
+        let node = if is_multi_state && level == 1 {
+            let mut states = [0; 4];
+            for (cell, state) in cells {
+                states[(cell.y * 2 + cell.x) as usize] = state.index();
+            }
+            MacrocellNode::States(states)
+        } else if !is_multi_state && level == LEAF_LEVEL {
+            let bits = cells
+                .iter()
+                .fold(0, |bits, (cell, _)| bits | 1 << (cell.y * 8 + cell.x));
+            MacrocellNode::Leaf(bits)
+        } else {
+            let half = 1 << (level - 1);
+            let mut quadrants = [vec![], vec![], vec![], vec![]];
+            for &(cell, state) in cells {
+                let index = (cell.y / half) * 2 + cell.x / half;
+                let cell = Position {
+                    x: cell.x % half,
+                    y: cell.y % half,
+                };
+                quadrants[index as usize].push((cell, state));
+            }
+
+            let mut children = [0; 4];
+            for (child, quadrant) in children.iter_mut().zip(quadrants.iter()) {
+                *child = self.build(quadrant, level - 1, is_multi_state, numbers);
+            }
+            MacrocellNode::Branch { level, children }
+        };
+
+        if let Some(&number) = numbers.get(&node) {
+            return number;
+        }
+        self.nodes.push(node);
+        numbers.insert(node, self.nodes.len());
+        self.nodes.len()
+    }
+
+    pub fn level(&self) -> u8 {
+        match self.nodes.last() {
+            Some(&node) => Macrocell::level_of(node),
+            None => 0,
+        }
+    }
+
+    fn level_of(node: MacrocellNode) -> u8 {
+        match node {
+            MacrocellNode::Leaf(_) => LEAF_LEVEL,
+            MacrocellNode::States(_) => 1,
+            MacrocellNode::Branch { level, .. } => level,
+        }
+    }
+
+    /// Top left and bottom right live cell relative to the top left corner of the root, or None without live cells
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let mut bounds: Vec<Option<(Position, Position)>> = vec![];
+        for &node in &self.nodes {
+            let cells: Vec<Position> = match node {
+                MacrocellNode::Leaf(bits) => (0..64)
+                    .filter(|bit| (bits >> bit) & 1 == 1)
+                    .map(|bit| Position {
+                        x: bit % 8,
+                        y: bit / 8,
+                    })
+                    .collect(),
+                MacrocellNode::States(states) => (0..4)
+                    .filter(|&index| states[index as usize] != 0)
+                    .map(|index| Position {
+                        x: index % 2,
+                        y: index / 2,
+                    })
+                    .collect(),
+                // A child's bounds, moved to where the child lies in the node
+                MacrocellNode::Branch { level, children } => {
+                    let half = 1 << (level - 1);
+                    let child_bounds = |index: isize| match children[index as usize] {
+                        0 => None,
+                        child => bounds[child - 1].map(|child_bounds| (index, child_bounds)),
+                    };
+                    (0..4)
+                        .filter_map(child_bounds)
+                        .flat_map(|(index, (min, max))| {
+                            let offset = Position {
+                                x: (index % 2) * half,
+                                y: (index / 2) * half,
+                            };
+                            vec![min + offset, max + offset]
+                        })
+                        .collect()
+                }
+            };
+            bounds.push(bounding_box(&cells));
+        }
+        bounds.last().copied().flatten()
+    }
+
+    /// Whether any cell is in another state than dead or alive, which only rules with more than two states have
+    pub fn is_multi_state(&self) -> bool {
+        self.nodes.iter().any(|node| match node {
+            MacrocellNode::States(states) => states.iter().any(|&state| state > 1),
+            _ => false,
+        })
+    }
+
+    /// Every cell of the quadtree that isn't dead. This expands the whole pattern, so only use it for patterns that
+    /// fit a grid
+    pub fn to_pattern(&self) -> Pattern {
+        let mut cells = vec![];
+        if !self.nodes.is_empty() {
+            self.expand(self.nodes.len(), Position { x: 0, y: 0 }, &mut cells);
+        }
+
+        let rule = self.rule.as_ref().and_then(|rule| rule.parse().ok());
+        let mut pattern = Pattern::from_cells(&cells, rule);
+        if pattern.rule.is_none() {
+            pattern.rule_table = self.rule.clone().filter(|rule| rule_table::is_name(rule));
+        }
+        pattern.comments = self.comments.clone();
+        pattern
+    }
+
+    fn expand(&self, number: usize, top_left: Position, cells: &mut Vec<(Position, CellState)>) {
+        match self.nodes[number - 1] {
+            MacrocellNode::Leaf(bits) => {
+                for bit in (0..64).filter(|bit| (bits >> bit) & 1 == 1) {
+                    let position = Position {
+                        x: bit % 8,
+                        y: bit / 8,
+                    };
+                    cells.push((top_left + position, CellState::Alive));
+                }
+            }
+            MacrocellNode::States(states) => {
+                for index in (0..4).filter(|&index| states[index as usize] != 0) {
+                    let position = Position {
+                        x: index % 2,
+                        y: index / 2,
+                    };
+                    let state = CellState::from_index(states[index as usize]);
+                    cells.push((top_left + position, state));
+                }
+            }
+            MacrocellNode::Branch { level, children } => {
+                let half = 1 << (level - 1);
+                for (index, &child) in children.iter().enumerate().filter(|(_, &child)| child != 0)
+                {
+                    let offset = Position {
+                        x: (index % 2) as isize * half,
+                        y: (index / 2) as isize * half,
+                    };
+                    self.expand(child, top_left + offset, cells);
+                }
+            }
+        }
+    }
+}
+
+fn bounding_box(cells: &[Position]) -> Option<(Position, Position)> {
+    let first = *cells.first()?;
+    Some(cells.iter().fold((first, first), |(min, max), cell| {
+        let min = Position {
+            x: min.x.min(cell.x),
+            y: min.y.min(cell.y),
+        };
+        let max = Position {
+            x: max.x.max(cell.x),
+            y: max.y.max(cell.y),
+        };
+        (min, max)
+    }))
+}
+
+pub fn parse(text: &str) -> Result<Macrocell, PatternError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.starts_with(HEADER) => (),
+        Some((line_index, line)) => {
+            let kind = PatternErrorKind::MissingHeader(HEADER);
+            return Err(PatternError::new(line_index, line, 0, kind));
+        }
+        None => {
+            let kind = PatternErrorKind::MissingHeader(HEADER);
+            return Err(PatternError::new(0, "", 0, kind));
+        }
+    }
+
+    let mut macrocell = Macrocell::default();
+    for (line_index, line) in lines {
+        let invalid_node = |message: &str| {
+            let kind = PatternErrorKind::InvalidNode(message.to_string());
+            PatternError::new(line_index, line, 0, kind)
+        };
+
+        let trimmed = line.trim();
+        if let Some(rule) = trimmed.strip_prefix("#R") {
+            macrocell.rule = Some(rule.trim().to_string());
+        } else if let Some(comment) = trimmed.strip_prefix('#') {
+            macrocell.comments.push(comment.to_string());
+        } else if trimmed.is_empty() {
+            continue;
+        } else if trimmed.starts_with(|character| ".*$".contains(character)) {
+            let mut bits = 0;
+            let (mut x, mut y) = (0, 0);
+            for (index, character) in line.char_indices() {
+                match character {
+                    '.' => x += 1,
+                    '*' if x < LEAF_SIZE && y < LEAF_SIZE => {
+                        bits |= 1 << (y * LEAF_SIZE + x);
+                        x += 1;
+                    }
+                    '*' => return Err(invalid_node("Leaf has more than 8x8 cells")),
+                    '$' => {
+                        x = 0;
+                        y += 1;
+                    }
+                    character if character.is_whitespace() => (),
+                    character => {
+                        let kind = PatternErrorKind::UnexpectedCharacter(character);
+                        return Err(PatternError::new(line_index, line, index, kind));
+                    }
+                }
+            }
+            macrocell.nodes.push(MacrocellNode::Leaf(bits));
+        } else {
+            let numbers: Vec<usize> = trimmed
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid_node("Node should be five whole numbers"))?;
+            let (level, children) = match numbers[..] {
+                [level, nw, ne, sw, se] => (level, [nw, ne, sw, se]),
+                _ => return Err(invalid_node("Node should be five whole numbers")),
+            };
+
+            let node = match level {
+                0 => return Err(invalid_node("Nodes start at level 1")),
+                1 => {
+                    let mut states = [0; 4];
+                    for (state, &child) in states.iter_mut().zip(children.iter()) {
+                        *state = match child {
+                            0..=255 => child as u8,
+                            _ => return Err(invalid_node("Cell states go up to 255")),
+                        };
+                    }
+                    MacrocellNode::States(states)
+                }
+                level if level > MAX_LEVEL as usize => {
+                    return Err(invalid_node("Node is too deep, levels go up to 60"))
+                }
+                level => {
+                    for &child in children.iter().filter(|&&child| child != 0) {
+                        if child > macrocell.nodes.len() {
+                            return Err(invalid_node("Node refers to a node that comes after it"));
+                        }
+                        if Macrocell::level_of(macrocell.nodes[child - 1]) as usize != level - 1 {
+                            return Err(invalid_node(
+                                "Children have to be one level below the node",
+                            ));
+                        }
+                    }
+                    MacrocellNode::Branch {
+                        level: level as u8,
+                        children,
+                    }
+                }
+            };
+            macrocell.nodes.push(node);
+        }
+    }
+
+    Ok(macrocell)
+}
+
+pub fn write(macrocell: &Macrocell) -> String {
+    let mut text = format!("{} (conway)\n", HEADER);
+    if let Some(rule) = &macrocell.rule {
+        text.push_str(&format!("#R {}\n", rule));
+    }
+    for comment in &macrocell.comments {
+        text.push_str(&format!("#{}\n", comment));
+    }
+
+    for &node in &macrocell.nodes {
+        match node {
+            // Dead cells at the end of a row and empty rows at the end of the leaf are left out
+            MacrocellNode::Leaf(bits) => {
+                let last_row = (0..LEAF_SIZE)
+                    .rev()
+                    .find(|y| (bits >> (y * LEAF_SIZE)) & 0xff != 0)
+                    .unwrap_or(0);
+                for y in 0..=last_row {
+                    let row = (bits >> (y * LEAF_SIZE)) & 0xff;
+                    let width = (u64::BITS - row.leading_zeros()) as usize;
+                    for x in 0..width {
+                        text.push(if (row >> x) & 1 == 1 { '*' } else { '.' });
+                    }
+                    text.push('$');
+                }
+                text.push('\n');
+            }
+            MacrocellNode::States([nw, ne, sw, se]) => {
+                text.push_str(&format!("1 {} {} {} {}\n", nw, ne, sw, se));
+            }
+            MacrocellNode::Branch { level, children } => {
+                let [nw, ne, sw, se] = children;
+                text.push_str(&format!("{} {} {} {} {}\n", level, nw, ne, sw, se));
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::hashlife::HashLife;
+
+    const R_PENTOMINO: &str = "[M2] (conway)\n#R B3/S23\n.**$**$.*$\n4 1 0 0 0\n";
+
+    fn cells(cells: &[(isize, isize, u8)]) -> Vec<(Position, CellState)> {
+        cells
+            .iter()
+            .map(|&(x, y, state)| (Position { x, y }, CellState::from_index(state)))
+            .collect()
+    }
+
+    #[test]
+    fn reads_an_r_pentomino() {
+        let macrocell = parse(R_PENTOMINO).unwrap();
+        assert_eq!(macrocell.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(macrocell.level(), 4);
+        let bounds = (Position { x: 0, y: 0 }, Position { x: 2, y: 2 });
+        assert_eq!(macrocell.bounds(), Some(bounds));
+
+        let pattern = macrocell.to_pattern();
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(
+            pattern.cells,
+            cells(&[(1, 0, 1), (2, 0, 1), (0, 1, 1), (1, 1, 1), (1, 2, 1)])
+        );
+    }
+
+    #[test]
+    fn r_pentomino_settles_after_1103_generations() {
+        let macrocell = parse(R_PENTOMINO).unwrap();
+        let rule: Rule = "B3/S23".parse().unwrap();
+        let mut hashlife = HashLife::from_macrocell(&macrocell).unwrap();
+        hashlife.advance(&rule, 1103);
+        assert_eq!(hashlife.population(), 116);
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let pattern = parse(R_PENTOMINO).unwrap().to_pattern();
+        let macrocell = Macrocell::from_cells(&pattern.cells, pattern.rule.clone());
+        assert_eq!(write(&macrocell), "[M2] (conway)\n#R B3/S23\n.**$**$.*$\n");
+
+        // Identical leaves are written once
+        let rows: Vec<(isize, isize, u8)> = (0..40).map(|x| (x, x % 2 * 17, 1)).collect();
+        let pattern = Pattern::from_cells(&cells(&rows), None);
+        let macrocell = Macrocell::from_cells(&pattern.cells, None);
+        assert_eq!(
+            macrocell
+                .nodes
+                .iter()
+                .filter(|node| matches!(node, MacrocellNode::Leaf(_)))
+                .count(),
+            2
+        );
+        assert_eq!(parse(&write(&macrocell)).unwrap().to_pattern(), pattern);
+    }
+
+    #[test]
+    fn keeps_the_states_of_multi_state_rules() {
+        // Brian's Brain, with dying cells in state 2
+        let rule: Rule = "/2/3".parse().unwrap();
+        let pattern = Pattern::from_cells(
+            &cells(&[(1, 0, 1), (2, 0, 2), (0, 1, 1), (1, 1, 1)]),
+            Some(rule.clone()),
+        );
+        let macrocell = Macrocell::from_cells(&pattern.cells, Some(rule));
+        assert!(macrocell.is_multi_state());
+        assert_eq!(
+            write(&macrocell),
+            "[M2] (conway)\n#R /2/3\n1 0 1 1 1\n1 2 0 0 0\n2 1 2 0 0\n"
+        );
+
+        let read = parse(&write(&macrocell)).unwrap();
+        assert_eq!(read.to_pattern(), pattern);
+        assert!(HashLife::from_macrocell(&read).is_err());
+    }
+
+    #[test]
+    fn points_at_invalid_nodes() {
+        let error = parse("[M2]\n.*$\n2 1 0 0 0\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(
+            error.kind,
+            PatternErrorKind::InvalidNode(
+                "Children have to be one level below the node".to_string()
+            )
+        );
+
+        let error = parse("[M2]\n4 2 0 0 0\n").unwrap_err();
+        assert_eq!(
+            error.kind,
+            PatternErrorKind::InvalidNode("Node refers to a node that comes after it".to_string())
+        );
+
+        let error = parse("[M2]\n.*x$\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.kind, PatternErrorKind::UnexpectedCharacter('x'));
+
+        let error = parse("x = 3, y = 3\n").unwrap_err();
+        assert_eq!(error.kind, PatternErrorKind::MissingHeader(HEADER));
+    }
+}
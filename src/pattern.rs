@@ -0,0 +1,218 @@
+use crate::macrocell::{self, Macrocell};
+use crate::rule::{Rule, RuleError};
+use crate::rule_table::RuleTable;
+use crate::simulation::{CellState, Position};
+use crate::{life106, plaintext, rle};
+
+use anyhow::{anyhow, Context};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Pattern read from or written to a file. Cell positions are relative to the top left corner of the pattern
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    pub rule: Option<Rule>, // None if the file doesn't say which rule the pattern is for
+    pub rule_table: Option<String>, // Name of the rule table the file gives instead, see Pattern::read_rule_table
+    pub comments: Vec<String>,      // Comment lines without their comment marker
+    pub cells: Vec<(Position, CellState)>, // Cells that aren't dead, row by row
+}
+
+impl Pattern {
+    /// Pattern that fits tightly around the cells that aren't dead. Of cells listed more than once, the first counts
+    pub fn from_cells(cells: &[(Position, CellState)], rule: Option<Rule>) -> Pattern {
+        let cells: Vec<(Position, CellState)> = cells
+            .iter()
+            .copied()
+            .filter(|&(_, state)| state != CellState::Dead)
+            .collect();
+        if cells.is_empty() {
+            return Pattern {
+                rule,
+                ..Pattern::default()
+            };
+        }
+
+        let min_x = cells.iter().map(|(position, _)| position.x).min().unwrap();
+        let min_y = cells.iter().map(|(position, _)| position.y).min().unwrap();
+        let max_x = cells.iter().map(|(position, _)| position.x).max().unwrap();
+        let max_y = cells.iter().map(|(position, _)| position.y).max().unwrap();
+
+        let mut cells: Vec<(Position, CellState)> = cells
+            .into_iter()
+            .map(|(position, state)| {
+                let position = Position {
+                    x: position.x - min_x,
+                    y: position.y - min_y,
+                };
+                (position, state)
+            })
+            .collect();
+        cells.sort_by_key(|(position, _)| (position.y, position.x));
+        cells.dedup_by_key(|(position, _)| *position);
+
+        Pattern {
+            width: (max_x - min_x + 1) as usize,
+            height: (max_y - min_y + 1) as usize,
+            rule,
+            rule_table: None,
+            comments: vec![],
+            cells,
+        }
+    }
+
+    /// Pattern that fits tightly around the live cells
+    pub fn from_live_cells(live_cells: &[Position], rule: Option<Rule>) -> Pattern {
+        let cells: Vec<(Position, CellState)> = live_cells
+            .iter()
+            .map(|&position| (position, CellState::Alive))
+            .collect();
+        Pattern::from_cells(&cells, rule)
+    }
+
+    /// Positions of the live cells, leaving out those in other states
+    pub fn live_cells(&self) -> Vec<Position> {
+        self.cells
+            .iter()
+            .filter(|&&(_, state)| state == CellState::Alive)
+            .map(|&(position, _)| position)
+            .collect()
+    }
+
+    /// Takes the rule from the rule table the file names in place of a rulestring, as in "rule = WireWorld". The
+    /// table is the .rule file of that name in the directory, which is where Golly looks for it next to a pattern
+    pub fn read_rule_table(&mut self, directory: &Path) -> anyhow::Result<()> {
+        let name = match self.rule_table.take() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let path = directory.join(format!("{}.rule", name));
+        let text = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "The rule \"{}\" isn't a rulestring, and there is no rule table {} next to the pattern",
+                name,
+                path.display()
+            )
+        })?;
+        let table = RuleTable::parse(&text)
+            .map_err(|error| anyhow!("Invalid rule table {}: {}", path.display(), error))?;
+        self.rule = Some(Rule::from_table(table));
+        Ok(())
+    }
+
+    /// Number of states a rule needs for all cells of the pattern, which is at least two
+    pub fn states(&self) -> usize {
+        self.cells
+            .iter()
+            .map(|(_, state)| state.index() as usize + 1)
+            .fold(2, usize::max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Rle,       // .rle
+    Plaintext, // .cells
+    Life106,   // .lif or .life
+    Macrocell, // .mc
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "rle" => Some(Format::Rle),
+            "cells" => Some(Format::Plaintext),
+            "lif" | "life" => Some(Format::Life106),
+            "mc" => Some(Format::Macrocell),
+            _ => None,
+        }
+    }
+
+    pub fn parse(self, text: &str) -> Result<Pattern, PatternError> {
+        match self {
+            Format::Rle => rle::parse(text),
+            Format::Plaintext => plaintext::parse(text),
+            Format::Life106 => life106::parse(text),
+            Format::Macrocell => Ok(macrocell::parse(text)?.to_pattern()),
+        }
+    }
+
+    pub fn write(self, pattern: &Pattern) -> String {
+        match self {
+            Format::Rle => rle::write(pattern),
+            Format::Plaintext => plaintext::write(pattern),
+            Format::Life106 => life106::write(pattern),
+            Format::Macrocell => {
+                let mut tree = Macrocell::from_cells(&pattern.cells, pattern.rule.clone());
+                tree.comments = pattern.comments.clone();
+                macrocell::write(&tree)
+            }
+        }
+    }
+}
+
+/// Lines and columns start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PatternErrorKind,
+}
+
+impl PatternError {
+    /// Error at byte index of the line, which has line_index lines before it
+    pub fn new(line_index: usize, line: &str, index: usize, kind: PatternErrorKind) -> Self {
+        PatternError {
+            line: line_index + 1,
+            column: line[..index].chars().count() + 1,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.line, self.column, self.kind
+        )
+    }
+}
+
+impl Error for PatternError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternErrorKind {
+    MissingHeader(&'static str), // Header the format starts with
+    InvalidHeader(String),
+    InvalidRule(RuleError),
+    UnexpectedCharacter(char),
+    InvalidCoordinates(String),
+    InvalidNode(String), // Macrocell node line that doesn't make sense, with the reason why
+    RunCountTooLarge,    // RLE run that reaches further than coordinates go
+}
+
+impl fmt::Display for PatternErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternErrorKind::MissingHeader(header) => {
+                write!(f, "Pattern has no \"{}\" header", header)
+            }
+            PatternErrorKind::InvalidHeader(part) => write!(f, "Invalid header entry \"{}\"", part),
+            PatternErrorKind::InvalidRule(error) => write!(f, "Invalid rule: {}", error),
+            PatternErrorKind::UnexpectedCharacter(character) => {
+                write!(f, "Unexpected character '{}'", character)
+            }
+            PatternErrorKind::InvalidCoordinates(text) => {
+                write!(f, "Expected two whole numbers, found \"{}\"", text)
+            }
+            PatternErrorKind::InvalidNode(reason) => write!(f, "{}", reason),
+            PatternErrorKind::RunCountTooLarge => write!(f, "Run count is too large"),
+        }
+    }
+}
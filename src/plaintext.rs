@@ -0,0 +1,115 @@
+//! Plaintext patterns, as in the .cells files on the LifeWiki:
+//!
+//! ```text
+//! !Name: Glider
+//! .O.
+//! ..O
+//! OOO
+//! ```
+//!
+//! Lines starting with ! are comments, every other line is a row of cells. . is a dead cell and O a live cell.
+
+use crate::pattern::{Pattern, PatternError, PatternErrorKind};
+use crate::simulation::{CellState, Position};
+
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut y = 0;
+    for (line_index, line) in text.lines().enumerate() {
+        if let Some(comment) = line.strip_prefix('!') {
+            pattern.comments.push(comment.to_string());
+            continue;
+        }
+
+        let line = line.trim_end();
+        for (x, (index, character)) in line.char_indices().enumerate() {
+            match character {
+                '.' => (),
+                'O' | '*' => {
+                    let position = Position { x: x as isize, y };
+                    pattern.cells.push((position, CellState::Alive));
+                }
+                character => {
+                    let kind = PatternErrorKind::UnexpectedCharacter(character);
+                    return Err(PatternError::new(line_index, line, index, kind));
+                }
+            }
+        }
+
+        // Empty lines at the end of the file don't count as rows
+        if !line.is_empty() {
+            pattern.width = pattern.width.max(line.chars().count());
+            pattern.height = y as usize + 1;
+        }
+        y += 1;
+    }
+
+    Ok(pattern)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+    for comment in &pattern.comments {
+        text.push_str(&format!("!{}\n", comment));
+    }
+
+    // Only live cells can be written down, so cells in other states are left out
+    let mut rows = vec![vec!['.'; pattern.width]; pattern.height];
+    for position in pattern.live_cells() {
+        rows[position.y as usize][position.x as usize] = 'O';
+    }
+    for row in rows {
+        text.extend(row);
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::sparse::SparseGrid;
+
+    const BLINKER: &str = "!Name: Blinker\n\nOOO\n";
+
+    #[test]
+    fn reads_and_writes_a_blinker() {
+        let pattern = parse(BLINKER).unwrap();
+        assert_eq!(pattern.comments, vec!["Name: Blinker".to_string()]);
+        assert_eq!((pattern.width, pattern.height), (3, 2));
+        assert_eq!(
+            pattern.live_cells(),
+            vec![
+                Position { x: 0, y: 1 },
+                Position { x: 1, y: 1 },
+                Position { x: 2, y: 1 },
+            ]
+        );
+        assert_eq!(write(&pattern), "!Name: Blinker\n...\nOOO\n");
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+    }
+
+    #[test]
+    fn blinker_turns_upright() {
+        let pattern = parse(BLINKER).unwrap();
+        let mut grid = SparseGrid::new();
+        for &(position, state) in &pattern.cells {
+            grid.set(position, state);
+        }
+        grid.step(&"B3/S23".parse().unwrap());
+
+        let mut live_cells = grid.live_cells();
+        live_cells.sort_by_key(|position| (position.y, position.x));
+        let upright: Vec<Position> = (0..3).map(|y| Position { x: 1, y }).collect();
+        assert_eq!(live_cells, upright);
+    }
+
+    #[test]
+    fn points_at_unexpected_characters() {
+        let error = parse(".O.\n..o\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.kind, PatternErrorKind::UnexpectedCharacter('o'));
+    }
+}
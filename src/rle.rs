@@ -0,0 +1,450 @@
+//! Run Length Encoded patterns, the format most Life software uses to exchange patterns:
+//!
+//! ```text
+//! #N Glider
+//! x = 3, y = 3, rule = B3/S23
+//! bob$2bo$3o!
+//! ```
+//!
+//! b is a dead cell, o a live cell, $ ends a row and ! ends the pattern. A number in front repeats the tag.
+//! Patterns of rules with more than two states write dead cells as . and the others as A to X for states 1 to 24,
+//! with a prefix from p to y adding 24 for every letter after o, so pA is state 25 and yO state 255.
+//! Patterns of rule tables give the name of the table as their rule, see [`Pattern::read_rule_table`].
+
+use crate::pattern::{Pattern, PatternError, PatternErrorKind};
+use crate::rule_table;
+use crate::simulation::{CellState, Position};
+
+const MAX_LINE_LENGTH: usize = 70;
+const HEADER: &str = "x = ..., y = ...";
+const MAX_RUN_COUNT: usize = u32::MAX as usize;
+const STATES_PER_PREFIX: u8 = 24; // A to X
+
+pub fn parse(text: &str) -> Result<Pattern, PatternError> {
+    let mut pattern = Pattern::default();
+    let mut lines = text.lines().enumerate();
+
+    // Comment lines come before the header
+    let (header_index, header) = loop {
+        let (line_index, line) = match lines.next() {
+            Some(line) => line,
+            None => {
+                let kind = PatternErrorKind::MissingHeader(HEADER);
+                return Err(PatternError::new(0, "", 0, kind));
+            }
+        };
+        if let Some(comment) = line.trim().strip_prefix('#') {
+            pattern.comments.push(comment.to_string());
+        } else if line.trim_start().starts_with('x') {
+            break (line_index, line);
+        } else if !line.trim().is_empty() {
+            let kind = PatternErrorKind::MissingHeader(HEADER);
+            return Err(PatternError::new(line_index, line, 0, kind));
+        }
+    };
+
+    // Golly adds the grid it ran the pattern on to the rule, as in "B3/S23:T100,80". That part is ignored
+    let entries = header.split(':').next().unwrap_or_default();
+    let mut start = 0;
+    for part in entries.split(',') {
+        let entry_start = start + part.len() - part.trim_start().len();
+        let error = |kind| PatternError::new(header_index, header, entry_start, kind);
+        let invalid_header = || error(PatternErrorKind::InvalidHeader(part.trim().to_string()));
+
+        let mut entry = part.splitn(2, '=').map(str::trim);
+        match (entry.next(), entry.next()) {
+            (Some("x"), Some(value)) => {
+                pattern.width = value.parse().map_err(|_| invalid_header())?;
+            }
+            (Some("y"), Some(value)) => {
+                pattern.height = value.parse().map_err(|_| invalid_header())?;
+            }
+            // Rules such as R5,C0,M1,S34..58,B34..45,NM have commas of their own, so the rule is the rest of the line.
+            // Patterns of rule tables give the name of the table instead
+            (Some("rule"), Some(_)) => {
+                let (_, value) = entries[start..].split_once('=').unwrap_or_default();
+                let value = value.trim();
+                match value.parse() {
+                    Ok(rule) => pattern.rule = Some(rule),
+                    Err(_) if rule_table::is_name(value) => {
+                        pattern.rule_table = Some(value.to_string())
+                    }
+                    Err(rule_error) => {
+                        return Err(error(PatternErrorKind::InvalidRule(rule_error)))
+                    }
+                }
+                break;
+            }
+            _ => return Err(invalid_header()),
+        }
+        start += part.len() + 1;
+    }
+
+    let mut position = Position { x: 0, y: 0 };
+    let mut run_count: Option<usize> = None;
+    let mut prefix: Option<char> = None; // Prefix of the state whose letter comes next
+    'body: for (line_index, line) in lines {
+        if let Some(comment) = line.trim().strip_prefix('#') {
+            pattern.comments.push(comment.to_string());
+            continue;
+        }
+
+        for (index, character) in line.char_indices() {
+            let too_large = || {
+                let kind = PatternErrorKind::RunCountTooLarge;
+                PatternError::new(line_index, line, index, kind)
+            };
+            let unexpected = || {
+                let kind = PatternErrorKind::UnexpectedCharacter(character);
+                PatternError::new(line_index, line, index, kind)
+            };
+            let count = run_count.unwrap_or(1) as isize;
+            let state = match (prefix.take(), character) {
+                (None, '0'..='9') => {
+                    let digit = character.to_digit(10).unwrap() as usize;
+                    // Runs stay small enough that the coordinates they lead to fit into an isize
+                    let count = run_count
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|count| count.checked_add(digit))
+                        .filter(|&count| count <= MAX_RUN_COUNT)
+                        .ok_or_else(too_large)?;
+                    run_count = Some(count);
+                    continue;
+                }
+                (None, 'p'..='y') => {
+                    prefix = Some(character);
+                    continue;
+                }
+                (None, 'b') | (None, '.') => Some(CellState::Dead),
+                (None, 'o') => Some(CellState::Alive),
+                (prefix, 'A'..='X') => {
+                    let letter = character as u8 - b'A' + 1;
+                    let offset = prefix.map_or(0, |prefix| prefix as u8 - b'p' + 1);
+                    let index = offset
+                        .checked_mul(STATES_PER_PREFIX)
+                        .and_then(|offset| offset.checked_add(letter))
+                        .ok_or_else(unexpected)?;
+                    Some(CellState::from_index(index))
+                }
+                (None, '$') => {
+                    position.x = 0;
+                    position.y = position.y.checked_add(count).ok_or_else(too_large)?;
+                    None
+                }
+                (None, '!') => break 'body,
+                (None, character) if character.is_whitespace() => None,
+                _ => return Err(unexpected()),
+            };
+
+            if let Some(state) = state {
+                let end = position.x.checked_add(count).ok_or_else(too_large)?;
+                if state != CellState::Dead {
+                    for x in position.x..end {
+                        pattern.cells.push((Position { x, y: position.y }, state));
+                    }
+                }
+                position.x = end;
+            }
+            run_count = None;
+        }
+    }
+
+    // Be lenient about headers that understate the pattern size
+    for (position, _) in &pattern.cells {
+        pattern.width = pattern.width.max(position.x as usize + 1);
+        pattern.height = pattern.height.max(position.y as usize + 1);
+    }
+
+    Ok(pattern)
+}
+
+pub fn write(pattern: &Pattern) -> String {
+    let mut text = String::new();
+    for comment in &pattern.comments {
+        text.push_str(&format!("#{}\n", comment));
+    }
+
+    text.push_str(&format!("x = {}, y = {}", pattern.width, pattern.height));
+    if let Some(rule) = &pattern.rule {
+        text.push_str(&format!(", rule = {}", rule));
+    }
+    text.push('\n');
+
+    let mut cells = pattern.cells.clone();
+    cells.sort_by_key(|(position, _)| (position.y, position.x));
+    cells.dedup_by_key(|(position, _)| *position);
+
+    // Letters are only needed for more than two states, as Golly writes them
+    let is_multi_state = pattern.rule.as_ref().is_some_and(|rule| rule.states() > 2)
+        || cells.iter().any(|(_, state)| state.index() > 1);
+    let tag = |state: CellState| tag(state, is_multi_state);
+
+    // Runs of cells in the same state, the dead cells between them and row ends. Dead cells at the end of a row are
+    // left out
+    let mut tokens = vec![];
+    let mut position = Position { x: 0, y: 0 };
+    let mut run: Option<(CellState, usize)> = None;
+    for (cell, state) in cells {
+        if let Some((run_state, length)) = run {
+            if cell.y != position.y || cell.x != position.x || state != run_state {
+                tokens.push(token(length, &tag(run_state)));
+                run = None;
+            }
+        }
+        if cell.y > position.y {
+            tokens.push(token((cell.y - position.y) as usize, "$"));
+            position = Position { x: 0, y: cell.y };
+        }
+        if cell.x > position.x {
+            tokens.push(token((cell.x - position.x) as usize, &tag(CellState::Dead)));
+        }
+        run = Some((state, run.map_or(1, |(_, length)| length + 1)));
+        position.x = cell.x + 1;
+    }
+    if let Some((state, length)) = run {
+        tokens.push(token(length, &tag(state)));
+    }
+    tokens.push("!".to_string());
+
+    // Wrap lines without splitting up a token
+    let mut line = String::new();
+    for token in tokens {
+        if line.len() + token.len() > MAX_LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+// b and o for two states, and . and letters for more
+fn tag(state: CellState, is_multi_state: bool) -> String {
+    match (state, is_multi_state) {
+        (CellState::Dead, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (CellState::Dead, true) => ".".to_string(),
+        (state, true) => {
+            let index = state.index() - 1;
+            let letter = (b'A' + index % STATES_PER_PREFIX) as char;
+            match index / STATES_PER_PREFIX {
+                0 => letter.to_string(),
+                prefix => format!("{}{}", (b'p' + prefix - 1) as char, letter),
+            }
+        }
+    }
+}
+
+fn token(count: usize, tag: &str) -> String {
+    if count == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", count, tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::rule::{Rule, RuleError};
+    use crate::rule_table::RuleTable;
+    use crate::sparse::SparseGrid;
+
+    use std::{env, fs, process};
+
+    const GLIDER: &str = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    fn positions(cells: &[(isize, isize)]) -> Vec<Position> {
+        cells.iter().map(|&(x, y)| Position { x, y }).collect()
+    }
+
+    #[test]
+    fn reads_a_glider() {
+        let pattern = parse(GLIDER).unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 3));
+        assert_eq!(pattern.rule, Some("B3/S23".parse().unwrap()));
+        assert_eq!(pattern.comments, vec!["N Glider".to_string()]);
+        assert_eq!(
+            pattern.live_cells(),
+            positions(&[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)])
+        );
+    }
+
+    #[test]
+    fn glider_moves_diagonally_in_four_generations() {
+        let pattern = parse(GLIDER).unwrap();
+        let rule = pattern.rule.unwrap();
+        let mut grid = SparseGrid::new();
+        for &(position, state) in &pattern.cells {
+            grid.set(position, state);
+        }
+        grid.advance(&rule, 4);
+
+        let mut live_cells = grid.live_cells();
+        live_cells.sort_by_key(|position| (position.y, position.x));
+        assert_eq!(
+            live_cells,
+            positions(&[(2, 1), (3, 2), (1, 3), (2, 3), (3, 3)])
+        );
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        assert_eq!(
+            write(&parse(GLIDER).unwrap()),
+            "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+
+        // Long runs, empty rows and lines that need wrapping
+        let live_cells: Vec<Position> = (0..200)
+            .map(|i| Position {
+                x: i % 97 * 3,
+                y: i / 97 * 4,
+            })
+            .collect();
+        let pattern = Pattern::from_live_cells(&live_cells, None);
+        assert_eq!(parse(&write(&pattern)).unwrap(), pattern);
+        assert!(write(&pattern)
+            .lines()
+            .all(|line| line.len() <= MAX_LINE_LENGTH));
+    }
+
+    #[test]
+    fn reads_rules_with_commas() {
+        let text = "x = 1, y = 1, rule = R5,C0,M1,S34..58,B34..45,NM:P100,100\no!";
+        let rule = parse(text).unwrap().rule.unwrap();
+        assert_eq!(rule, "R5,C0,M1,S34..58,B34..45,NM".parse().unwrap());
+
+        let error = parse("x = 1, y = 1, rule = R5,C0,M1,S34..58,NM\no!").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 15));
+        assert_eq!(
+            error.kind,
+            PatternErrorKind::InvalidRule(RuleError::MissingBirth)
+        );
+    }
+
+    #[test]
+    fn rereads_the_rules_it_writes() {
+        let rulestrings = [
+            "B3/S23",
+            "/2/3",
+            "B2-a/S12",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "B2/S34H",
+            "B4,10/S2,12L",
+        ];
+        for rulestring in &rulestrings {
+            let rule: Rule = rulestring.parse().unwrap();
+            let pattern = Pattern::from_live_cells(&[Position { x: 0, y: 0 }], Some(rule));
+            let text = write(&pattern);
+            assert!(text.contains(&format!("rule = {}\n", rulestring)));
+            assert_eq!(parse(&text).unwrap(), pattern);
+        }
+    }
+
+    #[test]
+    fn keeps_the_states_of_multi_state_rules() {
+        // Brian's Brain, with dying cells in state 2
+        let text = "x = 3, y = 2, rule = /2/3\n.AB$2A!\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(
+            pattern.cells,
+            vec![
+                (Position { x: 1, y: 0 }, CellState::Alive),
+                (Position { x: 2, y: 0 }, CellState::Dying(1)),
+                (Position { x: 0, y: 1 }, CellState::Alive),
+                (Position { x: 1, y: 1 }, CellState::Alive),
+            ]
+        );
+        assert_eq!(write(&pattern), text);
+
+        // States past X take a prefix
+        let text = "x = 4, y = 1\npA2yOX!\n";
+        let pattern = parse(text).unwrap();
+        let states: Vec<u8> = pattern
+            .cells
+            .iter()
+            .map(|(_, state)| state.index())
+            .collect();
+        assert_eq!(states, vec![25, 255, 255, 24]);
+        assert_eq!(write(&pattern), text);
+
+        // Golly also writes b and o for multi-state rules
+        assert_eq!(
+            parse("x = 2, y = 1, rule = /2/3\nbo!").unwrap().cells,
+            vec![(Position { x: 1, y: 0 }, CellState::Alive)]
+        );
+    }
+
+    #[test]
+    fn reads_rule_tables_from_next_to_the_pattern() {
+        let table = "@RULE Cycle\n@TABLE\nn_states:3\nneighborhood:vonNeumann\nsymmetries:none\n1,0,0,0,0,2\n";
+        let rule = Rule::from_table(RuleTable::parse(table).unwrap());
+        let cells = [(Position { x: 0, y: 0 }, CellState::Dying(1))];
+        let text = write(&Pattern::from_cells(&cells, Some(rule.clone())));
+        assert_eq!(text, "x = 1, y = 1, rule = Cycle\nB!\n");
+
+        let mut pattern = parse(&text).unwrap();
+        assert_eq!(pattern.rule, None);
+        assert_eq!(pattern.rule_table.as_deref(), Some("Cycle"));
+        assert_eq!(pattern.cells, cells);
+
+        let directory = env::temp_dir().join(format!("conway-rle-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        assert!(pattern.clone().read_rule_table(&directory).is_err());
+        fs::write(directory.join("Cycle.rule"), table).unwrap();
+        let result = pattern.read_rule_table(&directory);
+        fs::remove_dir_all(&directory).unwrap();
+        result.unwrap();
+        assert_eq!(pattern.rule, Some(rule));
+
+        // Anything else that isn't a rulestring is still an error
+        let error = parse("x = 1, y = 1, rule = B3/S2x\no!").unwrap_err();
+        assert_eq!(
+            error.kind,
+            PatternErrorKind::InvalidRule(RuleError::UnknownLetter(2, 'x'))
+        );
+    }
+
+    #[test]
+    fn points_at_errors() {
+        let error = parse("x = 3, y = 3\nbo$2bo$3z!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 9));
+        assert_eq!(error.kind, PatternErrorKind::UnexpectedCharacter('z'));
+
+        // Prefixes need a letter after them, and yO is the last state there is
+        let error = parse("x = 2, y = 1\npo!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 2));
+        assert_eq!(error.kind, PatternErrorKind::UnexpectedCharacter('o'));
+        let error = parse("x = 2, y = 1\nAyP!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.kind, PatternErrorKind::UnexpectedCharacter('P'));
+
+        let error = parse("x = 3, y = three\no!").unwrap_err();
+        assert_eq!(
+            error.kind,
+            PatternErrorKind::InvalidHeader("y = three".to_string())
+        );
+
+        let error = parse("bo$2bo$3o!").unwrap_err();
+        assert_eq!(error.kind, PatternErrorKind::MissingHeader(HEADER));
+    }
+
+    #[test]
+    fn rejects_overlong_runs() {
+        let error = parse("x = 1, y = 1\n99999999999999999999999o!").unwrap_err();
+        assert_eq!(error.kind, PatternErrorKind::RunCountTooLarge);
+
+        let runs = format!("x = 1, y = 1\n{}$o!", MAX_RUN_COUNT + 1);
+        assert_eq!(
+            parse(&runs).unwrap_err().kind,
+            PatternErrorKind::RunCountTooLarge
+        );
+    }
+}
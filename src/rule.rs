@@ -0,0 +1,1013 @@
+use crate::rule_table::{Neighborhood, RuleTable};
+use crate::simulation::{CellState, Position};
+use crate::tiling::Tiling;
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Offsets of the eight neighbors of a cell. Bit i of a neighborhood is set when the neighbor at `NEIGHBORS[i]` is alive
+pub const NEIGHBORS: [Position; 8] = [
+    Position { x: -1, y: -1 },
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: -1 },
+    Position { x: -1, y: 0 },
+    Position { x: 1, y: 0 },
+    Position { x: -1, y: 1 },
+    Position { x: 0, y: 1 },
+    Position { x: 1, y: 1 },
+];
+
+// Letters of Hensel notation for 1 to 4 live neighbors, in their usual order, each with one neighborhood it stands
+// for. The letter stands for the rotations and reflections of that neighborhood as well.
+// 5 to 7 live neighbors take the letter of the neighborhood of their dead neighbors
+const LETTERS: [&[(char, u8)]; 4] = [
+    &[('c', 0b0000_0001), ('e', 0b0000_0010)],
+    &[
+        ('c', 0b0000_0101),
+        ('e', 0b0000_1010),
+        ('k', 0b0001_0001),
+        ('a', 0b0000_0011),
+        ('i', 0b0001_1000),
+        ('n', 0b0010_0100),
+    ],
+    &[
+        ('c', 0b0010_0101),
+        ('e', 0b0001_1010),
+        ('k', 0b0011_0010),
+        ('a', 0b0000_1011),
+        ('i', 0b0000_0111),
+        ('n', 0b0000_1101),
+        ('y', 0b0011_0001),
+        ('q', 0b0010_0110),
+        ('j', 0b0000_1110),
+        ('r', 0b0001_1001),
+    ],
+    &[
+        ('c', 0b1010_0101),
+        ('e', 0b0101_1010),
+        ('k', 0b0011_0011),
+        ('a', 0b0000_1111),
+        ('i', 0b0001_1101),
+        ('n', 0b0010_0111),
+        ('y', 0b0011_0101),
+        ('q', 0b0011_0110),
+        ('j', 0b0011_1010),
+        ('r', 0b0001_1011),
+        ('t', 0b0011_1001),
+        ('w', 0b0010_1110),
+        ('z', 0b0011_1100),
+    ],
+];
+
+// Largest radius of a Larger than Life neighborhood, the same as in Golly
+const MAX_RADIUS: usize = 500;
+
+/// Most states a rule can have, the same as in Golly. Each state still fits in a byte, see
+/// [`CellState::index`]
+pub const MAX_STATES: u16 = 256;
+
+/// Shape of the neighborhood of a Larger than Life rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Moore,      // Square
+    VonNeumann, // Diamond
+    Circular,   // Cells whose centers lie within the radius plus a half of the middle
+}
+
+/// Extended neighborhood of a Larger than Life rule such as Bosco's rule, R5,C0,M1,S34..58,B34..45,NM. Instead of
+/// their direct neighbors, cells count the live cells within the radius
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LargerThanLife {
+    pub radius: usize,
+    pub shape: Shape,
+    pub counts_middle: bool,      // Whether a live cell counts itself
+    pub birth: (usize, usize),    // Fewest and most live cells that bring a dead cell to life
+    pub survival: (usize, usize), // Fewest and most live cells that keep a live cell alive
+}
+
+impl LargerThanLife {
+    /// How far the neighborhood reaches to either side of the middle column, in each row from the top one down
+    pub fn half_widths(&self) -> Vec<usize> {
+        let radius = self.radius as isize;
+        (-radius..=radius)
+            .map(|y_offset| match self.shape {
+                Shape::Moore => self.radius,
+                Shape::VonNeumann => self.radius - y_offset.unsigned_abs(),
+                Shape::Circular => {
+                    let reach = radius * radius + radius - y_offset * y_offset;
+                    (reach as f64).sqrt() as usize
+                }
+            })
+            .collect()
+    }
+
+    /// Number of cells in the neighborhood, the middle one included
+    pub fn size(&self) -> usize {
+        self.half_widths()
+            .iter()
+            .map(|half_width| 2 * half_width + 1)
+            .sum()
+    }
+}
+
+/// Isotropic rule on the Moore neighborhood, e.g. B3/S23 for Conway's Game of Life. Outer-totalistic rules only look
+/// at the number of live neighbors. Isotropic non-totalistic rules such as B2-a/S12 tell arrangements of the same
+/// number apart with the letters of Hensel notation, as long as rotating or reflecting an arrangement doesn't change
+/// what happens.
+/// Generations rules such as Brian's Brain, /2/3, have more than two states: a live cell that doesn't survive decays
+/// through the dying states before it is dead and can be born again. Only live cells count as neighbors.
+/// Larger than Life rules count the live cells in a larger neighborhood instead, see [`LargerThanLife`].
+/// Rules on hexagons and triangles end in H or L, as in B2/S34H, and only look at the number of live neighbors.
+/// Rule tables such as Wireworld list the transitions of each state instead, see [`RuleTable`]. Their states past the
+/// live one are stored as dying states, so that state n is `Dying(n - 1)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    birth: [bool; 256], // birth[n]: Dead cell with neighborhood n, or n live neighbors off squares, comes alive
+    survival: [bool; 256], // survival[n]: Live cell with neighborhood n, or n live neighbors off squares, stays alive
+    states: u16,           // Alive, dead and the dying states in between
+    larger_than_life: Option<LargerThanLife>, // Replaces the neighborhoods above
+    tiling: Tiling,
+    table: Option<Arc<RuleTable>>, // Replaces everything above but the states and the tiling
+}
+
+impl Rule {
+    pub fn new(birth: &[usize], survival: &[usize]) -> Result<Rule, RuleError> {
+        Rule::generations(birth, survival, 2)
+    }
+
+    /// Rule whose cells go through states - 2 dying states after they stop living
+    pub fn generations(
+        birth: &[usize],
+        survival: &[usize],
+        states: u16,
+    ) -> Result<Rule, RuleError> {
+        Rule::on_tiling(birth, survival, states, Tiling::Square)
+    }
+
+    /// Generations rule on cells of another shape, whose neighbors are those given by the tiling
+    pub fn on_tiling(
+        birth: &[usize],
+        survival: &[usize],
+        states: u16,
+        tiling: Tiling,
+    ) -> Result<Rule, RuleError> {
+        if !(2..=MAX_STATES).contains(&states) {
+            return Err(RuleError::InvalidStateCount(states.to_string()));
+        }
+        let mut rule = Rule {
+            birth: [false; 256],
+            survival: [false; 256],
+            states,
+            larger_than_life: None,
+            tiling,
+            table: None,
+        };
+
+        for (counts, neighborhoods) in [(birth, &mut rule.birth), (survival, &mut rule.survival)] {
+            for &count in counts {
+                if count > tiling.neighbor_count() {
+                    return Err(RuleError::InvalidNeighborCount(count));
+                }
+                if tiling != Tiling::Square {
+                    neighborhoods[count] = true;
+                    continue;
+                }
+                for (neighborhood, entry) in neighborhoods.iter_mut().enumerate() {
+                    if neighborhood.count_ones() as usize == count {
+                        *entry = true;
+                    }
+                }
+            }
+        }
+
+        Ok(rule)
+    }
+
+    /// Larger than Life rule, whose cells go through states - 2 dying states after they stop living like those of a
+    /// Generations rule
+    pub fn extended(larger_than_life: LargerThanLife, states: u16) -> Result<Rule, RuleError> {
+        if !(2..=MAX_STATES).contains(&states) {
+            return Err(RuleError::InvalidStateCount(states.to_string()));
+        }
+        if !(1..=MAX_RADIUS).contains(&larger_than_life.radius) {
+            return Err(RuleError::InvalidRadius(larger_than_life.radius));
+        }
+        let size = larger_than_life.size();
+        for (fewest, most) in [larger_than_life.birth, larger_than_life.survival] {
+            if fewest > most || most > size {
+                return Err(RuleError::InvalidCountRange(fewest, most));
+            }
+        }
+
+        let mut rule = Rule::generations(&[], &[], states)?;
+        rule.larger_than_life = Some(larger_than_life);
+        Ok(rule)
+    }
+
+    /// Rule that runs the rule table. Tables on the hexagonal neighborhood put their cells on hexagons
+    pub fn from_table(table: RuleTable) -> Rule {
+        let tiling = match table.neighborhood() {
+            Neighborhood::Hexagonal => Tiling::Hexagonal,
+            _ => Tiling::Square,
+        };
+        let mut rule = Rule::on_tiling(&[], &[], table.states(), tiling).unwrap();
+        rule.table = Some(Arc::new(table));
+        rule
+    }
+
+    /// Whether a dead cell with this many live neighbors comes alive, however they are arranged. Rule tables only tell
+    /// whether a cell among dead neighbors comes alive, so that empty space doesn't fill up
+    pub fn births(&self, live_neighbor_count: usize) -> bool {
+        if let Some(table) = &self.table {
+            let dead = vec![0; table.neighborhood().offsets().len() + 1];
+            return live_neighbor_count == 0 && table.next_state(&dead) != 0;
+        }
+        match self.larger_than_life {
+            Some(LargerThanLife {
+                birth: (fewest, most),
+                ..
+            }) => (fewest..=most).contains(&live_neighbor_count),
+            None => self.holds_for_count(&self.birth, live_neighbor_count),
+        }
+    }
+
+    /// Whether a live cell with this many live neighbors stays alive, however they are arranged. Never for rule tables
+    pub fn survives(&self, live_neighbor_count: usize) -> bool {
+        if self.table.is_some() {
+            return false;
+        }
+        match self.larger_than_life {
+            Some(LargerThanLife {
+                survival: (fewest, most),
+                ..
+            }) => (fewest..=most).contains(&live_neighbor_count),
+            None => self.holds_for_count(&self.survival, live_neighbor_count),
+        }
+    }
+
+    pub fn larger_than_life(&self) -> Option<LargerThanLife> {
+        self.larger_than_life
+    }
+
+    pub fn tiling(&self) -> Tiling {
+        self.tiling
+    }
+
+    pub fn table(&self) -> Option<&RuleTable> {
+        self.table.as_deref()
+    }
+
+    /// Whether only the number of direct live neighbors on the square tiling matters, not their arrangement
+    pub fn is_totalistic(&self) -> bool {
+        // Every neighborhood has to agree with the one whose live neighbors take up the lowest bits
+        self.larger_than_life.is_none()
+            && self.table.is_none()
+            && self.tiling == Tiling::Square
+            && [&self.birth, &self.survival].iter().all(|neighborhoods| {
+                (0..256).all(|neighborhood: usize| {
+                    let lowest = (1 << neighborhood.count_ones()) - 1;
+                    neighborhoods[neighborhood] == neighborhoods[lowest]
+                })
+            })
+    }
+
+    /// Number of states, up to [`MAX_STATES`]
+    pub fn states(&self) -> u16 {
+        self.states
+    }
+
+    /// State after a cell in the given state with the given neighborhood of live neighbors, see [`NEIGHBORS`]. Only
+    /// for rules on the square tiling that aren't Larger than Life rules
+    pub fn next_state(&self, state: CellState, neighborhood: u8) -> CellState {
+        match state {
+            CellState::Dead if self.birth[neighborhood as usize] => CellState::Alive,
+            CellState::Dead => CellState::Dead,
+            CellState::Alive if self.survival[neighborhood as usize] => CellState::Alive,
+            CellState::Alive => self.decayed(0),
+            CellState::Dying(age) => self.decayed(age),
+        }
+    }
+
+    /// State after a cell in the given state with the given number of live neighbors, not counting the cell itself.
+    /// Neighbors are the cells in the Larger than Life neighborhood, or the direct neighbors on the rule's tiling
+    pub fn next_state_by_count(&self, state: CellState, live_neighbor_count: usize) -> CellState {
+        let counts_middle = self
+            .larger_than_life
+            .is_some_and(|larger_than_life| larger_than_life.counts_middle);
+        let count = match state {
+            CellState::Alive if counts_middle => live_neighbor_count + 1,
+            _ => live_neighbor_count,
+        };
+        match state {
+            CellState::Dead if self.births(count) => CellState::Alive,
+            CellState::Dead => CellState::Dead,
+            CellState::Alive if self.survives(count) => CellState::Alive,
+            CellState::Alive => self.decayed(0),
+            CellState::Dying(age) => self.decayed(age),
+        }
+    }
+
+    /// Whether [`previous_state`](Rule::previous_state) can tell the state a cell changed from. Cells of rule tables
+    /// can get to a state from several others
+    pub fn has_previous_states(&self) -> bool {
+        self.table.is_none()
+    }
+
+    /// State a cell was in one generation before the rule took it to the given state. Cells always change in the
+    /// same order, so this only needs the new state, as long as the state actually changed
+    pub fn previous_state(&self, state: CellState) -> CellState {
+        match state {
+            CellState::Alive => CellState::Dead,
+            CellState::Dying(1) => CellState::Alive,
+            CellState::Dying(age) => CellState::Dying(age - 1),
+            CellState::Dead if self.states > 2 => CellState::Dying((self.states - 2) as u8),
+            CellState::Dead => CellState::Alive,
+        }
+    }
+
+    // State after a cell of the given age, 0 for a live cell, decays once more
+    fn decayed(&self, age: u8) -> CellState {
+        if age as usize + 2 < self.states as usize {
+            CellState::Dying(age + 1)
+        } else {
+            CellState::Dead
+        }
+    }
+
+    fn holds_for_count(&self, neighborhoods: &[bool; 256], count: usize) -> bool {
+        if self.tiling != Tiling::Square {
+            return count <= self.tiling.neighbor_count() && neighborhoods[count];
+        }
+        (0..=255_u8)
+            .filter(|neighborhood| neighborhood.count_ones() as usize == count)
+            .all(|neighborhood| neighborhoods[neighborhood as usize])
+    }
+
+    // Parses neighbor counts such as "2-a3i", where letters pick out some arrangements of a count and a minus sign
+    // all but those, into the neighborhoods they stand for
+    fn parse_counts(part: &str, neighborhoods: &mut [bool; 256]) -> Result<(), RuleError> {
+        let mut is_listed = [false; 9];
+        let mut characters = part.chars().peekable();
+        while let Some(character) = characters.next() {
+            let count = character
+                .to_digit(10)
+                .ok_or(RuleError::UnexpectedCharacter(character))? as usize;
+            let entry = is_listed
+                .get_mut(count)
+                .ok_or(RuleError::InvalidNeighborCount(count))?;
+            if *entry {
+                return Err(RuleError::DuplicateNeighborCount(count));
+            }
+            *entry = true;
+
+            let is_excluding = characters.next_if_eq(&'-').is_some();
+            let mut letters = vec![];
+            while let Some(letter) = characters.next_if(|character| character.is_ascii_alphabetic())
+            {
+                let letter = letter.to_ascii_lowercase();
+                if !letters_for(count).iter().any(|&(known, _)| known == letter) {
+                    return Err(RuleError::UnknownLetter(count, letter));
+                }
+                letters.push(letter);
+            }
+            if is_excluding && letters.is_empty() {
+                return Err(RuleError::UnexpectedCharacter('-'));
+            }
+
+            for neighborhood in 0..=255_u8 {
+                if neighborhood.count_ones() as usize == count
+                    && (letters.is_empty()
+                        || letters.contains(&letter(neighborhood)) != is_excluding)
+                {
+                    neighborhoods[neighborhood as usize] = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Parses the neighbor counts of a rule off the square tiling, which have no letters. Counts past 9 need commas
+    // between them, as in "4,10,12"
+    fn parse_tiling_counts(
+        part: &str,
+        tiling: Tiling,
+        counts: &mut [bool; 256],
+    ) -> Result<(), RuleError> {
+        let parsed: Vec<usize> = if part.contains(',') {
+            part.split(',')
+                .map(|number| {
+                    number.parse().map_err(|_| {
+                        RuleError::UnexpectedCharacter(
+                            number.chars().find(|c| !c.is_ascii_digit()).unwrap_or(','),
+                        )
+                    })
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            part.chars()
+                .map(|character| {
+                    character
+                        .to_digit(10)
+                        .map(|count| count as usize)
+                        .ok_or(RuleError::UnexpectedCharacter(character))
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        for count in parsed {
+            if count > tiling.neighbor_count() {
+                return Err(RuleError::InvalidNeighborCount(count));
+            }
+            if counts[count] {
+                return Err(RuleError::DuplicateNeighborCount(count));
+            }
+            counts[count] = true;
+        }
+        Ok(())
+    }
+}
+
+// Parses a Larger than Life rulestring in Golly's notation, such as "R5,C0,M1,S34..58,B34..45,NM". C gives the number
+// of states, where C0 and C2 both mean 2. M1 counts the middle cell, and N picks the Moore (M), von Neumann (N) or
+// circular (C) neighborhood. C, M and N can be left out
+fn parse_larger_than_life(rulestring: &str) -> Result<Rule, RuleError> {
+    let mut larger_than_life = LargerThanLife {
+        radius: 0,
+        shape: Shape::Moore,
+        counts_middle: false,
+        birth: (0, 0),
+        survival: (0, 0),
+    };
+    let mut states = 2;
+    let (mut has_birth, mut has_survival) = (false, false);
+
+    for part in rulestring.split(',') {
+        let invalid = || RuleError::InvalidLargerThanLife(part.to_string());
+        let range = |value: &str| -> Result<(usize, usize), RuleError> {
+            let (fewest, most) = value.split_once("..").unwrap_or((value, value));
+            Ok((
+                fewest.parse().map_err(|_| invalid())?,
+                most.parse().map_err(|_| invalid())?,
+            ))
+        };
+
+        let value = part.get(1..).ok_or_else(invalid)?;
+        match part.chars().next() {
+            Some('R') => larger_than_life.radius = value.parse().map_err(|_| invalid())?,
+            Some('C') => match value.parse() {
+                Ok(0) => states = 2,
+                Ok(count) => states = count,
+                Err(_) => return Err(RuleError::InvalidStateCount(value.to_string())),
+            },
+            Some('M') => match value {
+                "0" => larger_than_life.counts_middle = false,
+                "1" => larger_than_life.counts_middle = true,
+                _ => return Err(invalid()),
+            },
+            Some('S') => {
+                larger_than_life.survival = range(value)?;
+                has_survival = true;
+            }
+            Some('B') => {
+                larger_than_life.birth = range(value)?;
+                has_birth = true;
+            }
+            Some('N') => match value {
+                "M" => larger_than_life.shape = Shape::Moore,
+                "N" => larger_than_life.shape = Shape::VonNeumann,
+                "C" => larger_than_life.shape = Shape::Circular,
+                _ => return Err(invalid()),
+            },
+            _ => return Err(invalid()),
+        }
+    }
+
+    if !has_birth {
+        return Err(RuleError::MissingBirth);
+    }
+    if !has_survival {
+        return Err(RuleError::MissingSurvival);
+    }
+    Rule::extended(larger_than_life, states)
+}
+
+// Letters for the number of live neighbors, each with one neighborhood it stands for. No letters means that the
+// count only has a single neighborhood
+fn letters_for(count: usize) -> Vec<(char, u8)> {
+    match count {
+        1..=4 => LETTERS[count - 1].to_vec(),
+        5..=7 => LETTERS[7 - count]
+            .iter()
+            .map(|&(letter, neighborhood)| (letter, !neighborhood))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// Letter of the neighborhood in Hensel notation, or a space for 0 or 8 live neighbors, which need no letter
+fn letter(neighborhood: u8) -> char {
+    let count = neighborhood.count_ones() as usize;
+    letters_for(count)
+        .into_iter()
+        .find(|&(_, representative)| symmetries(representative).contains(&neighborhood))
+        .map_or(' ', |(letter, _)| letter)
+}
+
+// The neighborhood turned by each multiple of 90 degrees, and mirrored
+fn symmetries(neighborhood: u8) -> [u8; 8] {
+    let transform = |map: &dyn Fn(Position) -> Position| -> u8 {
+        (0..8)
+            .filter(|&bit| neighborhood & (1 << bit) != 0)
+            .map(|bit| {
+                let offset = map(NEIGHBORS[bit]);
+                1 << NEIGHBORS.iter().position(|&other| other == offset).unwrap()
+            })
+            .fold(0, |sum, bit| sum | bit)
+    };
+    let turns = [
+        |p: Position| p,
+        |p: Position| Position { x: -p.y, y: p.x },
+        |p: Position| Position { x: -p.x, y: -p.y },
+        |p: Position| Position { x: p.y, y: -p.x },
+    ];
+
+    let mut symmetries = [0; 8];
+    for (index, turn) in turns.iter().enumerate() {
+        symmetries[index] = transform(turn);
+        symmetries[index + 4] = transform(&|p| turn(Position { x: -p.x, y: p.y }));
+    }
+    symmetries
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        // B3/S23
+        Rule::new(&[3], &[2, 3]).unwrap()
+    }
+}
+
+// Accepts "B36/S23", "b36/s23", "B36S23" and the older survival/birth notation "23/36". Hensel notation adds letters
+// after a count, as in "B2-a/S12". Generations rules add the number of states, as in "B2/S/C3" or "/2/3".
+// Larger than Life rules start with their radius, as in "R5,C0,M1,S34..58,B34..45,NM". A trailing H puts the rule on
+// hexagons and a trailing L on triangles, as in "B2/S34H"
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(rulestring: &str) -> Result<Self, Self::Err> {
+        let rulestring: String = rulestring
+            .chars()
+            .filter(|character| !character.is_whitespace())
+            .collect::<String>()
+            .to_uppercase();
+
+        if rulestring.is_empty() {
+            return Err(RuleError::Empty);
+        }
+        if rulestring.starts_with('R') {
+            return parse_larger_than_life(&rulestring);
+        }
+
+        let (rulestring, tiling) = match rulestring.strip_suffix('H') {
+            Some(rulestring) => (rulestring, Tiling::Hexagonal),
+            None => match rulestring.strip_suffix('L') {
+                Some(rulestring) => (rulestring, Tiling::Triangular),
+                None => (rulestring.as_str(), Tiling::Square),
+            },
+        };
+        let (rulestring, mut states) = match rulestring.rfind("/C") {
+            Some(index) => (&rulestring[..index], Some(&rulestring[index + 2..])),
+            None => (rulestring, None),
+        };
+
+        let (birth, survival) = if rulestring.starts_with('B') {
+            let survival_start = rulestring.find('S').ok_or(RuleError::MissingSurvival)?;
+            let birth = rulestring[1..survival_start].trim_end_matches('/');
+            (birth, &rulestring[survival_start + 1..])
+        } else if rulestring.starts_with('S') {
+            let birth_start = rulestring.find('B').ok_or(RuleError::MissingBirth)?;
+            let survival = rulestring[1..birth_start].trim_end_matches('/');
+            (&rulestring[birth_start + 1..], survival)
+        } else {
+            let mut parts = rulestring.split('/');
+            let survival = parts.next().unwrap_or_default();
+            let birth = parts.next().ok_or(RuleError::MissingBirth)?;
+            if let Some(part) = parts.next() {
+                if states.is_some() {
+                    return Err(RuleError::UnexpectedCharacter(
+                        part.chars().next().unwrap_or('/'),
+                    ));
+                }
+                states = Some(part);
+            }
+            (birth, survival)
+        };
+
+        let states = match states {
+            Some(states) => match states.parse() {
+                Ok(states) if (2..=MAX_STATES).contains(&states) => states,
+                _ => return Err(RuleError::InvalidStateCount(states.to_string())),
+            },
+            None => 2,
+        };
+        let mut rule = Rule {
+            birth: [false; 256],
+            survival: [false; 256],
+            states,
+            larger_than_life: None,
+            tiling,
+            table: None,
+        };
+        if tiling == Tiling::Square {
+            Rule::parse_counts(birth, &mut rule.birth)?;
+            Rule::parse_counts(survival, &mut rule.survival)?;
+        } else {
+            Rule::parse_tiling_counts(birth, tiling, &mut rule.birth)?;
+            Rule::parse_tiling_counts(survival, tiling, &mut rule.survival)?;
+        }
+
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(table) = &self.table {
+            return write!(f, "{}", table.name());
+        }
+        if let Some(larger_than_life) = self.larger_than_life {
+            let shape = match larger_than_life.shape {
+                Shape::Moore => 'M',
+                Shape::VonNeumann => 'N',
+                Shape::Circular => 'C',
+            };
+            return write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                larger_than_life.radius,
+                if self.states == 2 { 0 } else { self.states },
+                larger_than_life.counts_middle as u8,
+                larger_than_life.survival.0,
+                larger_than_life.survival.1,
+                larger_than_life.birth.0,
+                larger_than_life.birth.1,
+                shape
+            );
+        }
+
+        // A count with most of its letters lists the ones it doesn't have after a minus sign instead
+        let digits = |neighborhoods: &[bool; 256]| -> String {
+            if self.tiling != Tiling::Square {
+                let counts: Vec<String> = (0..=self.tiling.neighbor_count())
+                    .filter(|&count| neighborhoods[count])
+                    .map(|count| count.to_string())
+                    .collect();
+                let separator = if counts.iter().any(|count| count.len() > 1) {
+                    ","
+                } else {
+                    ""
+                };
+                return counts.join(separator);
+            }
+
+            let mut text = String::new();
+            for count in 0..=8 {
+                let letters = letters_for(count);
+                if letters.is_empty() {
+                    let neighborhood = if count == 0 { 0 } else { 255 };
+                    if neighborhoods[neighborhood] {
+                        text.push_str(&count.to_string());
+                    }
+                    continue;
+                }
+
+                let (present, absent): (Vec<_>, Vec<_>) = letters
+                    .iter()
+                    .partition(|&&(_, neighborhood)| neighborhoods[neighborhood as usize]);
+                let letters = |letters: Vec<&(char, u8)>| -> String {
+                    letters.iter().map(|&&(letter, _)| letter).collect()
+                };
+                match (present.len(), absent.len()) {
+                    (0, _) => continue,
+                    (_, 0) => text.push_str(&count.to_string()),
+                    (present_count, absent_count) if present_count <= absent_count => {
+                        text.push_str(&format!("{}{}", count, letters(present)))
+                    }
+                    _ => text.push_str(&format!("{}-{}", count, letters(absent))),
+                }
+            }
+            text
+        };
+
+        // Generations rules are written the way Golly writes them, with survival first
+        match self.states {
+            2 => write!(f, "B{}/S{}", digits(&self.birth), digits(&self.survival))?,
+            states => write!(
+                f,
+                "{}/{}/{}",
+                digits(&self.survival),
+                digits(&self.birth),
+                states
+            )?,
+        }
+        match self.tiling {
+            Tiling::Square => Ok(()),
+            Tiling::Hexagonal => write!(f, "H"),
+            Tiling::Triangular => write!(f, "L"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    Empty,
+    MissingBirth,
+    MissingSurvival,
+    UnexpectedCharacter(char),
+    InvalidNeighborCount(usize),
+    DuplicateNeighborCount(usize),
+    InvalidStateCount(String),
+    UnknownLetter(usize, char),
+    InvalidLargerThanLife(String),
+    InvalidRadius(usize),
+    InvalidCountRange(usize, usize),
+    MissingTable,
+    InvalidTable(usize, String),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::Empty => write!(f, "Rulestring is empty"),
+            RuleError::MissingBirth => write!(f, "Rulestring has no birth (B) part"),
+            RuleError::MissingSurvival => write!(f, "Rulestring has no survival (S) part"),
+            RuleError::UnexpectedCharacter(character) => {
+                write!(f, "Unexpected character '{}' in rulestring", character)
+            }
+            RuleError::InvalidNeighborCount(count) => write!(
+                f,
+                "Neighbor count {} is out of range, cells don't have that many neighbors",
+                count
+            ),
+            RuleError::DuplicateNeighborCount(count) => {
+                write!(f, "Neighbor count {} appears more than once", count)
+            }
+            RuleError::InvalidStateCount(states) => write!(
+                f,
+                "Invalid number of states \"{}\", rules have 2 to {}",
+                states, MAX_STATES
+            ),
+            RuleError::UnknownLetter(count, letter) => write!(
+                f,
+                "Letter '{}' doesn't stand for any arrangement of {} live neighbors",
+                letter, count
+            ),
+            RuleError::InvalidLargerThanLife(part) => {
+                write!(f, "Invalid part \"{}\" of a Larger than Life rule", part)
+            }
+            RuleError::InvalidRadius(radius) => write!(
+                f,
+                "Radius {} is out of range, Larger than Life rules have 1 to {}",
+                radius, MAX_RADIUS
+            ),
+            RuleError::InvalidCountRange(fewest, most) => write!(
+                f,
+                "Count range {}..{} doesn't fit into the neighborhood",
+                fewest, most
+            ),
+            RuleError::MissingTable => write!(f, "Rule file has no @TABLE section"),
+            RuleError::InvalidTable(line, message) => {
+                write!(f, "Line {} of the rule table: {}", line, message)
+            }
+        }
+    }
+}
+
+impl Error for RuleError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, NaiveGrid};
+    use crate::simulation::Position;
+    use crate::topology::Topology;
+
+    #[test]
+    fn reads_and_writes_rulestrings() {
+        let highlife: Rule = "B36/S23".parse().unwrap();
+        assert!(highlife.births(3) && highlife.births(6) && !highlife.births(2));
+        assert!(highlife.survives(2) && highlife.survives(3) && !highlife.survives(6));
+        assert_eq!(highlife.to_string(), "B36/S23");
+
+        // Other ways of writing the same rule, including the older survival/birth notation
+        for rulestring in &["b36/s23", "B36S23", "S23/B36", "23/36", " B36 / S23 "] {
+            assert_eq!(rulestring.parse::<Rule>().unwrap(), highlife);
+        }
+
+        let seeds: Rule = "B2/S".parse().unwrap();
+        assert!((0..=8).all(|count| !seeds.survives(count)));
+        assert_eq!(seeds.to_string(), "B2/S");
+        assert_eq!("/2".parse::<Rule>().unwrap(), seeds);
+        assert_eq!(Rule::default().to_string(), "B3/S23");
+    }
+
+    #[test]
+    fn rejects_invalid_rulestrings() {
+        let error = |rulestring: &str| rulestring.parse::<Rule>().unwrap_err();
+        assert_eq!(error(""), RuleError::Empty);
+        assert_eq!(error("B3"), RuleError::MissingSurvival);
+        assert_eq!(error("S23"), RuleError::MissingBirth);
+        assert_eq!(error("23"), RuleError::MissingBirth);
+        assert_eq!(error("B3/S29"), RuleError::InvalidNeighborCount(9));
+        assert_eq!(error("B33/S23"), RuleError::DuplicateNeighborCount(3));
+        assert_eq!(error("B3!/S23"), RuleError::UnexpectedCharacter('!'));
+    }
+
+    #[test]
+    fn reads_and_writes_generations_rules() {
+        let star_wars: Rule = "345/2/4".parse().unwrap();
+        assert_eq!(star_wars.states(), 4);
+        assert!(star_wars.births(2) && star_wars.survives(5) && !star_wars.survives(2));
+        assert_eq!(star_wars.to_string(), "345/2/4");
+        assert_eq!("B2/S345/C4".parse::<Rule>().unwrap(), star_wars);
+        assert_eq!(Rule::generations(&[2], &[3, 4, 5], 4).unwrap(), star_wars);
+
+        let error = |rulestring: &str| rulestring.parse::<Rule>().unwrap_err();
+        assert_eq!(
+            error("345/2/1"),
+            RuleError::InvalidStateCount("1".to_string())
+        );
+        assert_eq!(
+            error("345/2/257"),
+            RuleError::InvalidStateCount("257".to_string())
+        );
+        assert_eq!("345/2/256".parse::<Rule>().unwrap().states(), 256);
+    }
+
+    #[test]
+    fn generations_cells_decay_through_their_dying_states() {
+        let star_wars: Rule = "345/2/4".parse().unwrap();
+        let mut state = CellState::Alive;
+        let mut states = vec![];
+        while state != CellState::Dead {
+            let next = star_wars.next_state_by_count(state, 0);
+            assert_eq!(star_wars.previous_state(next), state);
+            states.push(next);
+            state = next;
+        }
+        assert_eq!(
+            states,
+            vec![CellState::Dying(1), CellState::Dying(2), CellState::Dead]
+        );
+
+        // Decaying cells stay on their way to dead, however many live neighbors they have
+        assert_eq!(
+            star_wars.next_state_by_count(CellState::Dying(1), 2),
+            CellState::Dying(2)
+        );
+        assert_eq!(
+            star_wars.next_state_by_count(CellState::Alive, 4),
+            CellState::Alive
+        );
+        assert_eq!(star_wars.previous_state(CellState::Alive), CellState::Dead);
+    }
+
+    #[test]
+    fn dying_cells_are_not_live_neighbors() {
+        let brians_brain: Rule = "/2/3".parse().unwrap();
+        let mut grid = NaiveGrid::new(4, 4, Topology::TORUS);
+        grid.set(Position { x: 0, y: 0 }, CellState::Alive);
+        grid.set(Position { x: 1, y: 0 }, CellState::Dying(1));
+        grid.set(Position { x: 0, y: 1 }, CellState::Alive);
+        grid.step(&brians_brain);
+
+        // Three neighbors that aren't dead, but only two live ones
+        assert_eq!(grid.get(Position { x: 1, y: 1 }), CellState::Alive);
+        assert_eq!(grid.get(Position { x: 0, y: 0 }), CellState::Dying(1));
+        assert_eq!(grid.get(Position { x: 1, y: 0 }), CellState::Dead);
+    }
+
+    #[test]
+    fn reads_and_writes_rules_in_hensel_notation() {
+        let rule: Rule = "B2-a/S12".parse().unwrap();
+        assert!(rule.birth[0b0000_0101] && !rule.birth[0b0000_0011]);
+        assert!(!rule.is_totalistic());
+        assert_eq!(rule.to_string(), "B2-a/S12");
+        assert_eq!("b2-A/s12".parse::<Rule>().unwrap(), rule);
+
+        // A count with all of its letters is the same as the count alone
+        let life: Rule = "B3cekainyqjr/S2cekain3".parse().unwrap();
+        assert_eq!(life, Rule::default());
+        assert!(life.is_totalistic());
+        assert_eq!(life.to_string(), "B3/S23");
+
+        // Counts past 4 take the letter of their dead neighbors
+        let rule: Rule = "B2e5c/S".parse().unwrap();
+        assert!(rule.birth[0b1101_1010] && !rule.birth[0b1010_0101]);
+        assert_eq!(rule.to_string(), "B2e5c/S");
+    }
+
+    #[test]
+    fn hensel_rules_treat_turned_and_mirrored_neighborhoods_alike() {
+        let rule: Rule = "B2-a3ij6k/S1e2c4-wz".parse().unwrap();
+        for neighborhood in 0..=255_u8 {
+            for symmetry in symmetries(neighborhood).iter() {
+                assert_eq!(
+                    rule.next_state(CellState::Dead, *symmetry),
+                    rule.next_state(CellState::Dead, neighborhood)
+                );
+                assert_eq!(
+                    rule.next_state(CellState::Alive, *symmetry),
+                    rule.next_state(CellState::Alive, neighborhood)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_hensel_notation() {
+        let error = |rulestring: &str| rulestring.parse::<Rule>().unwrap_err();
+        assert_eq!(error("B2x/S23"), RuleError::UnknownLetter(2, 'x'));
+        assert_eq!(error("B1k/S23"), RuleError::UnknownLetter(1, 'k'));
+        assert_eq!(error("B3/S8c"), RuleError::UnknownLetter(8, 'c'));
+        assert_eq!(error("B2-/S23"), RuleError::UnexpectedCharacter('-'));
+        assert_eq!(error("B3a3/S23"), RuleError::DuplicateNeighborCount(3));
+        assert_eq!(error("B9/S23"), RuleError::InvalidNeighborCount(9));
+    }
+
+    #[test]
+    fn reads_and_writes_larger_than_life_rules() {
+        let bosco: Rule = "R5,C0,M1,S34..58,B34..45,NM".parse().unwrap();
+        assert_eq!(
+            bosco.larger_than_life(),
+            Some(LargerThanLife {
+                radius: 5,
+                shape: Shape::Moore,
+                counts_middle: true,
+                birth: (34, 45),
+                survival: (34, 58),
+            })
+        );
+        assert_eq!(bosco.states(), 2);
+        assert_eq!(bosco.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+
+        // C, M and N can be left out, and a single count needs no range
+        let rule: Rule = "r2,c3,s2..3,b3".parse().unwrap();
+        assert_eq!(rule.states(), 3);
+        assert_eq!(rule.to_string(), "R2,C3,M0,S2..3,B3..3,NM");
+        assert_eq!(rule.to_string().parse::<Rule>().unwrap(), rule);
+    }
+
+    #[test]
+    fn neighborhoods_have_the_usual_sizes() {
+        let size = |rulestring: &str| {
+            let rule: Rule = rulestring.parse().unwrap();
+            rule.larger_than_life().unwrap().size()
+        };
+        assert_eq!(size("R1,S2..3,B3,NM"), 9);
+        assert_eq!(size("R2,S2..3,B3,NN"), 13);
+        assert_eq!(size("R2,S2..3,B3,NC"), 21);
+        assert_eq!(size("R5,S34..58,B34..45,NM"), 121);
+    }
+
+    #[test]
+    fn reads_and_writes_rules_on_other_tilings() {
+        let hexagonal: Rule = "B2/S34H".parse().unwrap();
+        assert_eq!(hexagonal.tiling(), Tiling::Hexagonal);
+        assert!(hexagonal.births(2) && !hexagonal.births(3));
+        assert!(hexagonal.survives(4) && !hexagonal.survives(2));
+        assert_eq!(hexagonal.to_string(), "B2/S34H");
+
+        // Triangles have 12 neighbors, so counts past 9 are written with commas
+        let triangular: Rule = "B4,10/S2,12L".parse().unwrap();
+        assert_eq!(triangular.tiling(), Tiling::Triangular);
+        assert!(triangular.births(10) && !triangular.births(1));
+        assert!(triangular.survives(12));
+        assert_eq!(triangular.to_string(), "B4,10/S2,12L");
+        assert_eq!("B45/S2L".parse::<Rule>().unwrap().to_string(), "B45/S2L");
+
+        let error = |rulestring: &str| rulestring.parse::<Rule>().unwrap_err();
+        assert_eq!(error("B7/S2H"), RuleError::InvalidNeighborCount(7));
+        assert_eq!(error("B4,13/S2L"), RuleError::InvalidNeighborCount(13));
+        assert_eq!(error("B4,4/S2L"), RuleError::DuplicateNeighborCount(4));
+    }
+
+    #[test]
+    fn rejects_invalid_larger_than_life_rules() {
+        let error = |rulestring: &str| rulestring.parse::<Rule>().unwrap_err();
+        assert_eq!(error("R5,C0,M1,S34..58,NM"), RuleError::MissingBirth);
+        assert_eq!(error("R5,B34..45"), RuleError::MissingSurvival);
+        assert_eq!(
+            error("R5,X1,S1,B1"),
+            RuleError::InvalidLargerThanLife("X1".to_string())
+        );
+        assert_eq!(
+            error("R5,M2,S1,B1"),
+            RuleError::InvalidLargerThanLife("M2".to_string())
+        );
+        assert_eq!(error("R501,S1,B1"), RuleError::InvalidRadius(501));
+        assert_eq!(error("R1,S2..10,B3"), RuleError::InvalidCountRange(2, 10));
+        assert_eq!(error("R1,S3..2,B3"), RuleError::InvalidCountRange(3, 2));
+    }
+}
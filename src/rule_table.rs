@@ -0,0 +1,752 @@
+//! Rule tables in Golly's .rule format, which define automata such as Wireworld by listing the transitions of a cell
+//! for the states of its neighbors:
+//!
+//! ```text
+//! @RULE WireWorldVonNeumann
+//! @TABLE
+//! n_states:4
+//! neighborhood:vonNeumann
+//! symmetries:permute
+//! var a={0,1,2,3}
+//! var b={0,1,2,3}
+//! var c={0,1,2,3}
+//! var d={0,1,2,3}
+//! var e={0,2,3}
+//! var f={0,2,3}
+//! var g={0,2,3}
+//! # Electron heads become tails, tails become wire, and wire with one or two heads next to it becomes a head
+//! 1,a,b,c,d,2
+//! 2,a,b,c,d,3
+//! 3,1,e,f,g,1
+//! 3,1,1,f,g,1
+//! @COLORS
+//! 1 0 128 255
+//! 2 255 255 255
+//! 3 255 128 0
+//! ```
+//!
+//! A transition lists the state of the cell, the states of its neighbors in clockwise order starting from the north,
+//! and the state the cell goes to. The first transition that matches wins, and cells that match none stay as they
+//! are. Variables stand for any of their states, and take the same one everywhere they appear in a transition.
+//! Symmetries make a transition match its rotations and reflections as well, or any order of the neighbors for
+//! permute. Only the @TABLE and @COLORS sections are read.
+
+use crate::rule::{RuleError, MAX_STATES};
+use crate::simulation::Position;
+
+use std::collections::{HashMap, HashSet};
+
+// Offsets of the neighbors of each neighborhood, in the order transitions list them
+const MOORE: [Position; 8] = [
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: -1 },
+    Position { x: 1, y: 0 },
+    Position { x: 1, y: 1 },
+    Position { x: 0, y: 1 },
+    Position { x: -1, y: 1 },
+    Position { x: -1, y: 0 },
+    Position { x: -1, y: -1 },
+];
+const VON_NEUMANN: [Position; 4] = [
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: 0 },
+    Position { x: 0, y: 1 },
+    Position { x: -1, y: 0 },
+];
+// The skewed square grid of the hexagonal tiling, see Tiling
+const HEXAGONAL: [Position; 6] = [
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: 0 },
+    Position { x: 1, y: 1 },
+    Position { x: 0, y: 1 },
+    Position { x: -1, y: 0 },
+    Position { x: -1, y: -1 },
+];
+const ONE_DIMENSIONAL: [Position; 2] = [Position { x: -1, y: 0 }, Position { x: 1, y: 0 }];
+
+// A cell and the neighbors of the largest neighborhood
+const MAX_INPUTS: usize = 9;
+
+/// Whether the text could be the name of a rule table, which patterns give in place of a rulestring to run on the
+/// table of that name. Names start with a letter and go on with letters, digits, dashes and underscores
+pub fn is_name(text: &str) -> bool {
+    text.starts_with(|character: char| character.is_ascii_alphabetic())
+        && text.chars().all(|character| {
+            character.is_ascii_alphanumeric() || character == '-' || character == '_'
+        })
+}
+
+/// Cells whose states a transition of a rule table looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    Moore,
+    VonNeumann,
+    Hexagonal,
+    OneDimensional,
+}
+
+impl Neighborhood {
+    /// Offsets of the neighbors, in the order transitions list them
+    pub fn offsets(self) -> &'static [Position] {
+        match self {
+            Neighborhood::Moore => &MOORE,
+            Neighborhood::VonNeumann => &VON_NEUMANN,
+            Neighborhood::Hexagonal => &HEXAGONAL,
+            Neighborhood::OneDimensional => &ONE_DIMENSIONAL,
+        }
+    }
+
+    // Neighbor each neighbor goes to when the neighborhood is mirrored from left to right
+    fn reflected(self, neighbor: usize) -> usize {
+        match self {
+            Neighborhood::OneDimensional => 1 - neighbor,
+            _ => {
+                let count = self.offsets().len();
+                (count - neighbor) % count
+            }
+        }
+    }
+}
+
+// States a cell of a transition matches, and the variable that ties it to other cells with the same variable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Input {
+    states: [u64; 4], // Bit n is set if the input matches state n
+    binding: Option<usize>,
+}
+
+impl Input {
+    fn contains(&self, state: u8) -> bool {
+        self.states[state as usize / 64] & (1 << (state % 64)) != 0
+    }
+
+    // Whether the input matches the state, binding its variable to the state if nothing bound it yet
+    fn matches(&self, state: u8, bound: &mut [Option<u8>; MAX_INPUTS]) -> bool {
+        if !self.contains(state) {
+            return false;
+        }
+        match self.binding {
+            Some(binding) => *bound[binding].get_or_insert(state) == state,
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Output {
+    State(u8),
+    Bound(usize), // State of the cells with the binding
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Transition {
+    inputs: Vec<Input>, // The cell itself, followed by its neighbors, sorted if they are permuted
+    output: Output,
+    is_permuted: bool, // Whether the neighbors match in any order
+}
+
+impl Transition {
+    // State the cell goes to if the transition matches the states of the cell and its neighbors
+    fn apply(&self, states: &[u8]) -> Option<u8> {
+        let mut bound = [None; MAX_INPUTS];
+        if !self.inputs[0].matches(states[0], &mut bound) {
+            return None;
+        }
+        let is_match = if self.is_permuted {
+            let mut neighbors = states[1..].to_vec();
+            neighbors.sort_unstable();
+            let mut is_used = [false; MAX_INPUTS];
+            matches_in_any_order(&self.inputs[1..], &neighbors, &mut is_used, &mut bound)
+        } else {
+            self.inputs[1..]
+                .iter()
+                .zip(&states[1..])
+                .all(|(input, &state)| input.matches(state, &mut bound))
+        };
+        if !is_match {
+            return None;
+        }
+
+        match self.output {
+            Output::State(state) => Some(state),
+            Output::Bound(binding) => bound[binding],
+        }
+    }
+}
+
+/// Automaton defined by a Golly rule table, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleTable {
+    name: String,
+    states: u16,
+    neighborhood: Neighborhood,
+    transitions: Vec<Transition>, // Every rotation and reflection of each transition the symmetries ask for
+    by_state: Vec<Vec<usize>>,    // by_state[n]: Transitions that match a cell in state n, in order
+    colors: Vec<Option<[u8; 3]>>, // colors[n]: Color of state n in the @COLORS section
+}
+
+impl RuleTable {
+    /// Reads the text of a .rule file
+    pub fn parse(text: &str) -> Result<RuleTable, RuleError> {
+        let mut name = String::new();
+        let mut section = "";
+        let mut table_lines = vec![];
+        let mut color_lines = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if let Some(header) = line.strip_prefix('@') {
+                let mut words = header.split_whitespace();
+                section = match words.next() {
+                    Some("RULE") => {
+                        name = words.next().unwrap_or_default().to_string();
+                        "RULE"
+                    }
+                    Some("TABLE") => "TABLE",
+                    Some("COLORS") => "COLORS",
+                    _ => "",
+                };
+            } else if !line.is_empty() {
+                match section {
+                    "TABLE" => table_lines.push((index + 1, line)),
+                    "COLORS" => color_lines.push((index + 1, line)),
+                    _ => (),
+                }
+            }
+        }
+        if table_lines.is_empty() {
+            return Err(RuleError::MissingTable);
+        }
+
+        let mut table = RuleTable::parse_table(&table_lines)?;
+        table.name = name;
+        table.parse_colors(&color_lines)?;
+        Ok(table)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn states(&self) -> u16 {
+        self.states
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    /// Color of the state given in the @COLORS section, as red, green and blue
+    pub fn color(&self, state: u8) -> Option<[u8; 3]> {
+        self.colors.get(state as usize).copied().flatten()
+    }
+
+    /// State a cell goes to, given its own state followed by those of its neighbors in the order of
+    /// [`Neighborhood::offsets`]
+    pub fn next_state(&self, states: &[u8]) -> u8 {
+        self.by_state
+            .get(states[0] as usize)
+            .and_then(|indices| {
+                indices
+                    .iter()
+                    .find_map(|&index| self.transitions[index].apply(states))
+            })
+            .unwrap_or(states[0])
+    }
+
+    // Reads the @TABLE section: the number of states, the neighborhood and the symmetries come first, and variables
+    // have to be declared before the transitions that use them
+    fn parse_table(lines: &[(usize, &str)]) -> Result<RuleTable, RuleError> {
+        let (mut states, mut neighborhood, mut symmetries) = (None, None, None);
+        let mut variables: HashMap<&str, [u64; 4]> = HashMap::new();
+        let mut table = RuleTable {
+            name: String::new(),
+            states: 0,
+            neighborhood: Neighborhood::Moore,
+            transitions: vec![],
+            by_state: vec![],
+            colors: vec![],
+        };
+
+        for &(number, line) in lines {
+            let invalid = |message: &str| RuleError::InvalidTable(number, message.to_string());
+
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim() {
+                    "n_states" => match value.parse() {
+                        Ok(count) if (2..=MAX_STATES).contains(&count) => states = Some(count),
+                        _ => return Err(RuleError::InvalidStateCount(value.to_string())),
+                    },
+                    "neighborhood" => {
+                        neighborhood = Some(match value {
+                            "Moore" => Neighborhood::Moore,
+                            "vonNeumann" => Neighborhood::VonNeumann,
+                            "hexagonal" => Neighborhood::Hexagonal,
+                            "oneDimensional" => Neighborhood::OneDimensional,
+                            _ => return Err(invalid("unknown neighborhood")),
+                        })
+                    }
+                    "symmetries" => symmetries = Some(value.to_string()),
+                    _ => return Err(invalid("unknown setting")),
+                }
+                continue;
+            }
+
+            let (states, neighborhood, symmetries) = match (states, neighborhood, &symmetries) {
+                (Some(states), Some(neighborhood), Some(symmetries)) => {
+                    (states, neighborhood, symmetries)
+                }
+                _ => {
+                    return Err(invalid(
+                        "n_states, neighborhood and symmetries have to come first",
+                    ))
+                }
+            };
+
+            if let Some(declaration) = line.strip_prefix("var ") {
+                let (name, value) = declaration
+                    .split_once('=')
+                    .ok_or_else(|| invalid("variables look like var a={0,1}"))?;
+                let set = parse_set(value.trim(), states, &variables)
+                    .ok_or_else(|| invalid("invalid set of states"))?;
+                variables.insert(name.trim(), set);
+                continue;
+            }
+
+            let transition = parse_transition(line, neighborhood, states, &variables)
+                .map_err(|message| invalid(&message))?;
+            for variant in symmetric_variants(&transition, neighborhood, symmetries)
+                .ok_or_else(|| invalid("unknown symmetries"))?
+            {
+                table.transitions.push(variant);
+            }
+        }
+
+        table.states = states.ok_or(RuleError::MissingTable)?;
+        table.neighborhood = neighborhood.unwrap_or(Neighborhood::Moore);
+        table.by_state = (0..table.states)
+            .map(|state| state as u8)
+            .map(|state| {
+                (0..table.transitions.len())
+                    .filter(|&index| table.transitions[index].inputs[0].contains(state))
+                    .collect()
+            })
+            .collect();
+        Ok(table)
+    }
+
+    // Reads the @COLORS section, where each line is a state followed by its red, green and blue. A line with two
+    // colors instead fades from the first to the second across the states after the dead one
+    fn parse_colors(&mut self, lines: &[(usize, &str)]) -> Result<(), RuleError> {
+        self.colors = vec![None; self.states as usize];
+        for &(number, line) in lines {
+            let values: Vec<u8> = line
+                .split(|character: char| character.is_whitespace() || character == ',')
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| RuleError::InvalidTable(number, "invalid color".to_string()))?;
+
+            match values[..] {
+                [state, red, green, blue] if u16::from(state) < self.states => {
+                    self.colors[state as usize] = Some([red, green, blue])
+                }
+                [red, green, blue, last_red, last_green, last_blue] => {
+                    let last = self.states as usize - 1;
+                    for state in 1..=last {
+                        let fraction = if last == 1 {
+                            0.0
+                        } else {
+                            (state - 1) as f32 / (last - 1) as f32
+                        };
+                        let mix = |from: u8, to: u8| {
+                            (from as f32 + (to as f32 - from as f32) * fraction).round() as u8
+                        };
+                        self.colors[state] = Some([
+                            mix(red, last_red),
+                            mix(green, last_green),
+                            mix(blue, last_blue),
+                        ]);
+                    }
+                }
+                _ => {
+                    return Err(RuleError::InvalidTable(
+                        number,
+                        "colors look like \"1 255 0 0\"".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// States of a set such as {0,2,a}, a single state, or a variable
+fn parse_set(text: &str, states: u16, variables: &HashMap<&str, [u64; 4]>) -> Option<[u64; 4]> {
+    let mut set = [0; 4];
+    let items = match text.strip_prefix('{') {
+        Some(text) => text.strip_suffix('}')?.split(',').collect(),
+        None => vec![text],
+    };
+
+    for item in items {
+        let item = item.trim();
+        match item.parse::<u8>() {
+            Ok(state) if u16::from(state) < states => set[state as usize / 64] |= 1 << (state % 64),
+            Ok(_) => return None,
+            Err(_) => {
+                for (words, &other) in set.iter_mut().zip(variables.get(item)?) {
+                    *words |= other;
+                }
+            }
+        }
+    }
+    Some(set)
+}
+
+// Splits a transition into its parts at the commas outside of braces. Tables with fewer than 11 states may leave the
+// commas out, as in "01111000002"
+fn split_transition(line: &str) -> Vec<String> {
+    if !line.contains(',') && !line.contains('{') {
+        return line
+            .chars()
+            .map(|character| character.to_string())
+            .collect();
+    }
+
+    let mut parts = vec![String::new()];
+    let mut depth = 0;
+    for character in line.chars() {
+        match character {
+            ',' if depth == 0 => parts.push(String::new()),
+            _ => {
+                match character {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => (),
+                }
+                parts.last_mut().unwrap().push(character);
+            }
+        }
+    }
+    parts.iter().map(|part| part.trim().to_string()).collect()
+}
+
+fn parse_transition(
+    line: &str,
+    neighborhood: Neighborhood,
+    states: u16,
+    variables: &HashMap<&str, [u64; 4]>,
+) -> Result<Transition, String> {
+    let parts = split_transition(line);
+    let expected = neighborhood.offsets().len() + 2;
+    if parts.len() != expected {
+        return Err(format!(
+            "transitions need {} states, this one has {}",
+            expected,
+            parts.len()
+        ));
+    }
+    let (output, inputs) = parts.split_last().unwrap();
+
+    // Variables that appear more than once are bound, and so is the one the output names
+    let is_variable = |part: &str| variables.contains_key(part);
+    let bindings: Vec<&str> = inputs
+        .iter()
+        .map(String::as_str)
+        .filter(|&part| is_variable(part))
+        .filter(|&part| part == output || inputs.iter().filter(|other| *other == part).count() > 1)
+        .collect();
+    let binding = |part: &str| bindings.iter().position(|&bound| bound == part);
+
+    let inputs = inputs
+        .iter()
+        .map(|part| {
+            let states = parse_set(part, states, variables)
+                .ok_or_else(|| format!("\"{}\" isn't a state or a declared variable", part))?;
+            Ok(Input {
+                states,
+                binding: binding(part),
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    let output = match output.parse::<u8>() {
+        Ok(state) if u16::from(state) < states => Output::State(state),
+        Ok(state) => return Err(format!("state {} is out of range", state)),
+        Err(_) => Output::Bound(
+            binding(output)
+                .ok_or_else(|| format!("the output \"{}\" isn't one of the inputs", output))?,
+        ),
+    };
+    Ok(Transition {
+        inputs,
+        output,
+        is_permuted: false,
+    })
+}
+
+// The transition with its neighbors rearranged in every way the symmetries allow, each arrangement once. Permuted
+// neighbors are matched in any order when the transition is applied instead, so they are only sorted
+fn symmetric_variants(
+    transition: &Transition,
+    neighborhood: Neighborhood,
+    symmetries: &str,
+) -> Option<Vec<Transition>> {
+    if symmetries == "permute" {
+        let mut inputs = transition.inputs.clone();
+        inputs[1..].sort();
+        return Some(vec![Transition {
+            inputs,
+            output: transition.output.clone(),
+            is_permuted: true,
+        }]);
+    }
+
+    // Turns by a fraction of a full rotation, optionally mirrored
+    let (turns, reflects) = match symmetries {
+        "none" => (1, false),
+        "reflect" | "reflect_horizontal" => (1, true),
+        _ => {
+            let rotation = symmetries.strip_prefix("rotate")?;
+            let (turns, reflects) = match rotation.strip_suffix("reflect") {
+                Some(turns) => (turns, true),
+                None => (rotation, false),
+            };
+            (turns.parse::<usize>().ok()?, reflects)
+        }
+    };
+    let count = neighborhood.offsets().len();
+    if turns == 0 || !count.is_multiple_of(turns) {
+        return None;
+    }
+
+    let rearrange = |order: &[usize]| -> Vec<Input> {
+        let mut inputs = vec![transition.inputs[0].clone()];
+        inputs.extend(
+            order
+                .iter()
+                .map(|&neighbor| transition.inputs[neighbor + 1].clone()),
+        );
+        inputs
+    };
+    let step = count / turns;
+    let mut arrangements = vec![];
+    for turn in 0..turns {
+        let order: Vec<usize> = (0..count)
+            .map(|neighbor| (neighbor + turn * step) % count)
+            .collect();
+        arrangements.push(rearrange(&order));
+        if reflects {
+            let order: Vec<usize> = order
+                .iter()
+                .map(|&neighbor| neighborhood.reflected(neighbor))
+                .collect();
+            arrangements.push(rearrange(&order));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    Some(
+        arrangements
+            .into_iter()
+            .filter(|inputs| seen.insert(inputs.clone()))
+            .map(|inputs| Transition {
+                inputs,
+                output: transition.output.clone(),
+                is_permuted: false,
+            })
+            .collect(),
+    )
+}
+
+// Whether the inputs match the neighbors, sorted by state, in some order, binding variables on the way. Neighbors
+// already matched to an input are marked as used
+fn matches_in_any_order(
+    inputs: &[Input],
+    neighbors: &[u8],
+    is_used: &mut [bool; MAX_INPUTS],
+    bound: &mut [Option<u8>; MAX_INPUTS],
+) -> bool {
+    let (input, rest) = match inputs.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    for (index, &state) in neighbors.iter().enumerate() {
+        // Neighbors in the same state are interchangeable, so only the first unused one of them is tried
+        if is_used[index] || (index > 0 && neighbors[index - 1] == state && !is_used[index - 1]) {
+            continue;
+        }
+        let mut tried = *bound;
+        if input.matches(state, &mut tried) {
+            is_used[index] = true;
+            if matches_in_any_order(rest, neighbors, is_used, &mut tried) {
+                *bound = tried;
+                return true;
+            }
+            is_used[index] = false;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::rule::Rule;
+    use crate::simulation::CellState;
+    use crate::sparse::SparseGrid;
+
+    const WIREWORLD: &str = "\
+@RULE WireWorld
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b=a
+var c=a
+var d=a
+var e=a
+var f=a
+var g=a
+var h=a
+var i={0,2,3}
+var j=i
+var k=i
+var l=i
+var m=i
+var n=i
+var o=i
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+3,1,i,j,k,l,m,n,o,1
+3,1,1,i,j,k,l,m,n,1
+@COLORS
+1 255 255 255
+2 0 128 255
+3 255 128 0
+";
+
+    // Generation at which an electron sent down a Wireworld diode from one end first reaches the other, if it does
+    fn electron_arrival(from_left: bool) -> Option<usize> {
+        let rule = Rule::from_table(RuleTable::parse(WIREWORLD).unwrap());
+        let mut grid = SparseGrid::new();
+        for (y, row) in ["...##.....", "####.######", "...##....."]
+            .iter()
+            .enumerate()
+        {
+            for (x, character) in row.chars().enumerate() {
+                if character == '#' {
+                    grid.set(
+                        Position {
+                            x: x as isize,
+                            y: y as isize,
+                        },
+                        CellState::Dying(2),
+                    );
+                }
+            }
+        }
+        let (head, tail, end) = if from_left { (1, 0, 10) } else { (9, 10, 0) };
+        grid.set(Position { x: head, y: 1 }, CellState::Alive);
+        grid.set(Position { x: tail, y: 1 }, CellState::Dying(1));
+
+        (1..=30).find(|_| {
+            grid.step(&rule);
+            grid.get(Position { x: end, y: 1 }) == CellState::Alive
+        })
+    }
+
+    #[test]
+    fn reads_wireworld() {
+        let table = RuleTable::parse(WIREWORLD).unwrap();
+        assert_eq!(table.name(), "WireWorld");
+        assert_eq!(table.states(), 4);
+        assert_eq!(table.neighborhood(), Neighborhood::Moore);
+        assert_eq!(table.color(2), Some([0, 128, 255]));
+        assert_eq!(table.color(0), None);
+
+        // Permuted transitions are kept once, however many orders their neighbors have
+        assert_eq!(table.transitions.len(), 4);
+        assert_eq!(table.next_state(&[3, 0, 1, 0, 0, 3, 0, 0, 1]), 1);
+        assert_eq!(table.next_state(&[3, 1, 1, 0, 0, 3, 0, 0, 1]), 3);
+        assert_eq!(table.next_state(&[1, 3, 3, 3, 3, 3, 3, 3, 3]), 2);
+        assert_eq!(table.next_state(&[0, 1, 1, 1, 1, 1, 1, 1, 1]), 0);
+    }
+
+    #[test]
+    fn electrons_pass_a_diode_one_way() {
+        assert_eq!(electron_arrival(true), Some(9));
+        assert_eq!(electron_arrival(false), None);
+    }
+
+    #[test]
+    fn permuted_variables_take_the_same_state_everywhere() {
+        let table = RuleTable::parse(
+            "@TABLE\nn_states:4\nneighborhood:vonNeumann\nsymmetries:permute\nvar a={1,2}\n0,a,a,0,0,a\n",
+        )
+        .unwrap();
+        assert_eq!(table.next_state(&[0, 2, 0, 0, 2]), 2);
+        assert_eq!(table.next_state(&[0, 0, 1, 1, 0]), 1);
+        assert_eq!(table.next_state(&[0, 1, 0, 0, 2]), 0);
+        assert_eq!(table.next_state(&[0, 1, 1, 1, 0]), 0);
+    }
+
+    #[test]
+    fn reads_tables_with_256_states() {
+        let table = RuleTable::parse(
+            "@TABLE\nn_states:256\nneighborhood:oneDimensional\nsymmetries:none\n255,0,0,0\n",
+        )
+        .unwrap();
+        assert_eq!(table.states(), 256);
+        assert_eq!(table.next_state(&[255, 0, 0]), 0);
+        assert_eq!(table.next_state(&[255, 0, 1]), 255);
+        assert_eq!(Rule::from_table(table).states(), 256);
+    }
+
+    #[test]
+    fn points_at_errors() {
+        let error = |table: &str| RuleTable::parse(&format!("@TABLE\n{}", table)).unwrap_err();
+        let invalid =
+            |line: usize, message: &str| RuleError::InvalidTable(line, message.to_string());
+        assert_eq!(
+            RuleTable::parse("@RULE Empty\n").unwrap_err(),
+            RuleError::MissingTable
+        );
+        assert_eq!(
+            error("n_states:257"),
+            RuleError::InvalidStateCount("257".to_string())
+        );
+        assert_eq!(
+            error("n_states:2\nneighborhood:Hilbert"),
+            invalid(3, "unknown neighborhood")
+        );
+        assert_eq!(
+            error("0,1,0"),
+            invalid(
+                2,
+                "n_states, neighborhood and symmetries have to come first"
+            )
+        );
+
+        let settings = "n_states:3\nneighborhood:vonNeumann\nsymmetries:rotate4\n";
+        assert_eq!(
+            error(&format!("{}0,1,2,0,1", settings)),
+            invalid(5, "transitions need 6 states, this one has 5")
+        );
+        assert_eq!(
+            error(&format!("{}0,1,2,0,b,1", settings)),
+            invalid(5, "\"b\" isn't a state or a declared variable")
+        );
+        assert_eq!(
+            error(&format!("{}0,1,2,0,1,3", settings)),
+            invalid(5, "state 3 is out of range")
+        );
+        assert_eq!(
+            error("n_states:3\nneighborhood:vonNeumann\nsymmetries:rotate3\n0,1,2,0,1,2"),
+            invalid(5, "unknown symmetries")
+        );
+    }
+}
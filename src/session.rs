@@ -0,0 +1,260 @@
+//! Sessions record how a run started, so that anyone can recreate the exact same run:
+//!
+//! ```text
+//! rule = B3/S23
+//! topology = Wrapped/Wrapped
+//! grid = 96x96
+//! seed = 8461534126743925911
+//! density = 50%
+//! symmetry = C1
+//! region = 96x96 at 0, 0
+//! generation = 1200
+//! ```
+//!
+//! The soup with the given seed, density and region is placed on the grid and run to the generation. The topology
+//! is written as its left/right and top/bottom edges, as names such as Cylinder stand for more than one.
+//! Lines starting with # are comments.
+
+use crate::rule::{Rule, RuleError};
+use crate::simulation::Position;
+use crate::soup::{Soup, Symmetry};
+use crate::topology::Topology;
+
+use std::error::Error;
+use std::fmt;
+
+const KEYS: [&str; 8] = [
+    "rule",
+    "topology",
+    "grid",
+    "seed",
+    "density",
+    "symmetry",
+    "region",
+    "generation",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub rule: Rule,
+    pub topology: Topology,
+    pub width: usize, // Size of the grid the soup was run on
+    pub height: usize,
+    pub soup: Soup,
+    pub generation: usize,
+}
+
+pub fn parse(text: &str) -> Result<Session, SessionError> {
+    let mut values: [Option<(usize, &str)>; KEYS.len()] = [None; KEYS.len()];
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut entry = line.splitn(2, '=').map(str::trim);
+        match (entry.next(), entry.next()) {
+            (Some(key), Some(value)) => match KEYS.iter().position(|&known| known == key) {
+                Some(index) => values[index] = Some((line_number, value)),
+                None => return Err(SessionError::UnknownKey(line_number, key.to_string())),
+            },
+            _ => return Err(SessionError::InvalidLine(line_number)),
+        }
+    }
+
+    let value = |key: &'static str| {
+        let index = KEYS.iter().position(|&known| known == key).unwrap();
+        values[index].ok_or(SessionError::MissingKey(key))
+    };
+    let invalid = |line_number, key| SessionError::InvalidValue(line_number, key);
+
+    let (line_number, rule) = value("rule")?;
+    let rule = rule
+        .parse()
+        .map_err(|error| SessionError::InvalidRule(line_number, error))?;
+
+    let (line_number, topology) = value("topology")?;
+    let topology = Topology::from_name(topology).ok_or_else(|| invalid(line_number, "topology"))?;
+
+    let (line_number, grid) = value("grid")?;
+    let (width, height) = parse_size(grid).ok_or_else(|| invalid(line_number, "grid"))?;
+
+    let (line_number, seed) = value("seed")?;
+    let seed = seed.parse().map_err(|_| invalid(line_number, "seed"))?;
+
+    let (line_number, density) = value("density")?;
+    let density = match density.trim_end_matches('%').trim().parse() {
+        Ok(density) if density <= 100 => density,
+        _ => return Err(invalid(line_number, "density")),
+    };
+
+    // Sessions from before symmetric soups have no symmetry
+    let symmetry = match value("symmetry") {
+        Ok((line_number, symmetry)) => {
+            Symmetry::from_name(symmetry).ok_or_else(|| invalid(line_number, "symmetry"))?
+        }
+        Err(_) => Symmetry::C1,
+    };
+
+    let (line_number, region) = value("region")?;
+    let (origin, region_width, region_height) =
+        parse_region(region).ok_or_else(|| invalid(line_number, "region"))?;
+
+    let (line_number, generation) = value("generation")?;
+    let generation = generation
+        .parse()
+        .map_err(|_| invalid(line_number, "generation"))?;
+
+    Ok(Session {
+        rule,
+        topology,
+        width,
+        height,
+        soup: Soup {
+            seed,
+            density,
+            symmetry,
+            origin,
+            width: region_width,
+            height: region_height,
+        },
+        generation,
+    })
+}
+
+pub fn write(session: &Session) -> String {
+    let soup = &session.soup;
+    format!(
+        "rule = {}\ntopology = {}/{}\ngrid = {}x{}\nseed = {}\ndensity = {}%\nsymmetry = {}\nregion = {}x{} at {}, {}\ngeneration = {}\n",
+        session.rule,
+        session.topology.horizontal,
+        session.topology.vertical,
+        session.width,
+        session.height,
+        soup.seed,
+        soup.density,
+        soup.symmetry,
+        soup.width,
+        soup.height,
+        soup.origin.x,
+        soup.origin.y,
+        session.generation
+    )
+}
+
+// Width and height written as "96x64"
+fn parse_size(text: &str) -> Option<(usize, usize)> {
+    let mut parts = text.split('x').map(|part| part.trim().parse().ok());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(width)), Some(Some(height)), None) => Some((width, height)),
+        _ => None,
+    }
+}
+
+// Size and top left corner written as "96x64 at -10, 20"
+fn parse_region(text: &str) -> Option<(Position, usize, usize)> {
+    let mut parts = text.splitn(2, " at ");
+    let (width, height) = parse_size(parts.next()?)?;
+    let mut coordinates = parts
+        .next()?
+        .split(',')
+        .map(|part| part.trim().parse().ok());
+    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+        (Some(Some(x)), Some(Some(y)), None) => Some((Position { x, y }, width, height)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    InvalidLine(usize), // Number of a line that isn't "key = value"
+    UnknownKey(usize, String),
+    InvalidValue(usize, &'static str),
+    InvalidRule(usize, RuleError),
+    MissingKey(&'static str),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::InvalidLine(line) => {
+                write!(f, "Line {}: Expected \"key = value\"", line)
+            }
+            SessionError::UnknownKey(line, key) => {
+                write!(f, "Line {}: Unknown key \"{}\"", line, key)
+            }
+            SessionError::InvalidValue(line, key) => write!(f, "Line {}: Invalid {}", line, key),
+            SessionError::InvalidRule(line, error) => {
+                write!(f, "Line {}: Invalid rule: {}", line, error)
+            }
+            SessionError::MissingKey(key) => write!(f, "Session has no {}", key),
+        }
+    }
+}
+
+impl Error for SessionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Edge;
+
+    const SESSION: &str = "rule = B3/S23\ntopology = Wrapped/Wrapped\ngrid = 96x64\nseed = 42\n\
+        density = 50%\nsymmetry = C1\nregion = 96x64 at 0, 0\ngeneration = 1200\n";
+
+    #[test]
+    fn writes_what_it_reads() {
+        let session = parse(SESSION).unwrap();
+        assert_eq!(session.topology, Topology::TORUS);
+        assert_eq!((session.width, session.height), (96, 64));
+        assert_eq!(session.soup.seed, 42);
+        assert_eq!(session.generation, 1200);
+        assert_eq!(write(&session), SESSION);
+    }
+
+    #[test]
+    fn keeps_the_topology_apart_from_its_mirror_image() {
+        let edges = [Edge::Dead, Edge::Wrapped, Edge::Twisted];
+        for &horizontal in &edges {
+            for &vertical in &edges {
+                let topology = Topology {
+                    horizontal,
+                    vertical,
+                };
+                let session = Session {
+                    topology,
+                    ..parse(SESSION).unwrap()
+                };
+                assert_eq!(parse(&write(&session)).unwrap().topology, topology);
+            }
+        }
+
+        // Names still work, as in sessions from before the edges were written
+        let text = SESSION.replace("Wrapped/Wrapped", "Klein bottle");
+        assert!(parse(&text).is_ok());
+    }
+
+    #[test]
+    fn points_at_invalid_lines() {
+        let text = SESSION.replace("seed = 42", "seed = forty-two");
+        assert_eq!(
+            parse(&text).unwrap_err(),
+            SessionError::InvalidValue(4, "seed")
+        );
+        let text = SESSION.replace("density = 50%", "density = 150%");
+        assert_eq!(
+            parse(&text).unwrap_err(),
+            SessionError::InvalidValue(5, "density")
+        );
+        let text = SESSION.replace("generation = 1200\n", "");
+        assert_eq!(
+            parse(&text).unwrap_err(),
+            SessionError::MissingKey("generation")
+        );
+        assert_eq!(
+            parse("rule = B3/S23\nspeed = 10\n").unwrap_err(),
+            SessionError::UnknownKey(2, "speed".to_string())
+        );
+    }
+}
@@ -0,0 +1,938 @@
+use crate::census::Census;
+use crate::cycle::{Cycle, CycleDetector, Spaceship, SpaceshipDetector};
+use crate::engine::{Engine, EngineKind};
+use crate::hashlife::HashLife;
+use crate::history::History;
+use crate::macrocell::Macrocell;
+use crate::rule::Rule;
+use crate::soup::Soup;
+use crate::topology::{Edge, Topology};
+use crate::turmite::{Ant, Colony, Turmite};
+use crate::util;
+
+use anyhow::{bail, Result};
+
+use std::collections::HashMap;
+use std::ops::{Add, Mul};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Add for Position {
+    type Output = Self;
+
+    fn add(self, rhs: Position) -> Self {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+impl Mul<usize> for Position {
+    type Output = Self;
+
+    fn mul(self, rhs: usize) -> Self {
+        Self {
+            x: self.x * rhs as isize,
+            y: self.y * rhs as isize,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CellState {
+    Alive,
+    Dead,
+    Dying(u8), // Decay state of Generations rules, counting up from 1 in the generation after the cell stopped living
+}
+
+impl CellState {
+    /// State with the number Golly gives it: 0 is dead, 1 alive, and the dying states follow
+    pub fn from_index(index: u8) -> CellState {
+        match index {
+            0 => CellState::Dead,
+            1 => CellState::Alive,
+            age => CellState::Dying(age - 1),
+        }
+    }
+
+    /// Number Golly gives the state, see [`from_index`](CellState::from_index)
+    pub fn index(self) -> u8 {
+        match self {
+            CellState::Dead => 0,
+            CellState::Alive => 1,
+            CellState::Dying(age) => age + 1,
+        }
+    }
+
+    /// State a click on the cell switches it to. Dying cells come back to life
+    pub fn toggled(self) -> CellState {
+        match self {
+            CellState::Alive => CellState::Dead,
+            CellState::Dead | CellState::Dying(_) => CellState::Alive,
+        }
+    }
+
+    /// Next state when clicking through all states of a rule with this many, which goes back to dead after the last
+    pub fn cycled(self, states: u16) -> CellState {
+        CellState::from_index(((u16::from(self.index()) + 1) % states) as u8)
+    }
+}
+
+#[derive(Debug)]
+pub struct Cell {
+    pub position: Position, // Top left corner position
+    pub state: CellState,
+}
+
+impl Cell {
+    pub fn new(state: CellState, position: Position) -> Cell {
+        Cell { state, position }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    CellTransitions(Vec<(Position, CellState)>),
+    SetGeneration(usize, Vec<(Position, CellState)>), // Transitions that take the grid to the given generation
+    EvolutionRateChange(u128),
+    TogglePlay,
+    Evolve(usize),
+    JumpTo(usize),
+    RuleChange(Rule),
+    EngineChange(EngineKind),
+    TopologyChange(Topology),
+    ThreadCountChange(usize),
+    EditCells(Vec<(Position, CellState)>), // Cells the user drew on the canvas
+    LoadPattern(Vec<(Position, CellState)>), // Cells that replace the whole grid
+    LoadMacrocell(Macrocell, Position, usize, usize), // Quadtree on unbounded HashLife, and the window shown
+    ShowWindow(Position, usize, usize), // Window the UI moved to, which it needs the cells of after a macrocell load
+    Reset(usize), // Empties the UI's grid at generation 0, with the number of live cells there really are
+    Window(Position, usize, usize, Vec<(Position, CellState)>), // Live cells in a window of a loaded macrocell
+    NewSoup(Soup),                // Random soup that replaces the whole grid
+    StepBack,                     // Undoes the last generation or edit
+    SeekTo(usize),                // Goes back or forward through the history to the generation
+    HistoryRange(usize, usize),   // Oldest and newest generation the history can go to
+    PauseOnCycleChange(bool), // Whether to pause once the pattern becomes still, periodic or a spaceship
+    Cycle(Option<Cycle>), // Cycle the pattern is in, or None once it changes some other way than by the rule
+    Spaceship(Option<Spaceship>), // Same for a pattern that repeats in a different place
+    TakeCensus,
+    Census(Census), // Objects on the grid, sent when asked for and once the pattern settles into a cycle
+    AddAnt(Turmite, Ant), // Switches to moving ants instead of applying the rule, all following the latest turmite
+    RemoveAnts,           // Goes back to applying the rule
+    AntStepsChange(usize), // Turns all ants take per update
+    Ants(Vec<(Position, CellState)>, Vec<Ant>, usize), // Cells the ants painted, where they are and the steps so far
+}
+
+/// Settings a simulation starts out with
+pub struct Config {
+    pub width: usize,
+    pub height: usize,
+    pub target_refresh_rate: u64,
+    pub evolution_rate: u128, // evolutions/(100s)
+    pub is_paused: bool,
+    pub rule: Rule,
+    pub engine: EngineKind,
+    pub topology: Topology,
+    pub threads: usize,        // Threads that work on each generation
+    pub soup: Soup, // Cells the grid starts out with. Those outside a bounded grid are left out
+    pub history_budget: usize, // Bytes of memory the history can take up for going back in time
+    pub pause_on_cycle: bool, // Pauses once the pattern becomes still, periodic or a spaceship
+}
+
+// Cells that changed state, along with the states they changed from
+type Changes = (Vec<(Position, CellState)>, Vec<CellState>);
+
+pub struct Simulation {
+    engine: Box<dyn Engine>,
+    engine_kind: EngineKind,
+    width: usize,
+    height: usize,
+    topology: Topology,
+    threads: usize,
+    generation: usize,
+    evolution_rate: u128,
+    evolution_count: u128,
+    ui: util::ThreadChannel<Message>,
+    clock: Instant,
+    frame_count: u128,
+    target_refresh_rate: u128,
+    is_paused: bool,
+    rule: Rule,
+    history: History,
+    history_range: (usize, usize), // Range the UI was last told about
+    cycles: CycleDetector,
+    spaceships: SpaceshipDetector,
+    pause_on_cycle: bool,
+    colony: Option<Colony>, // Ants that move in place of the rule
+    is_partial: bool, // Neither the UI nor the spaceship detector know all cells, as they came from a large macrocell
+    ant_steps: usize, // Turns the ants take per update
+}
+
+impl Simulation {
+    pub fn new(ui: util::ThreadChannel<Message>, config: Config) -> Result<Simulation> {
+        let Config {
+            width,
+            height,
+            target_refresh_rate,
+            evolution_rate,
+            is_paused,
+            rule,
+            engine: engine_kind,
+            topology,
+            threads,
+            soup,
+            history_budget,
+            pause_on_cycle,
+        } = config;
+        if !engine_kind.supports_rule(&rule) {
+            bail!("The {} engine can't run {}", engine_kind, rule);
+        }
+        let mut engine = engine_kind.create(width, height, topology)?;
+        engine.set_threads(threads);
+
+        let (grid_width, grid_height) = (width as isize, height as isize);
+        let live_cells: Vec<Position> = soup
+            .live_cells()
+            .into_iter()
+            .filter(|position| {
+                !engine_kind.is_bounded()
+                    || (0..grid_width).contains(&position.x)
+                        && (0..grid_height).contains(&position.y)
+            })
+            .collect();
+
+        for &position in &live_cells {
+            engine.set(position, CellState::Alive);
+        }
+
+        // Set live cells in the UI
+        let live_cells: Vec<(Position, CellState)> = live_cells
+            .into_iter()
+            .map(|position| (position, CellState::Alive))
+            .collect();
+        let previous_states = vec![CellState::Dead; live_cells.len()];
+        let mut cycles = CycleDetector::new();
+        cycles.restart(0, &live_cells, &previous_states);
+        let mut spaceships = SpaceshipDetector::new();
+        spaceships.restart(0, &live_cells, &previous_states);
+        ui.send(Message::SetGeneration(0, live_cells));
+
+        Ok(Simulation {
+            engine,
+            engine_kind,
+            width,
+            height,
+            topology,
+            threads,
+            generation: 0,
+            evolution_rate,
+            evolution_count: 0,
+            ui,
+            clock: Instant::now(),
+            target_refresh_rate: target_refresh_rate.into(),
+            frame_count: 0,
+            is_paused,
+            rule,
+            history: History::new(history_budget),
+            history_range: (0, 0),
+            cycles,
+            spaceships,
+            pause_on_cycle,
+            colony: None,
+            ant_steps: 1,
+            is_partial: false,
+        })
+    }
+
+    pub fn run(&mut self) {
+        // Check for messages
+        // Update
+        loop {
+            // Check for messages
+            for message in self.ui.receive() {
+                self.handle(message);
+            }
+
+            // Advance simulation
+            let clock = self.clock.elapsed().as_millis();
+            if 10_000 * (self.evolution_count + 1) <= clock * self.evolution_rate {
+                if !self.is_paused {
+                    self.update();
+                    self.report_history();
+                }
+                self.evolution_count += 1;
+            }
+            self.sleep_remaining_frame();
+        }
+    }
+
+    /// Carries out a message from the UI, sending back any cells that change
+    pub fn handle(&mut self, message: Message) {
+        match message {
+            Message::EvolutionRateChange(rate) => {
+                self.evolution_rate = rate;
+                self.evolution_count = 0;
+                self.clock = Instant::now();
+                self.frame_count = 0;
+            }
+            Message::TogglePlay => self.is_paused = !self.is_paused,
+            Message::RuleChange(rule) => {
+                // The UI moves to an engine that can run the rule beforehand, but if it didn't, the cells move over to
+                // one here. Rules with B0 can't be run on an unbounded plane, so they are left out
+                let (mut transitions, mut previous_states) = (vec![], vec![]);
+                if !self.engine_kind.supports_rule(&rule) {
+                    let engine_kind = if self.engine_kind.is_bounded() {
+                        EngineKind::Naive
+                    } else {
+                        EngineKind::Sparse
+                    };
+                    if !engine_kind.supports_rule(&rule) {
+                        return;
+                    }
+                    match self.change_engine(engine_kind, self.topology) {
+                        Ok(changes) => (transitions, previous_states) = changes,
+                        Err(_) => return,
+                    }
+                }
+
+                // The same cells can evolve differently under the new rule
+                self.rule = rule;
+                let (dropped, dropped_states) = self.drop_extra_states();
+                transitions.extend(dropped);
+                previous_states.extend(dropped_states);
+                self.history.record(
+                    self.generation,
+                    self.generation,
+                    &transitions,
+                    &previous_states,
+                );
+                self.set_generation(transitions, previous_states);
+            }
+            Message::EngineChange(engine_kind) => {
+                // The UI only offers engines that work with the grid size, topology and rule
+                if let Ok((transitions, previous_states)) =
+                    self.change_engine(engine_kind, self.topology)
+                {
+                    self.history.record(
+                        self.generation,
+                        self.generation,
+                        &transitions,
+                        &previous_states,
+                    );
+                    self.set_generation(transitions, previous_states);
+                }
+            }
+            Message::TopologyChange(topology) => {
+                if let Ok((transitions, previous_states)) =
+                    self.change_engine(self.engine_kind, topology)
+                {
+                    self.history.record(
+                        self.generation,
+                        self.generation,
+                        &transitions,
+                        &previous_states,
+                    );
+                    self.set_generation(transitions, previous_states);
+                }
+            }
+            Message::ThreadCountChange(threads) => {
+                self.threads = threads;
+                self.engine.set_threads(threads);
+            }
+            Message::EditCells(cells) => {
+                let (transitions, previous_states) = self.edit_cells(cells);
+                self.history.record(
+                    self.generation,
+                    self.generation,
+                    &transitions,
+                    &previous_states,
+                );
+                self.set_generation(transitions, previous_states);
+            }
+            Message::LoadPattern(cells) => {
+                if let Ok((transitions, previous_states)) = self.load(cells) {
+                    self.set_generation(transitions, previous_states);
+                }
+            }
+            Message::NewSoup(soup) => {
+                let cells = soup
+                    .live_cells()
+                    .into_iter()
+                    .map(|position| (position, CellState::Alive))
+                    .collect();
+                if let Ok((transitions, previous_states)) = self.load(cells) {
+                    self.set_generation(transitions, previous_states);
+                }
+            }
+            Message::LoadMacrocell(macrocell, corner, width, height) => {
+                // The UI only sends macrocells HashLife can run
+                if let Ok(engine) = HashLife::from_macrocell(&macrocell) {
+                    self.load_macrocell(engine);
+                    self.send_window(corner, width, height);
+                }
+            }
+            Message::ShowWindow(corner, width, height) => self.send_window(corner, width, height),
+            Message::Evolve(generations) => {
+                for _i in 0..generations {
+                    self.update();
+                }
+            }
+            Message::JumpTo(generation)
+                if generation > self.generation && self.colony.is_none() =>
+            {
+                let (transitions, previous_states) = self.advance(generation - self.generation);
+                self.history
+                    .record(self.generation, generation, &transitions, &previous_states);
+                self.generation = generation;
+                self.set_generation(transitions, previous_states);
+            }
+            Message::StepBack => {
+                if let Some(change) = self.history.undo() {
+                    let transitions = change.reversed();
+                    let previous_states = change.states();
+                    self.generation = change.from;
+                    for &(position, state) in &transitions {
+                        self.engine.set(position, state);
+                    }
+                    self.set_generation(transitions, previous_states);
+                }
+            }
+            Message::SeekTo(generation) => {
+                let (transitions, previous_states) = self.seek(generation);
+                self.set_generation(transitions, previous_states);
+            }
+            Message::PauseOnCycleChange(pause_on_cycle) => self.pause_on_cycle = pause_on_cycle,
+            Message::TakeCensus => self.take_census(),
+            Message::AddAnt(turmite, ant) => {
+                self.history.clear();
+                let colony = self
+                    .colony
+                    .get_or_insert_with(|| Colony::new(turmite.clone()));
+                colony.turmite = turmite;
+                colony.ants.push(ant);
+                self.report_ants(vec![]);
+            }
+            Message::RemoveAnts => {
+                // Colors the ants painted that the rule has no state for die off
+                self.colony = None;
+                self.ui.send(Message::Ants(vec![], vec![], 0));
+                let (transitions, previous_states) = self.drop_extra_states();
+                self.history.record(
+                    self.generation,
+                    self.generation,
+                    &transitions,
+                    &previous_states,
+                );
+                self.set_generation(transitions, previous_states);
+            }
+            Message::AntStepsChange(steps) => self.ant_steps = steps,
+            _ => (),
+        }
+        self.report_history();
+    }
+
+    // Undoes or redoes changes until the grid is at the generation, or as close as the history allows.
+    // Edits made in the generation are kept. Returns the cells that changed state
+    fn seek(&mut self, generation: usize) -> Changes {
+        let mut original_states = HashMap::new();
+        loop {
+            let (transitions, previous_states) = if self
+                .history
+                .last_change()
+                .is_some_and(|change| change.to > generation)
+            {
+                let change = self.history.undo().unwrap();
+                self.generation = change.from;
+                (change.reversed(), change.states())
+            } else if self
+                .history
+                .next_change()
+                .is_some_and(|change| change.to <= generation)
+            {
+                let change = self.history.redo().unwrap();
+                self.generation = change.to;
+                (change.transitions.clone(), change.previous_states.clone())
+            } else {
+                break;
+            };
+
+            for ((position, state), previous_state) in transitions.into_iter().zip(previous_states)
+            {
+                // A cell's first transition tells which state it started out in
+                original_states.entry(position).or_insert(previous_state);
+                self.engine.set(position, state);
+            }
+        }
+
+        let mut changes: Vec<(Position, CellState, CellState)> = original_states
+            .into_iter()
+            .map(|(position, original_state)| (position, self.engine.get(position), original_state))
+            .filter(|(_, state, original_state)| state != original_state)
+            .collect();
+        changes.sort_by_key(|(position, _, _)| (position.y, position.x));
+        changes
+            .into_iter()
+            .map(|(position, state, original_state)| ((position, state), original_state))
+            .unzip()
+    }
+
+    // Advances the grid by several generations. Returns the cells that changed state, and the states they changed from
+    fn advance(&mut self, generations: usize) -> Changes {
+        // Without dying states, a changed cell was in the other state before, no matter how many generations passed
+        if self.rule.states() == 2 {
+            let transitions = self.engine.advance(&self.rule, generations);
+            let previous_states = transitions
+                .iter()
+                .map(|&(_, state)| state.toggled())
+                .collect();
+            return (transitions, previous_states);
+        }
+
+        let mut original_states = HashMap::new();
+        for _ in 0..generations {
+            let (transitions, previous_states) = self.engine.step_with_previous_states(&self.rule);
+            for ((position, _), previous_state) in transitions.into_iter().zip(previous_states) {
+                original_states.entry(position).or_insert(previous_state);
+            }
+        }
+
+        let mut changes: Vec<(Position, CellState, CellState)> = original_states
+            .into_iter()
+            .map(|(position, original_state)| (position, self.engine.get(position), original_state))
+            .filter(|(_, state, original_state)| state != original_state)
+            .collect();
+        changes.sort_by_key(|(position, _, _)| (position.y, position.x));
+        changes
+            .into_iter()
+            .map(|(position, state, original_state)| ((position, state), original_state))
+            .unzip()
+    }
+
+    // Kills cells in dying states the rule doesn't have, after a change to a rule with fewer states. Ants keep the
+    // colors they paint
+    fn drop_extra_states(&mut self) -> Changes {
+        let ant_colors = self
+            .colony
+            .as_ref()
+            .map_or(2, |colony| colony.turmite.colors());
+        let states = self.rule.states().max(u16::from(ant_colors));
+        let mut transitions = vec![];
+        let mut previous_states = vec![];
+        for (position, state) in self.engine.cells() {
+            if let CellState::Dying(age) = state {
+                if age as usize + 2 > states as usize {
+                    self.engine.set(position, CellState::Dead);
+                    transitions.push((position, CellState::Dead));
+                    previous_states.push(state);
+                }
+            }
+        }
+        (transitions, previous_states)
+    }
+
+    // Sends the UI cells that changed some other way than by the rule, after which cycles are looked for anew
+    fn set_generation(
+        &mut self,
+        transitions: Vec<(Position, CellState)>,
+        previous_states: Vec<CellState>,
+    ) {
+        self.restart_detectors(&transitions, &previous_states);
+        self.ui
+            .send(Message::SetGeneration(self.generation, transitions));
+    }
+
+    fn restart_detectors(
+        &mut self,
+        transitions: &[(Position, CellState)],
+        previous_states: &[CellState],
+    ) {
+        let had_cycle = self.cycles.cycle().is_some();
+        let had_spaceship = self.spaceships.spaceship().is_some();
+        self.cycles
+            .restart(self.generation, transitions, previous_states);
+        self.spaceships
+            .restart(self.generation, transitions, previous_states);
+        if had_cycle {
+            self.ui.send(Message::Cycle(None));
+        }
+        if had_spaceship {
+            self.ui.send(Message::Spaceship(None));
+        }
+    }
+
+    // Objects can lie across wrapped edges, so the cells are moved around first such that the edges run between two
+    // empty columns or rows, where there are any
+    fn take_census(&mut self) {
+        let mut live_cells = self.engine.live_cells();
+        if self.engine_kind.is_bounded() {
+            if self.topology.horizontal == Edge::Wrapped {
+                let xs: Vec<isize> = live_cells.iter().map(|position| position.x).collect();
+                let shift = seam_shift(&xs, self.width);
+                for position in &mut live_cells {
+                    position.x = (position.x - shift).rem_euclid(self.width as isize);
+                }
+            }
+            if self.topology.vertical == Edge::Wrapped {
+                let ys: Vec<isize> = live_cells.iter().map(|position| position.y).collect();
+                let shift = seam_shift(&ys, self.height);
+                for position in &mut live_cells {
+                    position.y = (position.y - shift).rem_euclid(self.height as isize);
+                }
+            }
+        }
+
+        let census = Census::take(&live_cells, self.rule.clone());
+        self.ui.send(Message::Census(census));
+    }
+
+    // Tells the UI how far the history reaches, if that changed
+    fn report_history(&mut self) {
+        let range = self.history.range(self.generation);
+        if range != self.history_range {
+            self.history_range = range;
+            self.ui.send(Message::HistoryRange(range.0, range.1));
+        }
+    }
+
+    // Moves the grid over to a new engine. Returns the cells that are lost because they lie outside a bounded grid,
+    // and the states they were in
+    fn change_engine(&mut self, engine_kind: EngineKind, topology: Topology) -> Result<Changes> {
+        let mut engine = engine_kind.create(self.width, self.height, topology)?;
+        engine.set_threads(self.threads);
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        let mut transitions = vec![];
+        let mut previous_states = vec![];
+        for (position, state) in self.engine.cells() {
+            let is_inside = (0..width).contains(&position.x) && (0..height).contains(&position.y);
+            if is_inside || !engine_kind.is_bounded() {
+                engine.set(position, state);
+            } else {
+                transitions.push((position, CellState::Dead));
+                previous_states.push(state);
+            }
+        }
+
+        self.engine = engine;
+        self.engine_kind = engine_kind;
+        self.topology = topology;
+        if let Some(colony) = &mut self.colony {
+            if engine_kind.is_bounded() {
+                colony.ants.retain(|ant| {
+                    (0..width).contains(&ant.position.x) && (0..height).contains(&ant.position.y)
+                });
+            }
+        }
+        self.report_ants(vec![]);
+        Ok((transitions, previous_states))
+    }
+
+    // Replaces all cells by the given ones, ignoring those outside a bounded grid, and starts over at generation 0.
+    // Returns the cells that changed state, and the states they changed from
+    fn load(&mut self, cells: Vec<(Position, CellState)>) -> Result<Changes> {
+        let mut engine = self
+            .engine_kind
+            .create(self.width, self.height, self.topology)?;
+        engine.set_threads(self.threads);
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        for (position, state) in cells {
+            let is_inside = (0..width).contains(&position.x) && (0..height).contains(&position.y);
+            if is_inside || !self.engine_kind.is_bounded() {
+                engine.set(position, state);
+            }
+        }
+
+        Ok(self.replace_engine(engine))
+    }
+
+    // Swaps in an engine holding generation 0 of a new pattern. Returns the cells that changed state, and the states
+    // they changed from
+    fn replace_engine(&mut self, engine: Box<dyn Engine>) -> Changes {
+        // Listing the cells of a loaded macrocell could take forever, so everything starts over from an empty grid
+        let old_cells: HashMap<Position, CellState> = if self.is_partial {
+            self.is_partial = false;
+            self.cycles = CycleDetector::new();
+            self.spaceships = SpaceshipDetector::new();
+            self.ui.send(Message::Reset(0));
+            HashMap::new()
+        } else {
+            self.engine.cells().into_iter().collect()
+        };
+        let new_cells: HashMap<Position, CellState> = engine.cells().into_iter().collect();
+
+        let mut changes: Vec<(Position, CellState, CellState)> = old_cells
+            .iter()
+            .filter(|(position, _)| !new_cells.contains_key(position))
+            .map(|(&position, &old_state)| (position, CellState::Dead, old_state))
+            .chain(new_cells.iter().filter_map(|(&position, &state)| {
+                let old_state = old_cells.get(&position).copied().unwrap_or(CellState::Dead);
+                (state != old_state).then_some((position, state, old_state))
+            }))
+            .collect();
+        changes.sort_by_key(|(position, _, _)| (position.y, position.x));
+
+        self.engine = engine;
+        self.generation = 0;
+        self.history.clear();
+        changes
+            .into_iter()
+            .map(|(position, state, old_state)| ((position, state), old_state))
+            .unzip()
+    }
+
+    // Moves to unbounded HashLife holding a macrocell's quadtree at generation 0. Its cells are never listed, so the UI
+    // only gets the population and the cells in the window it shows, and cycles are looked for by hashing the changes
+    // since the load. Spaceships can't be told apart without knowing where all cells are, so they are no longer looked
+    // for until the next load
+    fn load_macrocell(&mut self, engine: HashLife) {
+        self.engine = Box::new(engine);
+        self.engine_kind = EngineKind::UnboundedHashLife;
+        self.generation = 0;
+        self.history.clear();
+        self.is_partial = true;
+        self.cycles = CycleDetector::new();
+        self.cycles.restart(0, &[], &[]);
+        self.spaceships = SpaceshipDetector::new();
+        self.ui.send(Message::Reset(self.engine.population()));
+    }
+
+    fn send_window(&mut self, corner: Position, width: usize, height: usize) {
+        let cells = self.engine.cells_in(corner, width, height);
+        self.ui.send(Message::Window(corner, width, height, cells));
+    }
+
+    // Sets the cells and returns those that actually changed state, along with the states they changed from.
+    // Cells outside a bounded grid are ignored
+    fn edit_cells(&mut self, cells: Vec<(Position, CellState)>) -> Changes {
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        let mut transitions = vec![];
+        let mut previous_states = vec![];
+        for (position, state) in cells {
+            let is_inside = (0..width).contains(&position.x) && (0..height).contains(&position.y);
+            if !is_inside && self.engine_kind.is_bounded() {
+                continue;
+            }
+            let previous_state = self.engine.get(position);
+            if previous_state != state {
+                self.engine.set(position, state);
+                transitions.push((position, state));
+                previous_states.push(previous_state);
+            }
+        }
+        (transitions, previous_states)
+    }
+
+    // Computes the next generation and sends its transitions, along with the cycle or spaceship the pattern just
+    // turned out to be
+    fn update(&mut self) {
+        if self.colony.is_some() {
+            return self.move_ants();
+        }
+
+        let (transitions, previous_states) = self.engine.step_with_previous_states(&self.rule);
+        self.history.record(
+            self.generation,
+            self.generation + 1,
+            &transitions,
+            &previous_states,
+        );
+        self.generation += 1;
+        let cycle = self
+            .cycles
+            .step(self.generation, &transitions, &previous_states);
+        let spaceship = if self.is_partial {
+            None
+        } else {
+            self.spaceships
+                .step(self.generation, &transitions, &previous_states)
+        };
+        self.ui.send(Message::CellTransitions(transitions));
+
+        if let Some(cycle) = cycle {
+            self.ui.send(Message::Cycle(Some(cycle)));
+            self.take_census();
+        }
+        if let Some(spaceship) = spaceship {
+            self.ui.send(Message::Spaceship(Some(spaceship)));
+        }
+        if self.pause_on_cycle && (cycle.is_some() || spaceship.is_some()) {
+            self.is_paused = true;
+        }
+    }
+
+    // Ants paint cells some other way than by the rule, so the generation stays the same and cycles are looked for
+    // anew. The history is dropped, as it can't take the ants back to where they were
+    fn move_ants(&mut self) {
+        self.history.clear();
+        let bounds = if self.engine_kind.is_bounded() {
+            Some((self.topology, self.width, self.height))
+        } else {
+            None
+        };
+        let colony = self.colony.as_mut().unwrap();
+        let (transitions, previous_states) =
+            colony.advance(self.engine.as_mut(), bounds, self.ant_steps);
+        self.restart_detectors(&transitions, &previous_states);
+        self.report_ants(transitions);
+    }
+
+    // Once the last ant walked off the grid, the rule takes over again
+    fn report_ants(&mut self, transitions: Vec<(Position, CellState)>) {
+        if let Some(colony) = &self.colony {
+            self.ui.send(Message::Ants(
+                transitions,
+                colony.ants.clone(),
+                colony.steps,
+            ));
+            if colony.ants.is_empty() {
+                self.colony = None;
+            }
+        }
+    }
+
+    fn sleep_remaining_frame(&mut self) {
+        self.frame_count += 1;
+        let time_delta = ((self.frame_count * 1000) / self.target_refresh_rate) as i128
+            - self.clock.elapsed().as_micros() as i128;
+
+        if time_delta > 0 {
+            thread::sleep(Duration::from_micros(time_delta as u64));
+        }
+    }
+}
+
+// Amount to move coordinates back by so that the wrapped edge lies between two empty lines, or 0 if there are no two
+// empty lines next to each other
+fn seam_shift(coordinates: &[isize], size: usize) -> isize {
+    let mut is_empty = vec![true; size];
+    for &coordinate in coordinates {
+        is_empty[coordinate as usize] = false;
+    }
+    (0..size)
+        .find(|&line| is_empty[line] && is_empty[(line + 1) % size])
+        .map_or(0, |line| ((line + 1) % size) as isize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soup::Symmetry;
+    use crate::turmite::Heading;
+
+    fn simulation(rule: &str, engine: EngineKind) -> (Simulation, util::ThreadChannel<Message>) {
+        let (ui, backend) = util::ThreadChannel::new_pair();
+        let config = Config {
+            width: 16,
+            height: 16,
+            target_refresh_rate: 60,
+            evolution_rate: 100,
+            is_paused: true,
+            rule: rule.parse().unwrap(),
+            engine,
+            topology: Topology::TORUS,
+            threads: 1,
+            soup: Soup {
+                seed: 1,
+                density: 40,
+                symmetry: Symmetry::C1,
+                origin: Position { x: 0, y: 0 },
+                width: 16,
+                height: 16,
+            },
+            history_budget: 1 << 20,
+            pause_on_cycle: false,
+        };
+        (Simulation::new(backend, config).unwrap(), ui)
+    }
+
+    fn sorted_cells(simulation: &Simulation) -> Vec<(Position, CellState)> {
+        let mut cells = simulation.engine.cells();
+        cells.sort_by_key(|(position, _)| (position.y, position.x));
+        cells
+    }
+
+    #[test]
+    fn seeks_back_and_forth_through_the_history() {
+        let (mut simulation, ui) = simulation("/2/3", EngineKind::Naive);
+        let mut generations = vec![sorted_cells(&simulation)];
+        for _ in 0..10 {
+            simulation.handle(Message::Evolve(1));
+            generations.push(sorted_cells(&simulation));
+        }
+
+        for &generation in &[3, 8, 0, 10, 5] {
+            simulation.handle(Message::SeekTo(generation));
+            assert_eq!(simulation.generation, generation);
+            assert_eq!(sorted_cells(&simulation), generations[generation]);
+        }
+
+        // An edit in generation 5 replaces the generations after it
+        let position = Position { x: 7, y: 7 };
+        let state = simulation.engine.get(position).toggled();
+        simulation.handle(Message::EditCells(vec![(position, state)]));
+        simulation.handle(Message::SeekTo(10));
+        assert_eq!(simulation.generation, 5);
+        assert_eq!(simulation.engine.get(position), state);
+        assert!(ui
+            .receive()
+            .iter()
+            .any(|message| matches!(message, Message::HistoryRange(0, 5))));
+    }
+
+    #[test]
+    fn rule_changes_move_to_an_engine_that_can_run_the_rule() {
+        let (mut simulation, _ui) = simulation("B3/S23", EngineKind::HashLife);
+        let cells = sorted_cells(&simulation);
+        simulation.handle(Message::RuleChange("/2/3".parse().unwrap()));
+        assert_eq!(simulation.engine_kind, EngineKind::Naive);
+        assert_eq!(simulation.rule, "/2/3".parse().unwrap());
+        assert_eq!(sorted_cells(&simulation), cells);
+
+        // Live cells of Brian's Brain start dying instead of staying alive
+        simulation.handle(Message::Evolve(1));
+        assert!(simulation
+            .engine
+            .cells()
+            .iter()
+            .any(|&(_, state)| state == CellState::Dying(1)));
+    }
+
+    #[test]
+    fn rule_changes_an_unbounded_plane_cant_run_are_refused() {
+        let (mut unbounded, _ui) = simulation("B3/S23", EngineKind::UnboundedHashLife);
+        unbounded.handle(Message::RuleChange("B03/S23".parse().unwrap()));
+        assert_eq!(unbounded.engine_kind, EngineKind::UnboundedHashLife);
+        assert_eq!(unbounded.rule, Rule::default());
+    }
+
+    #[test]
+    fn removing_ants_drops_the_colors_the_rule_has_no_state_for() {
+        let (mut simulation, _ui) = simulation("B3/S23", EngineKind::Naive);
+        let turmite: Turmite = "RLR".parse().unwrap();
+        let ant = Ant::new(Position { x: 8, y: 8 }, Heading::North);
+        simulation.handle(Message::AddAnt(turmite, ant));
+        simulation.handle(Message::Evolve(50));
+        let painted = sorted_cells(&simulation);
+        assert!(painted
+            .iter()
+            .any(|&(_, state)| matches!(state, CellState::Dying(_))));
+
+        simulation.handle(Message::RemoveAnts);
+        assert!(sorted_cells(&simulation)
+            .iter()
+            .all(|&(_, state)| state == CellState::Alive));
+
+        // The dropped colors come back when the removal is undone
+        simulation.handle(Message::StepBack);
+        assert_eq!(sorted_cells(&simulation), painted);
+    }
+}
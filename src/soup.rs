@@ -0,0 +1,253 @@
+use crate::simulation::Position;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use std::fmt;
+
+/// Random live cells filling a rectangle. The same soup always has the same cells, so a seed is enough to recreate it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Soup {
+    pub seed: u64,
+    pub density: u32, // Chance of every cell in the region to be alive, in percent
+    pub symmetry: Symmetry,
+    pub origin: Position, // Top left corner of the region
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Soup {
+    pub fn live_cells(&self) -> Vec<Position> {
+        let (width, height) = self.symmetry.region_size(self.width, self.height);
+        let density = self.density.min(100);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        // Every cell gets a random state, and then takes on the state of the first cell it is symmetric to
+        let random_states: Vec<bool> = (0..width * height)
+            .map(|_| rng.gen_ratio(density, 100))
+            .collect();
+
+        let mut live_cells = vec![];
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let position = Position { x, y };
+                let first = self
+                    .symmetry
+                    .images(position, width as isize, height as isize)
+                    .into_iter()
+                    .min_by_key(|image| (image.y, image.x))
+                    .unwrap_or(position);
+                if random_states[first.y as usize * width + first.x as usize] {
+                    live_cells.push(self.origin + position);
+                }
+            }
+        }
+        live_cells
+    }
+}
+
+impl fmt::Display for Soup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Seed {}, {}% alive, {} symmetry, {}x{} at {}, {}",
+            self.seed,
+            self.density,
+            self.symmetry,
+            self.width,
+            self.height,
+            self.origin.x,
+            self.origin.y
+        )
+    }
+}
+
+/// Symmetry classes of apgsearch. The mirror axes and centers of rotation go through the middle of the region
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symmetry {
+    #[default]
+    C1, // No symmetry
+    C2,           // Same after half a turn
+    C4,           // Same after a quarter turn
+    D2Horizontal, // Top half mirrors the bottom half
+    D2Vertical,   // Left half mirrors the right half
+    D4,           // Mirrored both ways
+    D8,           // Mirrored both ways and along the diagonals
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 7] = [
+        Symmetry::C1,
+        Symmetry::C2,
+        Symmetry::C4,
+        Symmetry::D2Horizontal,
+        Symmetry::D2Vertical,
+        Symmetry::D4,
+        Symmetry::D8,
+    ];
+
+    pub fn next(self) -> Symmetry {
+        let index = Symmetry::ALL.iter().position(|&symmetry| symmetry == self);
+        Symmetry::ALL[index.map_or(0, |index| (index + 1) % Symmetry::ALL.len())]
+    }
+
+    /// Symmetry with the given display name, ignoring case
+    pub fn from_name(name: &str) -> Option<Symmetry> {
+        let name = name.trim();
+        Symmetry::ALL
+            .iter()
+            .copied()
+            .find(|symmetry| symmetry.to_string().eq_ignore_ascii_case(name))
+    }
+
+    /// Size of the part of a region that a soup fills. Quarter turns only work on squares, so C4 and D8 soups fill
+    /// the largest square in the top left corner
+    pub fn region_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Symmetry::C4 | Symmetry::D8 => (width.min(height), width.min(height)),
+            _ => (width, height),
+        }
+    }
+
+    // Cells that have to be in the same state as the position, including the position itself
+    fn images(self, position: Position, width: isize, height: isize) -> Vec<Position> {
+        let Position { x, y } = position;
+        let (mirrored_x, mirrored_y) = (width - 1 - x, height - 1 - y);
+        let at = |x, y| Position { x, y };
+
+        match self {
+            Symmetry::C1 => vec![position],
+            Symmetry::C2 => vec![position, at(mirrored_x, mirrored_y)],
+            Symmetry::C4 => vec![
+                position,
+                at(mirrored_y, x),
+                at(mirrored_x, mirrored_y),
+                at(y, mirrored_x),
+            ],
+            Symmetry::D2Horizontal => vec![position, at(x, mirrored_y)],
+            Symmetry::D2Vertical => vec![position, at(mirrored_x, y)],
+            Symmetry::D4 => vec![
+                position,
+                at(mirrored_x, y),
+                at(x, mirrored_y),
+                at(mirrored_x, mirrored_y),
+            ],
+            Symmetry::D8 => vec![
+                position,
+                at(mirrored_y, x),
+                at(mirrored_x, mirrored_y),
+                at(y, mirrored_x),
+                at(y, x),
+                at(mirrored_x, y),
+                at(mirrored_y, mirrored_x),
+                at(x, mirrored_y),
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Symmetry::C1 => "C1",
+            Symmetry::C2 => "C2",
+            Symmetry::C4 => "C4",
+            Symmetry::D2Horizontal => "D2-",
+            Symmetry::D2Vertical => "D2|",
+            Symmetry::D4 => "D4",
+            Symmetry::D8 => "D8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn soup(seed: u64, density: u32, width: usize, height: usize) -> Soup {
+        Soup {
+            seed,
+            density,
+            symmetry: Symmetry::C1,
+            origin: Position { x: 3, y: -2 },
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn seeds_recreate_their_soups() {
+        let cells = soup(42, 50, 16, 16).live_cells();
+        assert_eq!(soup(42, 50, 16, 16).live_cells(), cells);
+        assert_ne!(soup(43, 50, 16, 16).live_cells(), cells);
+        assert!(cells
+            .iter()
+            .all(|&Position { x, y }| (3..19).contains(&x) && (-2..14).contains(&y)));
+
+        assert!(soup(42, 0, 16, 16).live_cells().is_empty());
+        assert_eq!(soup(42, 100, 16, 16).live_cells().len(), 256);
+        let count = soup(7, 30, 100, 100).live_cells().len();
+        assert!((2700..3300).contains(&count), "{} live cells", count);
+    }
+
+    // Odd and even sides, and regions that aren't square
+    #[test]
+    fn soups_have_their_symmetry() {
+        for &symmetry in &Symmetry::ALL {
+            for &(width, height) in &[(8, 8), (9, 9), (8, 11), (11, 6)] {
+                let soup = Soup {
+                    symmetry,
+                    ..soup(width as u64, 50, width, height)
+                };
+                let cells: HashSet<Position> = soup
+                    .live_cells()
+                    .into_iter()
+                    .map(|position| Position {
+                        x: position.x - soup.origin.x,
+                        y: position.y - soup.origin.y,
+                    })
+                    .collect();
+                let (width, height) = symmetry.region_size(width, height);
+                let (right, bottom) = (width as isize - 1, height as isize - 1);
+
+                let half_turn = |Position { x, y }| Position {
+                    x: right - x,
+                    y: bottom - y,
+                };
+                let quarter_turn = |Position { x, y }| Position {
+                    x: bottom - y,
+                    y: x,
+                };
+                let upside_down = |Position { x, y }| Position { x, y: bottom - y };
+                let mirrored = |Position { x, y }| Position { x: right - x, y };
+                let diagonal = |Position { x, y }| Position { x: y, y: x };
+                let transforms: Vec<&dyn Fn(Position) -> Position> = match symmetry {
+                    Symmetry::C1 => vec![],
+                    Symmetry::C2 => vec![&half_turn],
+                    Symmetry::C4 => vec![&quarter_turn],
+                    Symmetry::D2Horizontal => vec![&upside_down],
+                    Symmetry::D2Vertical => vec![&mirrored],
+                    Symmetry::D4 => vec![&upside_down, &mirrored],
+                    Symmetry::D8 => vec![&quarter_turn, &diagonal],
+                };
+                for transform in transforms {
+                    let images: HashSet<Position> =
+                        cells.iter().map(|&cell| transform(cell)).collect();
+                    assert_eq!(images, cells, "{} soup of {}x{}", symmetry, width, height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn symmetries_have_names() {
+        for &symmetry in &Symmetry::ALL {
+            assert_eq!(Symmetry::from_name(&symmetry.to_string()), Some(symmetry));
+        }
+        assert_eq!(Symmetry::from_name("d2|"), Some(Symmetry::D2Vertical));
+        assert_eq!(Symmetry::from_name("D6"), None);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::engine::Engine;
+use crate::rule::{LargerThanLife, Rule, NEIGHBORS};
+use crate::rule_table::RuleTable;
+use crate::simulation::{CellState, Position};
+use crate::summed_area::SummedAreaTable;
+use crate::tiling::Tiling;
+
+use std::collections::{HashMap, HashSet};
+
+/// Unbounded plane that only stores its live and dying cells, so patterns can travel arbitrarily far.
+/// Rules with B0 can't be used, as they would bring the entire plane to life at once.
+#[derive(Default)]
+pub struct SparseGrid {
+    live_cells: HashSet<Position>,
+    dying_cells: HashMap<Position, u8>, // Ages of the cells in a decay state of a Generations rule
+}
+
+impl SparseGrid {
+    pub fn new() -> SparseGrid {
+        SparseGrid::default()
+    }
+
+    // Counts the live cells around every cell within the radius of the rectangle around the live cells, in a table
+    // over that rectangle
+    fn step_larger_than_life(
+        &mut self,
+        rule: &Rule,
+        larger_than_life: &LargerThanLife,
+    ) -> Vec<(Position, CellState)> {
+        let radius = larger_than_life.radius as isize;
+        let half_widths = larger_than_life.half_widths();
+        let bounds = self
+            .live_cells
+            .iter()
+            .fold(None, |bounds, &position| match bounds {
+                None => Some((position, position)),
+                Some((top_left, bottom_right)) => Some((
+                    Position {
+                        x: position.x.min(top_left.x),
+                        y: position.y.min(top_left.y),
+                    },
+                    Position {
+                        x: position.x.max(bottom_right.x),
+                        y: position.y.max(bottom_right.y),
+                    },
+                )),
+            });
+        let is_near = |position: &Position| {
+            bounds.is_some_and(|(top_left, bottom_right): (Position, Position)| {
+                (top_left.x - radius..=bottom_right.x + radius).contains(&position.x)
+                    && (top_left.y - radius..=bottom_right.y + radius).contains(&position.y)
+            })
+        };
+
+        // Live cells in the table count themselves
+        let mut near_cells = vec![];
+        if let Some((top_left, bottom_right)) = bounds {
+            let table = SummedAreaTable::new(
+                top_left,
+                (bottom_right.x - top_left.x + 1) as usize,
+                (bottom_right.y - top_left.y + 1) as usize,
+                |position| self.live_cells.contains(&position),
+            );
+            for y in top_left.y - radius..=bottom_right.y + radius {
+                for x in top_left.x - radius..=bottom_right.x + radius {
+                    let position = Position { x, y };
+                    let count = table.neighborhood_count(position, &half_widths)
+                        - self.live_cells.contains(&position) as usize;
+                    near_cells.push((position, count));
+                }
+            }
+        }
+
+        // Dying cells further away keep decaying
+        let loners = self
+            .dying_cells
+            .keys()
+            .filter(|position| !is_near(position))
+            .map(|&position| (position, 0));
+
+        let mut transitions: Vec<(Position, CellState)> = near_cells
+            .into_iter()
+            .chain(loners)
+            .filter_map(|(position, count)| {
+                let state = self.get(position);
+                match rule.next_state_by_count(state, count) {
+                    next_state if next_state != state => Some((position, next_state)),
+                    _ => None,
+                }
+            })
+            .collect();
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+
+    // Looks up the states of each cell that isn't dead and of the cells around them in the rule table. Every
+    // neighborhood of a rule table contains the opposite of each of its offsets, so those are the cells that have a
+    // neighbor that isn't dead
+    fn step_table(&mut self, table: &RuleTable) -> Vec<(Position, CellState)> {
+        let offsets = table.neighborhood().offsets();
+        let candidates: HashSet<Position> = self
+            .live_cells
+            .iter()
+            .chain(self.dying_cells.keys())
+            .flat_map(|&position| {
+                std::iter::once(position)
+                    .chain(offsets.iter().map(move |&offset| position + offset))
+            })
+            .collect();
+
+        let mut transitions: Vec<(Position, CellState)> = candidates
+            .into_iter()
+            .filter_map(|position| {
+                let state = self.get(position);
+                let mut states = vec![state.index()];
+                states.extend(
+                    offsets
+                        .iter()
+                        .map(|&offset| self.get(position + offset).index()),
+                );
+                match CellState::from_index(table.next_state(&states)) {
+                    next_state if next_state != state => Some((position, next_state)),
+                    _ => None,
+                }
+            })
+            .collect();
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+
+    // Counts the live neighbors of each cell on hexagons or triangles. A cell is a neighbor of each of its own
+    // neighbors on both tilings, so live cells hand their counts out the same way as on squares
+    fn step_on_tiling(&mut self, rule: &Rule, tiling: Tiling) -> Vec<(Position, CellState)> {
+        let mut counts: HashMap<Position, usize> = HashMap::new();
+        for &position in &self.live_cells {
+            for &offset in tiling.neighbors(position) {
+                *counts.entry(position + offset).or_insert(0) += 1;
+            }
+        }
+
+        let loners = self
+            .live_cells
+            .iter()
+            .chain(self.dying_cells.keys())
+            .filter(|position| !counts.contains_key(position))
+            .map(|&position| (position, 0));
+
+        let mut transitions: Vec<(Position, CellState)> = counts
+            .iter()
+            .map(|(&position, &count)| (position, count))
+            .chain(loners)
+            .filter_map(|(position, count)| {
+                let state = self.get(position);
+                match rule.next_state_by_count(state, count) {
+                    next_state if next_state != state => Some((position, next_state)),
+                    _ => None,
+                }
+            })
+            .collect();
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+}
+
+impl Engine for SparseGrid {
+    fn step(&mut self, rule: &Rule) -> Vec<(Position, CellState)> {
+        if let Some(table) = rule.table() {
+            return self.step_table(table);
+        }
+        if let Some(larger_than_life) = rule.larger_than_life() {
+            return self.step_larger_than_life(rule, &larger_than_life);
+        }
+        if rule.tiling() != Tiling::Square {
+            return self.step_on_tiling(rule, rule.tiling());
+        }
+
+        // A live cell is the neighbor in the opposite direction of each of its own neighbors, and NEIGHBORS lists
+        // opposite directions in reverse order
+        let mut neighborhoods: HashMap<Position, u8> = HashMap::new();
+        for &position in &self.live_cells {
+            for (bit, &offset) in NEIGHBORS.iter().enumerate() {
+                *neighborhoods.entry(position + offset).or_insert(0) |= 1 << (7 - bit);
+            }
+        }
+
+        // Cells without live neighbors can only stay alive or stay dead, or keep decaying
+        let loners = self
+            .live_cells
+            .iter()
+            .chain(self.dying_cells.keys())
+            .filter(|position| !neighborhoods.contains_key(position))
+            .map(|&position| (position, 0));
+
+        let mut transitions: Vec<(Position, CellState)> = neighborhoods
+            .iter()
+            .map(|(&position, &neighborhood)| (position, neighborhood))
+            .chain(loners)
+            .filter_map(|(position, neighborhood)| {
+                let state = self.get(position);
+                match rule.next_state(state, neighborhood) {
+                    next_state if next_state != state => Some((position, next_state)),
+                    _ => None,
+                }
+            })
+            .collect();
+        transitions.sort_by_key(|(position, _)| (position.y, position.x));
+
+        for &(position, state) in &transitions {
+            self.set(position, state);
+        }
+
+        transitions
+    }
+
+    fn get(&self, position: Position) -> CellState {
+        if self.live_cells.contains(&position) {
+            CellState::Alive
+        } else if let Some(&age) = self.dying_cells.get(&position) {
+            CellState::Dying(age)
+        } else {
+            CellState::Dead
+        }
+    }
+
+    fn set(&mut self, position: Position, state: CellState) {
+        self.live_cells.remove(&position);
+        self.dying_cells.remove(&position);
+        match state {
+            CellState::Alive => {
+                self.live_cells.insert(position);
+            }
+            CellState::Dying(age) => {
+                self.dying_cells.insert(position, age);
+            }
+            CellState::Dead => (),
+        }
+    }
+
+    fn live_cells(&self) -> Vec<Position> {
+        self.live_cells.iter().copied().collect()
+    }
+
+    fn cells(&self) -> Vec<(Position, CellState)> {
+        self.live_cells
+            .iter()
+            .map(|&position| (position, CellState::Alive))
+            .chain(
+                self.dying_cells
+                    .iter()
+                    .map(|(&position, &age)| (position, CellState::Dying(age))),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{EngineKind, NaiveGrid};
+    use crate::topology::Topology;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn sorted(mut cells: Vec<(Position, CellState)>) -> Vec<(Position, CellState)> {
+        cells.sort_by_key(|(position, _)| (position.y, position.x));
+        cells
+    }
+
+    // The soup sits in the middle of the naive grid, far enough from its edges that nothing reaches them in time
+    #[test]
+    fn matches_naive_grid_away_from_the_edges() {
+        let rules = [
+            "B3/S23",
+            "B36/S23",
+            "B2/S",
+            "B3678/S34678",
+            "/2/3",
+            "B2-a/S12",
+            "R2,C0,M1,S4..9,B5..7,NM",
+            "B2/S34H",
+            "B2,3/S2,12L",
+        ];
+        for (seed, rule) in rules.iter().enumerate() {
+            let rule: Rule = rule.parse().unwrap();
+            let mut rng = StdRng::seed_from_u64(seed as u64);
+            let mut naive = NaiveGrid::new(100, 100, Topology::default());
+            let mut sparse = SparseGrid::new();
+            for y in 40..60 {
+                for x in 40..60 {
+                    if rng.gen_ratio(2, 5) {
+                        naive.set(Position { x, y }, CellState::Alive);
+                        sparse.set(Position { x, y }, CellState::Alive);
+                    }
+                }
+            }
+
+            for generation in 0..12 {
+                assert_eq!(
+                    sparse.step(&rule),
+                    sorted(naive.step(&rule)),
+                    "{}, generation {}",
+                    rule,
+                    generation + 1
+                );
+            }
+            assert_eq!(sorted(sparse.cells()), sorted(naive.cells()));
+        }
+    }
+
+    #[test]
+    fn rejects_rules_with_b0() {
+        let rule: Rule = "B0/S8".parse().unwrap();
+        assert!(!EngineKind::Sparse.supports_rule(&rule));
+        assert!(!EngineKind::UnboundedHashLife.supports_rule(&rule));
+        assert!(EngineKind::Naive.supports_rule(&rule));
+    }
+}
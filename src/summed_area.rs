@@ -0,0 +1,102 @@
+//! Summed-area tables, which count the live cells in any rectangle of the grid in constant time. Larger than Life
+//! rules count their neighborhoods with them, so that large radii stay fast: a Moore neighborhood is a single
+//! rectangle, and von Neumann and circular neighborhoods take one row at a time.
+
+use crate::simulation::Position;
+
+pub struct SummedAreaTable {
+    origin: Position, // Top left cell of the table
+    width: usize,
+    height: usize,
+    sums: Vec<u32>, // sums[y * (width + 1) + x]: Live cells above row y and left of column x, relative to the origin
+}
+
+impl SummedAreaTable {
+    /// Table over the rectangle of cells with the origin as its top left corner
+    pub fn new(
+        origin: Position,
+        width: usize,
+        height: usize,
+        is_alive: impl Fn(Position) -> bool,
+    ) -> SummedAreaTable {
+        let mut sums = vec![0; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row_sum = 0;
+            for x in 0..width {
+                let position = origin
+                    + Position {
+                        x: x as isize,
+                        y: y as isize,
+                    };
+                row_sum += is_alive(position) as u32;
+                sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row_sum;
+            }
+        }
+
+        SummedAreaTable {
+            origin,
+            width,
+            height,
+            sums,
+        }
+    }
+
+    /// Live cells in the rectangle between two corners, both of them included. Cells outside the table count as dead
+    pub fn count(&self, top_left: Position, bottom_right: Position) -> usize {
+        let clamp = |value: isize, origin: isize, length: usize| -> usize {
+            (value - origin).clamp(0, length as isize) as usize
+        };
+        let (left, right) = (
+            clamp(top_left.x, self.origin.x, self.width),
+            clamp(bottom_right.x + 1, self.origin.x, self.width),
+        );
+        let (top, bottom) = (
+            clamp(top_left.y, self.origin.y, self.height),
+            clamp(bottom_right.y + 1, self.origin.y, self.height),
+        );
+        if left >= right || top >= bottom {
+            return 0;
+        }
+
+        let sum = |x: usize, y: usize| self.sums[y * (self.width + 1) + x] as usize;
+        sum(right, bottom) + sum(left, top) - sum(left, bottom) - sum(right, top)
+    }
+
+    /// Live cells in the neighborhood of a cell, the cell itself included. The neighborhood is given by how far it
+    /// reaches to either side in each row, see [`half_widths`](crate::rule::LargerThanLife::half_widths)
+    pub fn neighborhood_count(&self, position: Position, half_widths: &[usize]) -> usize {
+        let radius = (half_widths.len() / 2) as isize;
+
+        // Only the Moore neighborhood reaches all the way to the side in its top row
+        if half_widths.first() == Some(&(radius as usize)) {
+            let top_left = Position {
+                x: position.x - radius,
+                y: position.y - radius,
+            };
+            let bottom_right = Position {
+                x: position.x + radius,
+                y: position.y + radius,
+            };
+            return self.count(top_left, bottom_right);
+        }
+
+        half_widths
+            .iter()
+            .zip(-radius..=radius)
+            .map(|(&half_width, y_offset)| {
+                let y = position.y + y_offset;
+                let half_width = half_width as isize;
+                self.count(
+                    Position {
+                        x: position.x - half_width,
+                        y,
+                    },
+                    Position {
+                        x: position.x + half_width,
+                        y,
+                    },
+                )
+            })
+            .sum()
+    }
+}
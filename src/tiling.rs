@@ -0,0 +1,84 @@
+use crate::rule::NEIGHBORS;
+use crate::simulation::Position;
+
+// The square neighbors without the top right and bottom left ones, the way Golly lays out hexagons
+const HEXAGONAL_NEIGHBORS: [Position; 6] = [
+    Position { x: -1, y: -1 },
+    Position { x: 0, y: -1 },
+    Position { x: -1, y: 0 },
+    Position { x: 1, y: 0 },
+    Position { x: 0, y: 1 },
+    Position { x: 1, y: 1 },
+];
+
+// Triangles that share an edge or a corner with a triangle pointing up: three above its tip, four beside it and
+// five below its base
+const UPWARD_NEIGHBORS: [Position; 12] = [
+    Position { x: -1, y: -1 },
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: -1 },
+    Position { x: -2, y: 0 },
+    Position { x: -1, y: 0 },
+    Position { x: 1, y: 0 },
+    Position { x: 2, y: 0 },
+    Position { x: -2, y: 1 },
+    Position { x: -1, y: 1 },
+    Position { x: 0, y: 1 },
+    Position { x: 1, y: 1 },
+    Position { x: 2, y: 1 },
+];
+
+// The same for a triangle pointing down, upside down
+const DOWNWARD_NEIGHBORS: [Position; 12] = [
+    Position { x: -2, y: -1 },
+    Position { x: -1, y: -1 },
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: -1 },
+    Position { x: 2, y: -1 },
+    Position { x: -2, y: 0 },
+    Position { x: -1, y: 0 },
+    Position { x: 1, y: 0 },
+    Position { x: 2, y: 0 },
+    Position { x: -1, y: 1 },
+    Position { x: 0, y: 1 },
+    Position { x: 1, y: 1 },
+];
+
+/// Shape of the cells. Cells keep their square grid positions on every tiling:
+/// - Hexagons are the squares of a grid skewed so that each row lies half a cell left of the one above.
+/// - Triangles where x + y is even point up, the others point down, and each row alternates between the two.
+///
+/// Triangles only line up across a wrapped edge if its length is even
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tiling {
+    #[default]
+    Square,
+    Hexagonal,
+    Triangular,
+}
+
+impl Tiling {
+    /// Number of neighbors every cell has: those it shares an edge or a corner with
+    pub fn neighbor_count(self) -> usize {
+        match self {
+            Tiling::Square => 8,
+            Tiling::Hexagonal => 6,
+            Tiling::Triangular => 12,
+        }
+    }
+
+    /// Offsets of the neighbors of the cell at the position
+    pub fn neighbors(self, position: Position) -> &'static [Position] {
+        match self {
+            Tiling::Square => &NEIGHBORS,
+            Tiling::Hexagonal => &HEXAGONAL_NEIGHBORS,
+            Tiling::Triangular if Tiling::points_up(position) => &UPWARD_NEIGHBORS,
+            Tiling::Triangular => &DOWNWARD_NEIGHBORS,
+        }
+    }
+
+    /// Whether the cell at the position is a triangle pointing up on the triangular tiling
+    pub fn points_up(position: Position) -> bool {
+        (position.x + position.y).rem_euclid(2) == 0
+    }
+}
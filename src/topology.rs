@@ -0,0 +1,179 @@
+use crate::simulation::Position;
+
+use std::fmt;
+
+/// What happens to a cell's neighborhood where it crosses the edge of the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Dead,    // Cells beyond the edge are always dead
+    Wrapped, // The opposite edge continues where this one ends
+    Twisted, // Like Wrapped, but the opposite edge is attached upside down
+}
+
+impl Edge {
+    pub fn next(self) -> Edge {
+        match self {
+            Edge::Dead => Edge::Wrapped,
+            Edge::Wrapped => Edge::Twisted,
+            Edge::Twisted => Edge::Dead,
+        }
+    }
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Edge::Dead => "Dead",
+            Edge::Wrapped => "Wrapped",
+            Edge::Twisted => "Twisted",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Topology {
+    pub horizontal: Edge, // Left and right edges
+    pub vertical: Edge,   // Top and bottom edges
+}
+
+impl Topology {
+    pub const TORUS: Topology = Topology {
+        horizontal: Edge::Wrapped,
+        vertical: Edge::Wrapped,
+    };
+
+    /// Topology with the name it is displayed with, such as "Klein bottle", or with a pair of left/right and
+    /// top/bottom edges, such as "Wrapped/Twisted". Case doesn't matter
+    pub fn from_name(name: &str) -> Option<Topology> {
+        let edges = [Edge::Dead, Edge::Wrapped, Edge::Twisted];
+        let topologies = edges.iter().flat_map(|&horizontal| {
+            edges.iter().map(move |&vertical| Topology {
+                horizontal,
+                vertical,
+            })
+        });
+
+        let name = name.trim().to_lowercase();
+        topologies
+            .clone()
+            .find(|topology| {
+                let edges = format!("{}/{}", topology.horizontal, topology.vertical);
+                edges.to_lowercase() == name
+            })
+            .or_else(|| {
+                topologies
+                    .clone()
+                    .find(|topology| topology.to_string().to_lowercase() == name)
+            })
+    }
+
+    pub fn is_torus(self) -> bool {
+        self == Topology::TORUS
+    }
+
+    /// Position on the grid that a position off the grid stands for, or None if there is no such cell
+    pub fn wrap(self, position: Position, width: usize, height: usize) -> Option<Position> {
+        let (width, height) = (width as isize, height as isize);
+        let Position { mut x, mut y } = position;
+
+        if !(0..width).contains(&x) {
+            match self.horizontal {
+                Edge::Dead => return None,
+                Edge::Wrapped => x = x.rem_euclid(width),
+                Edge::Twisted => {
+                    x = x.rem_euclid(width);
+                    y = height - 1 - y;
+                }
+            }
+        }
+
+        if !(0..height).contains(&y) {
+            match self.vertical {
+                Edge::Dead => return None,
+                Edge::Wrapped => y = y.rem_euclid(height),
+                Edge::Twisted => {
+                    y = y.rem_euclid(height);
+                    x = width - 1 - x;
+                }
+            }
+        }
+
+        Some(Position { x, y })
+    }
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::TORUS
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match (self.horizontal, self.vertical) {
+            (Edge::Dead, Edge::Dead) => "Bounded plane",
+            (Edge::Wrapped, Edge::Wrapped) => "Torus",
+            (Edge::Twisted, Edge::Twisted) => "Cross-surface",
+            (Edge::Wrapped, Edge::Twisted) | (Edge::Twisted, Edge::Wrapped) => "Klein bottle",
+            (Edge::Wrapped, Edge::Dead) | (Edge::Dead, Edge::Wrapped) => "Cylinder",
+            (Edge::Twisted, Edge::Dead) | (Edge::Dead, Edge::Twisted) => "Möbius strip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_topologies_by_name_and_by_edges() {
+        let klein_bottle = Topology {
+            horizontal: Edge::Twisted,
+            vertical: Edge::Wrapped,
+        };
+        assert_eq!(Topology::from_name("Torus"), Some(Topology::TORUS));
+        assert_eq!(Topology::from_name(" torus "), Some(Topology::TORUS));
+        assert_eq!(Topology::from_name("twisted/wrapped"), Some(klein_bottle));
+        assert_eq!(Topology::from_name("Sphere"), None);
+        assert_eq!(klein_bottle.to_string(), "Klein bottle");
+
+        // A name stands for the first of the topologies it is shared by
+        assert_eq!(
+            Topology::from_name("Klein bottle"),
+            Some(Topology {
+                horizontal: Edge::Wrapped,
+                vertical: Edge::Twisted,
+            })
+        );
+    }
+
+    #[test]
+    fn wraps_positions_across_each_edge() {
+        let topology = |horizontal, vertical| Topology {
+            horizontal,
+            vertical,
+        };
+        let position = |x, y| Position { x, y };
+
+        let torus = Topology::TORUS;
+        assert_eq!(torus.wrap(position(2, 1), 4, 3), Some(position(2, 1)));
+        assert_eq!(torus.wrap(position(-1, 3), 4, 3), Some(position(3, 0)));
+
+        let twisted = topology(Edge::Twisted, Edge::Dead);
+        assert_eq!(twisted.wrap(position(4, 0), 4, 3), Some(position(0, 2)));
+        assert_eq!(twisted.wrap(position(1, -1), 4, 3), None);
+
+        let twisted = topology(Edge::Dead, Edge::Twisted);
+        assert_eq!(twisted.wrap(position(0, -1), 4, 3), Some(position(3, 2)));
+        assert_eq!(twisted.wrap(position(-1, 0), 4, 3), None);
+
+        // Crossing both twisted edges at a corner turns the position around twice
+        let cross_surface = topology(Edge::Twisted, Edge::Twisted);
+        assert_eq!(
+            cross_surface.wrap(position(-1, -1), 4, 3),
+            Some(position(0, 0))
+        );
+    }
+}
@@ -0,0 +1,551 @@
+//! Langton's ant and other turmites: agents that walk over the grid, each step turning by the color of the cell
+//! they stand on, painting it another color and moving one cell ahead. Colors are cell states numbered the way
+//! [`CellState::index`] numbers them, so a two-color ant paints cells dead and alive.
+//!
+//! ```
+//! use conway::turmite::{Ant, Colony, Heading, Turmite};
+//! use conway::{EngineKind, Position, Topology};
+//!
+//! let mut grid = EngineKind::Sparse.create(0, 0, Topology::TORUS).unwrap();
+//! let langtons_ant: Turmite = "RL".parse().unwrap();
+//! let mut colony = Colony::new(langtons_ant);
+//! colony.ants.push(Ant::new(Position { x: 0, y: 0 }, Heading::North));
+//! colony.advance(grid.as_mut(), None, 10_000);
+//!
+//! // Shortly before it starts building its highway
+//! assert_eq!(grid.live_cells().len(), 720);
+//! ```
+
+use crate::engine::Engine;
+use crate::simulation::{CellState, Position};
+use crate::topology::{Edge, Topology};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Most colors a turmite can paint, so that their number fits in a byte
+pub const MAX_COLORS: usize = 255;
+
+/// Direction an ant faces. The grid's y axis points down, so north is towards smaller y
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    const ALL: [Heading; 4] = [Heading::North, Heading::East, Heading::South, Heading::West];
+
+    pub fn turned(self, turn: Turn) -> Heading {
+        let quarters = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        };
+        Heading::ALL[(self as usize + quarters) % 4]
+    }
+
+    /// Offset of the cell ahead
+    pub fn offset(self) -> Position {
+        match self {
+            Heading::North => Position { x: 0, y: -1 },
+            Heading::East => Position { x: 1, y: 0 },
+            Heading::South => Position { x: 0, y: 1 },
+            Heading::West => Position { x: -1, y: 0 },
+        }
+    }
+
+    // Heading in the mirror image across a horizontal line when `vertically` is set, or a vertical line otherwise
+    fn mirrored(self, vertically: bool) -> Heading {
+        match (self, vertically) {
+            (Heading::North, true) => Heading::South,
+            (Heading::South, true) => Heading::North,
+            (Heading::East, false) => Heading::West,
+            (Heading::West, false) => Heading::East,
+            (heading, _) => heading,
+        }
+    }
+}
+
+/// Turn an ant makes relative to its heading, with the numbers Golly's turmite notation gives them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    None = 1,
+    Right = 2,
+    UTurn = 4,
+    Left = 8,
+}
+
+impl Turn {
+    fn from_letter(letter: char) -> Option<Turn> {
+        match letter.to_ascii_uppercase() {
+            'N' => Some(Turn::None),
+            'R' => Some(Turn::Right),
+            'U' => Some(Turn::UTurn),
+            'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Turn::None => 'N',
+            Turn::Right => 'R',
+            Turn::UTurn => 'U',
+            Turn::Left => 'L',
+        }
+    }
+
+    fn from_number(number: usize) -> Option<Turn> {
+        [Turn::None, Turn::Right, Turn::UTurn, Turn::Left]
+            .iter()
+            .copied()
+            .find(|&turn| turn as usize == number)
+    }
+}
+
+/// What a turmite in some state does on a cell of some color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Action {
+    pub color: u8, // Color it paints the cell
+    pub turn: Turn,
+    pub state: usize, // State it goes on in
+}
+
+/// Table of the actions of a turmite for each of its states and each color. Parses from an ant's turns on each color
+/// such as RL for Langton's ant or RRLLLRLLLRRR, or from Golly's turmite notation, such as {{{1,2,0},{0,8,0}}} for
+/// Langton's ant, which lists {color to paint, turn, next state} for each color within each state. Turns are 1 for
+/// none, 2 for right, 4 for a U-turn and 8 for left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turmite {
+    actions: Vec<Vec<Action>>, // Indexed by state, then color
+}
+
+impl Turmite {
+    /// Number of colors the turmite tells apart and paints cells in, counting dead as one
+    pub fn colors(&self) -> u8 {
+        self.actions[0].len() as u8
+    }
+
+    pub fn states(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Action on a cell of the color. Colors the turmite doesn't know count as the first
+    pub fn action(&self, state: usize, color: u8) -> Action {
+        let actions = &self.actions[state];
+        actions.get(color as usize).copied().unwrap_or(actions[0])
+    }
+
+    // Turns of an ant that paints each color with the next one, if this is such an ant
+    fn turns(&self) -> Option<Vec<Turn>> {
+        let colors = self.colors();
+        match self.actions.as_slice() {
+            [actions] => actions
+                .iter()
+                .enumerate()
+                .map(|(color, action)| {
+                    let is_next = action.color as usize == (color + 1) % colors as usize;
+                    Some(action.turn).filter(|_| is_next)
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn parse_turns(text: &str) -> Result<Turmite, TurmiteError> {
+        let turns = text
+            .chars()
+            .map(|letter| Turn::from_letter(letter).ok_or(TurmiteError::UnknownTurn(letter)))
+            .collect::<Result<Vec<Turn>, TurmiteError>>()?;
+        if !(2..=MAX_COLORS).contains(&turns.len()) {
+            return Err(TurmiteError::InvalidColorCount(turns.len()));
+        }
+
+        let actions = turns
+            .iter()
+            .enumerate()
+            .map(|(color, &turn)| Action {
+                color: ((color + 1) % turns.len()) as u8,
+                turn,
+                state: 0,
+            })
+            .collect();
+        Ok(Turmite {
+            actions: vec![actions],
+        })
+    }
+
+    // Reads the nested braces as a list of states, each a list of colors, each a list of three numbers
+    fn parse_table(text: &str) -> Result<Turmite, TurmiteError> {
+        let invalid = || TurmiteError::InvalidTable(text.to_string());
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let states = text
+            .strip_prefix("{{{")
+            .and_then(|text| text.strip_suffix("}}}"))
+            .ok_or_else(invalid)?;
+
+        let mut table: Vec<Vec<[usize; 3]>> = vec![];
+        for state in states.split("}},{{") {
+            let mut colors = vec![];
+            for color in state.split("},{") {
+                let numbers = color
+                    .split(',')
+                    .map(|number| number.parse::<usize>().map_err(|_| invalid()))
+                    .collect::<Result<Vec<usize>, TurmiteError>>()?;
+                match numbers.as_slice() {
+                    &[color, turn, state] => colors.push([color, turn, state]),
+                    _ => return Err(invalid()),
+                }
+            }
+            table.push(colors);
+        }
+
+        let color_count = table[0].len();
+        if !(2..=MAX_COLORS).contains(&color_count) {
+            return Err(TurmiteError::InvalidColorCount(color_count));
+        }
+        let state_count = table.len();
+        let actions = table
+            .into_iter()
+            .map(|colors| {
+                if colors.len() != color_count {
+                    return Err(invalid());
+                }
+                colors
+                    .into_iter()
+                    .map(|[color, turn, state]| {
+                        if color >= color_count || state >= state_count {
+                            return Err(invalid());
+                        }
+                        Ok(Action {
+                            color: color as u8,
+                            turn: Turn::from_number(turn).ok_or(TurmiteError::InvalidTurn(turn))?,
+                            state,
+                        })
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<Action>>, TurmiteError>>()?;
+        Ok(Turmite { actions })
+    }
+}
+
+impl FromStr for Turmite {
+    type Err = TurmiteError;
+
+    fn from_str(text: &str) -> Result<Turmite, TurmiteError> {
+        let text = text.trim();
+        if text.is_empty() {
+            Err(TurmiteError::Empty)
+        } else if text.starts_with('{') {
+            Turmite::parse_table(text)
+        } else {
+            Turmite::parse_turns(text)
+        }
+    }
+}
+
+/// Prints ants by their turns and other turmites in Golly's notation
+impl fmt::Display for Turmite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(turns) = self.turns() {
+            let letters: String = turns.into_iter().map(Turn::letter).collect();
+            return write!(f, "{}", letters);
+        }
+
+        let states: Vec<String> = self
+            .actions
+            .iter()
+            .map(|actions| {
+                let colors: Vec<String> = actions
+                    .iter()
+                    .map(|action| {
+                        format!(
+                            "{{{},{},{}}}",
+                            action.color, action.turn as usize, action.state
+                        )
+                    })
+                    .collect();
+                format!("{{{}}}", colors.join(","))
+            })
+            .collect();
+        write!(f, "{{{}}}", states.join(","))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TurmiteError {
+    Empty,
+    UnknownTurn(char),
+    InvalidTurn(usize),
+    InvalidColorCount(usize),
+    InvalidTable(String),
+}
+
+impl fmt::Display for TurmiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TurmiteError::Empty => write!(f, "Turmite is empty"),
+            TurmiteError::UnknownTurn(letter) => write!(
+                f,
+                "Unknown turn '{}', ants turn L, R, N (not at all) or U (around)",
+                letter
+            ),
+            TurmiteError::InvalidTurn(turn) => write!(
+                f,
+                "Invalid turn {}, turmites turn 1 (not at all), 2 (right), 4 (around) or 8 (left)",
+                turn
+            ),
+            TurmiteError::InvalidColorCount(colors) => write!(
+                f,
+                "Invalid number of colors {}, turmites have 2 to {}",
+                colors, MAX_COLORS
+            ),
+            TurmiteError::InvalidTable(table) => write!(
+                f,
+                "Invalid turmite \"{}\", expected {{{{{{color,turn,state}},...}},...}}",
+                table
+            ),
+        }
+    }
+}
+
+impl Error for TurmiteError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ant {
+    pub position: Position,
+    pub heading: Heading,
+    pub state: usize,
+    pub mirrored: bool, // Crossed twisted edges an odd number of times, which swaps its left and right
+}
+
+impl Ant {
+    pub fn new(position: Position, heading: Heading) -> Ant {
+        Ant {
+            position,
+            heading,
+            state: 0,
+            mirrored: false,
+        }
+    }
+}
+
+/// Ants that follow the same turmite, taking turns in the order they are listed
+#[derive(Debug, Clone)]
+pub struct Colony {
+    pub turmite: Turmite,
+    pub ants: Vec<Ant>,
+    pub steps: usize, // Steps taken by the whole colony, one per ant per turn
+}
+
+impl Colony {
+    pub fn new(turmite: Turmite) -> Colony {
+        Colony {
+            turmite,
+            ants: vec![],
+            steps: 0,
+        }
+    }
+
+    /// Moves every ant once. Bounded grids take their topology, width and height, and ants that walk off a dead edge
+    /// are gone. Returns the cells that were painted, with the states they were in before
+    pub fn step(
+        &mut self,
+        engine: &mut dyn Engine,
+        bounds: Option<(Topology, usize, usize)>,
+    ) -> Vec<(Position, CellState, CellState)> {
+        let mut changes = Vec::with_capacity(self.ants.len());
+        let turmite = &self.turmite;
+        self.ants.retain_mut(|ant| {
+            let state = engine.get(ant.position);
+            let action = turmite.action(ant.state, state.index());
+            let painted = CellState::from_index(action.color);
+            engine.set(ant.position, painted);
+            changes.push((ant.position, painted, state));
+
+            let turn = match (action.turn, ant.mirrored) {
+                (Turn::Right, true) => Turn::Left,
+                (Turn::Left, true) => Turn::Right,
+                (turn, _) => turn,
+            };
+            ant.heading = ant.heading.turned(turn);
+            ant.state = action.state;
+            ant.position = ant.position + ant.heading.offset();
+
+            match bounds {
+                None => true,
+                Some((topology, width, height)) => {
+                    let crosses_side = !(0..width as isize).contains(&ant.position.x);
+                    let crosses_end = !(0..height as isize).contains(&ant.position.y);
+                    match topology.wrap(ant.position, width, height) {
+                        None => false,
+                        Some(position) => {
+                            ant.position = position;
+                            // Twisted edges turn the grid upside down or around on the other side
+                            for (crosses, edge, vertically) in [
+                                (crosses_side, topology.horizontal, true),
+                                (crosses_end, topology.vertical, false),
+                            ] {
+                                if crosses && edge == Edge::Twisted {
+                                    ant.heading = ant.heading.mirrored(vertically);
+                                    ant.mirrored = !ant.mirrored;
+                                }
+                            }
+                            true
+                        }
+                    }
+                }
+            }
+        });
+        self.steps += changes.len();
+        changes
+    }
+
+    /// Moves the ants several times. Returns the cells whose state differs from before, and the states they were in
+    pub fn advance(
+        &mut self,
+        engine: &mut dyn Engine,
+        bounds: Option<(Topology, usize, usize)>,
+        turns: usize,
+    ) -> (Vec<(Position, CellState)>, Vec<CellState>) {
+        let mut original_states = HashMap::new();
+        for _ in 0..turns {
+            if self.ants.is_empty() {
+                break;
+            }
+            for (position, _, previous_state) in self.step(engine, bounds) {
+                original_states.entry(position).or_insert(previous_state);
+            }
+        }
+
+        let mut changes: Vec<(Position, CellState, CellState)> = original_states
+            .into_iter()
+            .map(|(position, original_state)| (position, engine.get(position), original_state))
+            .filter(|(_, state, original_state)| state != original_state)
+            .collect();
+        changes.sort_by_key(|(position, _, _)| (position.y, position.x));
+        changes
+            .into_iter()
+            .map(|(position, state, original_state)| ((position, state), original_state))
+            .unzip()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparse::SparseGrid;
+
+    #[test]
+    fn reads_and_writes_turmites() {
+        let langtons_ant: Turmite = "RL".parse().unwrap();
+        assert_eq!((langtons_ant.colors(), langtons_ant.states()), (2, 1));
+        assert_eq!(langtons_ant.to_string(), "RL");
+        assert_eq!("rl".parse::<Turmite>().unwrap(), langtons_ant);
+
+        // Golly's notation for an ant is written back as its turns
+        let table: Turmite = "{{{1, 2, 0}, {0, 8, 0}}}".parse().unwrap();
+        assert_eq!(table, langtons_ant);
+
+        let fibonacci = "{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}";
+        let turmite: Turmite = fibonacci.parse().unwrap();
+        assert_eq!((turmite.colors(), turmite.states()), (2, 2));
+        assert_eq!(
+            turmite.action(1, 1),
+            Action {
+                color: 0,
+                turn: Turn::None,
+                state: 0
+            }
+        );
+        assert_eq!(turmite.to_string(), fibonacci);
+    }
+
+    #[test]
+    fn rejects_invalid_turmites() {
+        let error = |text: &str| text.parse::<Turmite>().unwrap_err();
+        assert_eq!(error(" "), TurmiteError::Empty);
+        assert_eq!(error("RX"), TurmiteError::UnknownTurn('X'));
+        assert_eq!(error("R"), TurmiteError::InvalidColorCount(1));
+        assert_eq!(
+            error(&"L".repeat(MAX_COLORS + 1)),
+            TurmiteError::InvalidColorCount(MAX_COLORS + 1)
+        );
+        assert_eq!(error("{{{1,3,0},{0,8,0}}}"), TurmiteError::InvalidTurn(3));
+
+        // Colors and states past the end of the table
+        for table in &["{{{1,2,0},{2,8,0}}}", "{{{1,2,1},{0,8,0}}}", "{{1,2,0}}"] {
+            assert_eq!(error(table), TurmiteError::InvalidTable(table.to_string()));
+        }
+    }
+
+    #[test]
+    fn langtons_ant_builds_a_highway() {
+        let mut grid = SparseGrid::new();
+        let mut colony = Colony::new("RL".parse().unwrap());
+        colony
+            .ants
+            .push(Ant::new(Position { x: 0, y: 0 }, Heading::North));
+        colony.advance(&mut grid, None, 11_000);
+        assert_eq!(colony.steps, 11_000);
+
+        // The highway repeats every 104 steps, two cells further along a diagonal
+        let start = colony.ants[0];
+        colony.advance(&mut grid, None, 104);
+        let ant = colony.ants[0];
+        assert_eq!(ant.heading, start.heading);
+        assert_eq!(
+            (
+                (ant.position.x - start.position.x).abs(),
+                (ant.position.y - start.position.y).abs()
+            ),
+            (2, 2)
+        );
+    }
+
+    #[test]
+    fn twisted_edges_mirror_ants() {
+        let klein_bottle = Topology {
+            horizontal: Edge::Twisted,
+            vertical: Edge::Dead,
+        };
+        let bounds = Some((klein_bottle, 4, 4));
+        let mut grid = SparseGrid::new();
+        let mut colony = Colony::new("RN".parse().unwrap());
+        colony
+            .ants
+            .push(Ant::new(Position { x: 3, y: 0 }, Heading::East));
+        grid.set(Position { x: 3, y: 0 }, CellState::Alive);
+
+        // Off the right edge and back in upside down on the left
+        colony.step(&mut grid, bounds);
+        assert_eq!(colony.ants[0].position, Position { x: 0, y: 3 });
+        assert_eq!(colony.ants[0].heading, Heading::East);
+        assert!(colony.ants[0].mirrored);
+
+        // Right turns are left turns in the mirror image
+        let changes = colony.step(&mut grid, bounds);
+        assert_eq!(
+            changes,
+            vec![(Position { x: 0, y: 3 }, CellState::Alive, CellState::Dead)]
+        );
+        assert_eq!(colony.ants[0].heading, Heading::North);
+
+        // Live cells keep the ant going straight, off the dead top edge for good
+        for y in 0..3 {
+            grid.set(Position { x: 0, y }, CellState::Alive);
+        }
+        for _ in 0..3 {
+            colony.step(&mut grid, bounds);
+        }
+        assert!(colony.ants.is_empty());
+        assert_eq!(colony.steps, 5);
+    }
+}
@@ -1,6 +1,3 @@
-use anyhow::Result;
-use crossbeam_channel::{self, TrySendError};
-
 #[derive(Clone)]
 pub struct ThreadChannel<T> {
     pub sender: crossbeam_channel::Sender<T>,
@@ -25,8 +22,10 @@ impl<T> ThreadChannel<T> {
         (a, b)
     }
 
-    pub fn send(&self, message: T) -> Result<(), TrySendError<T>> {
-        self.sender.try_send(message)
+    /// The channels are unbounded, so sending only fails once the other thread has exited.
+    /// Nobody is left to receive the message then, so it is dropped.
+    pub fn send(&self, message: T) {
+        let _ = self.sender.try_send(message);
     }
 
     pub fn receive(&self) -> Vec<T> {